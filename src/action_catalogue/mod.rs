@@ -0,0 +1,346 @@
+use crate::plot_structures::ActionPlotPoint;
+use crate::processor_error::ProcessorError;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::borrow::Borrow;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+
+const DEFAULT_CATALOGUE: &str = include_str!("default_catalogue.toml");
+
+lazy_static! {
+    static ref BUILTIN: Arc<ActionCatalogue> = Arc::new(ActionCatalogue::from_toml_str(DEFAULT_CATALOGUE).expect("built-in catalogue is valid"));
+}
+
+/// Role of an action in the ACLS summary and protocol checks, whatever the scenario calls it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    Shock,
+    Medication,
+    PulseCheck,
+}
+
+/// Canonical description of a simulator action.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogueEntry {
+    pub name: String,
+    /// Other `SubAction Name` spellings of the action.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Defaults to the name of the action.
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Plotly marker symbol of the category.
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub dose_unit: Option<String>,
+    #[serde(default)]
+    pub kind: Option<ActionKind>,
+    /// Drug given by a medication, e.g. `epinephrine`.
+    #[serde(default)]
+    pub drug: Option<String>
+}
+
+impl CatalogueEntry {
+    pub fn category(&self) -> &str {
+        self.category.as_deref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Deserialize)]
+struct CatalogueFile {
+    #[serde(default)]
+    action: Vec<CatalogueEntry>,
+}
+
+/// Maps raw `SubAction Name` values to canonical actions. Names and aliases are matched ignoring case.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionCatalogue {
+    entries: Vec<CatalogueEntry>,
+    index: HashMap<String, usize>,
+}
+
+impl Default for ActionCatalogue {
+    fn default() -> Self {
+        ActionCatalogue::clone(&BUILTIN)
+    }
+}
+
+impl ActionCatalogue {
+    pub fn new(entries: Vec<CatalogueEntry>) -> Self {
+        let mut index = HashMap::new();
+        for (entry_idx, entry) in entries.iter().enumerate() {
+            for name in std::iter::once(&entry.name).chain(&entry.aliases) {
+                index.entry(name.to_lowercase()).or_insert(entry_idx);
+            }
+        }
+        Self { entries, index }
+    }
+
+    /// The catalogue the processor uses when none is given, shared instead of parsed again.
+    pub fn builtin() -> Arc<ActionCatalogue> {
+        BUILTIN.clone()
+    }
+
+    pub fn from_toml_str(toml: &str) -> Result<Self, ProcessorError> {
+        toml::from_str::<CatalogueFile>(toml)
+            .map(|file| Self::new(file.action))
+            .map_err(|e| ProcessorError::InvalidCatalogue { message: e.to_string() })
+    }
+
+    /// A json catalogue is an object with an `action` array, like the tables of the toml format.
+    pub fn from_json_str(json: &str) -> Result<Self, ProcessorError> {
+        serde_json::from_str::<CatalogueFile>(json)
+            .map(|file| Self::new(file.action))
+            .map_err(|e| ProcessorError::InvalidCatalogue { message: e.to_string() })
+    }
+
+    /// Loads a `.json` catalogue, any other file is read as toml.
+    pub fn load(path: &Path) -> Result<Self, ProcessorError> {
+        let content = std::fs::read_to_string(path).map_err(|e| ProcessorError::Io { message: format!("{}: {}", path.display(), e) })?;
+        let catalogue = match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => Self::from_json_str(&content),
+            _ => Self::from_toml_str(&content),
+        };
+        catalogue.map_err(|e| match e {
+            ProcessorError::InvalidCatalogue { message } => ProcessorError::InvalidCatalogue { message: format!("{}: {}", path.display(), message) },
+            e => e,
+        })
+    }
+
+    pub fn entries(&self) -> &[CatalogueEntry] {
+        &self.entries
+    }
+
+    pub fn lookup(&self, action_name: &str) -> Option<&CatalogueEntry> {
+        self.index.get(&action_name.to_lowercase()).map(|&entry_idx| &self.entries[entry_idx])
+    }
+
+    /// Entry of a performed action, whose name ends with the joules of a shock.
+    pub fn performed(&self, action_name: &str, shock_value: &str) -> Option<&CatalogueEntry> {
+        self.lookup(performed_name(action_name, shock_value))
+    }
+
+    pub fn is_kind(&self, action_name: &str, shock_value: &str, kind: ActionKind) -> bool {
+        self.performed(action_name, shock_value).is_some_and(|entry| entry.kind == Some(kind))
+    }
+
+    /// Whether the action is a medication giving `drug`, drugs are matched ignoring case.
+    pub fn gives_drug(&self, action_name: &str, shock_value: &str, drug: &str) -> bool {
+        self.performed(action_name, shock_value).is_some_and(|entry| {
+            entry.kind == Some(ActionKind::Medication) && entry.drug.as_deref().is_some_and(|entry_drug| entry_drug.eq_ignore_ascii_case(drug))
+        })
+    }
+
+    /// Names of the performed actions that are not in the catalogue, each listed once. These are still plotted
+    /// under their own name, a log of a scenario the catalogue does not cover yet is not invalid.
+    pub fn unknown_actions<I>(&self, points: I) -> BTreeSet<String>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<ActionPlotPoint>,
+    {
+        points.into_iter()
+            .filter_map(|point| match point.borrow() {
+                ActionPlotPoint::Action(action) => Some(self.unknown_name(&action.name, &action.shock_value)),
+                ActionPlotPoint::Error(error) => Some(self.unknown_name(&error.name, &error.shock_value)),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    fn unknown_name(&self, action_name: &str, shock_value: &str) -> Option<String> {
+        let name = performed_name(action_name, shock_value);
+        self.lookup(name).is_none().then(|| name.to_owned())
+    }
+
+    /// Style of the first entry of the category that has one.
+    pub fn category_style(&self, category: &str) -> (Option<&str>, Option<&str>) {
+        let entries = || self.entries.iter().filter(|entry| entry.category() == category);
+        (
+            entries().find_map(|entry| entry.icon.as_deref()),
+            entries().find_map(|entry| entry.color.as_deref()),
+        )
+    }
+}
+
+/// The joules of a shock are appended to the catalogue name of the action.
+fn performed_name<'a>(action_name: &'a str, shock_value: &str) -> &'a str {
+    action_name.strip_suffix(shock_value).unwrap_or(action_name).trim_end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENARIO_TOML: &str = r#"
+        [[action]]
+        name = "Give Aspirin"
+        aliases = ["Aspirin 325mg"]
+        category = "Medication"
+        icon = "triangle-up"
+        color = "green"
+        dose_unit = "mg"
+
+        [[action]]
+        name = "Pulse Check"
+    "#;
+
+    const KINDS_TOML: &str = r#"
+        [[action]]
+        name = "Cardiovert"
+        kind = "shock"
+
+        [[action]]
+        name = "Push Adrenaline"
+        kind = "medication"
+        drug = "Epinephrine"
+
+        [[action]]
+        name = "Epinephrine Guidelines"
+        drug = "epinephrine"
+    "#;
+
+    mod lookup {
+        use super::*;
+
+        #[test]
+        fn alias_ignoring_case() {
+            let catalogue = ActionCatalogue::from_toml_str(SCENARIO_TOML).unwrap();
+
+            let entry = catalogue.lookup("ASPIRIN 325MG").unwrap();
+
+            assert_eq!(entry.name, "Give Aspirin");
+            assert_eq!(entry.category(), "Medication");
+            assert_eq!(entry.dose_unit.as_deref(), Some("mg"));
+        }
+
+        #[test]
+        fn category_defaults_to_name() {
+            let catalogue = ActionCatalogue::from_toml_str(SCENARIO_TOML).unwrap();
+
+            assert_eq!(catalogue.lookup("Pulse Check").unwrap().category(), "Pulse Check");
+        }
+
+        #[test]
+        fn unknown_action() {
+            let catalogue = ActionCatalogue::from_toml_str(SCENARIO_TOML).unwrap();
+
+            assert_eq!(catalogue.lookup("Select Epinephrine"), None);
+        }
+
+        #[test]
+        fn builtin_spelling_fix() {
+            let catalogue = ActionCatalogue::default();
+
+            assert_eq!(catalogue.lookup("Ascultate Lungs").unwrap().name, "Auscultate Lungs");
+            assert_eq!(catalogue.lookup("Select Amiodarone").unwrap().category(), "Medication");
+        }
+    }
+
+    mod from_json_str {
+        use super::*;
+
+        #[test]
+        fn same_as_toml() {
+            let json = r#"{"action": [
+                {"name": "Give Aspirin", "aliases": ["Aspirin 325mg"], "category": "Medication", "icon": "triangle-up", "color": "green", "dose_unit": "mg"},
+                {"name": "Pulse Check"}
+            ]}"#;
+
+            assert_eq!(ActionCatalogue::from_json_str(json).unwrap(), ActionCatalogue::from_toml_str(SCENARIO_TOML).unwrap());
+        }
+
+        #[test]
+        fn invalid() {
+            let result = ActionCatalogue::from_json_str(r#"{"action": [{"category": "Medication"}]}"#);
+
+            assert!(matches!(result, Err(ProcessorError::InvalidCatalogue { .. })));
+        }
+    }
+
+    mod unknown_actions {
+        use super::*;
+        use crate::plot_structures::{Action, PlotLocation};
+
+        fn action(name: &str, shock_value: &str) -> ActionPlotPoint {
+            ActionPlotPoint::Action(Action {
+                location: PlotLocation::default(),
+                name: name.to_owned(),
+                action_category: name.to_owned(),
+                shock_value: shock_value.to_owned(),
+                username: "umich1".to_owned(),
+            })
+        }
+
+        #[test]
+        fn deduplicated() {
+            let catalogue = ActionCatalogue::from_toml_str(SCENARIO_TOML).unwrap();
+            let points = vec![action("Give Aspirin", ""), action("Select Epinephrine", ""), action("Select Epinephrine", "")];
+
+            assert_eq!(catalogue.unknown_actions(&points), BTreeSet::from(["Select Epinephrine".to_owned()]));
+        }
+
+        #[test]
+        fn shock_value_ignored() {
+            let catalogue = ActionCatalogue::default();
+
+            assert!(catalogue.unknown_actions([action("Defib (Unsynchronized Shock) 200J", "200J")]).is_empty());
+        }
+    }
+
+    mod kinds {
+        use super::*;
+
+        #[test]
+        fn shock_with_joules() {
+            let catalogue = ActionCatalogue::from_toml_str(KINDS_TOML).unwrap();
+
+            assert!(catalogue.is_kind("Cardiovert 100J", "100J", ActionKind::Shock));
+            assert!(!catalogue.is_kind("Push Adrenaline", "", ActionKind::Shock));
+        }
+
+        #[test]
+        fn drug_ignoring_case() {
+            let catalogue = ActionCatalogue::from_toml_str(KINDS_TOML).unwrap();
+
+            assert!(catalogue.gives_drug("Push Adrenaline", "", "epinephrine"));
+            assert!(!catalogue.gives_drug("Push Adrenaline", "", "amiodarone"));
+        }
+
+        #[test]
+        fn drug_requires_medication() {
+            let catalogue = ActionCatalogue::from_toml_str(KINDS_TOML).unwrap();
+
+            assert!(!catalogue.gives_drug("Epinephrine Guidelines", "", "epinephrine"));
+        }
+
+        #[test]
+        fn builtin_kinds() {
+            let catalogue = ActionCatalogue::default();
+
+            assert!(catalogue.is_kind("Defib (Unsynchronized Shock) 200J", "200J", ActionKind::Shock));
+            assert!(catalogue.is_kind("Synchronized Shock 100J", "100J", ActionKind::Shock));
+            assert!(catalogue.is_kind("Pulse Check", "", ActionKind::PulseCheck));
+            assert!(catalogue.gives_drug("Select Epinephrine", "", "epinephrine"));
+            assert!(catalogue.gives_drug("Select Amiodarone", "", "amiodarone"));
+        }
+    }
+
+    mod category_style {
+        use super::*;
+
+        #[test]
+        fn styled_category() {
+            let catalogue = ActionCatalogue::from_toml_str(SCENARIO_TOML).unwrap();
+
+            assert_eq!(catalogue.category_style("Medication"), (Some("triangle-up"), Some("green")));
+            assert_eq!(catalogue.category_style("Pulse Check"), (None, None));
+        }
+    }
+}
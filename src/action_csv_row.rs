@@ -1,11 +1,15 @@
-use crate::detection::{cpr_boundary, is_action_row, is_missed_action};
-use crate::parsing::{extract_stage_name, parse_time, process_action_name};
-use crate::plot_structures::CsvRowTime;
+use crate::detection::{cpr_boundary, is_action_row, is_rule_evaluation, is_speech_row};
+use crate::parsing::{extract_stage_name, parse_time, parse_vital_sign, parse_waveform_channel, process_action_name};
+use crate::plot_structures::{CsvRowTime, VitalSign, WaveformChannel};
+use crate::rule_csv_row::RuleCsvRow;
+use crate::processor_config::ProcessorConfig;
+use crate::session_date::SessionDate;
 // This lets us write `#[derive(Deserialize)]`.
 use serde::{Deserialize, Deserializer};
 use std::fmt::{Display, Formatter};
 /*
  * Used by serde macros to deserialize a non-empty string from a CSV file.
+ * The time is anchored to the session date in `post_deserialize`.
  */
 fn non_empty_string<'de, D>(deserializer: D) -> Result<Option<CsvRowTime>, D::Error>
 where
@@ -13,7 +17,7 @@ where
 {
     let value: Option<String> = Option::deserialize(deserializer)?;
     match value {
-        Some(s) if !s.trim().is_empty() => Ok(parse_time(&s[..])),
+        Some(s) if !s.trim().is_empty() => Ok(parse_time(&s[..], &SessionDate::default())),
         _ => Err(serde::de::Error::custom("Field cannot be empty")),
     }
 }
@@ -60,16 +64,29 @@ pub struct ActionCsvRow {
     #[serde(skip)]
     pub shock_value: String,
     #[serde(skip)]
+    pub known_action: bool,
+    #[serde(skip)]
     pub action_point: bool,
     #[serde(skip)]
-    pub cpr_boundary: Option<String>
+    pub speech_point: bool,
+    #[serde(skip)]
+    pub cpr_boundary: Option<String>,
+    #[serde(skip)]
+    pub vital_sign: Option<VitalSign>,
+    #[serde(skip)]
+    pub waveform_channel: Option<WaveformChannel>,
+    #[serde(skip)]
+    pub rule: Option<RuleCsvRow>,
+    /// Csv line number of the row.
+    #[serde(skip)]
+    pub row_number: usize
 }
 
 impl Display for ActionCsvRow {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "ActionCsvRow {{ timestamp: {:?}, action_vital_name: {:?}, subaction_time: {:?}, subaction_name: {:?}, score: {:?}, old_value: {:?}, new_value: {:?}, username: {:?}, speech_command: {:?}, parsed_stage: {:?}, action_name: {:?}, action_category: {:?}, shock_value: {:?}, action_point: {:?}, cpr_boundary: {:?} }}",
+            "ActionCsvRow {{ timestamp: {:?}, action_vital_name: {:?}, subaction_time: {:?}, subaction_name: {:?}, score: {:?}, old_value: {:?}, new_value: {:?}, username: {:?}, speech_command: {:?}, parsed_stage: {:?}, action_name: {:?}, action_category: {:?}, shock_value: {:?}, known_action: {:?}, action_point: {:?}, speech_point: {:?}, cpr_boundary: {:?}, vital_sign: {:?}, waveform_channel: {:?}, rule: {:?} }}",
             self.timestamp,
             self.action_vital_name,
             self.subaction_time,
@@ -83,20 +100,30 @@ impl Display for ActionCsvRow {
             self.action_name,
             self.action_category,
             self.shock_value,
+            self.known_action,
             self.action_point,
-            self.cpr_boundary
+            self.speech_point,
+            self.cpr_boundary,
+            self.vital_sign,
+            self.waveform_channel,
+            self.rule
         )
     }
 }
 
 impl ActionCsvRow {
-    pub fn post_deserialize(&mut self) {
-        self.parsed_stage = if is_missed_action(&self) {extract_stage_name(&self.username)} else { extract_stage_name(&self.action_vital_name) };
-        self.cpr_boundary = cpr_boundary(&self);
-        self.action_point = is_action_row(&self);
-        let processed_action_name = process_action_name(&self.subaction_name);
+    pub fn post_deserialize(&mut self, session_date: &SessionDate, config: &ProcessorConfig) {
+        self.timestamp = self.timestamp.as_ref().map(|time| CsvRowTime::new(time.total_seconds, session_date));
+        self.parsed_stage = if is_rule_evaluation(self) {extract_stage_name(&self.username)} else { extract_stage_name(&self.action_vital_name) };
+        self.cpr_boundary = cpr_boundary(self, config);
+        self.action_point = is_action_row(self);
+        self.speech_point = is_speech_row(self);
+        self.vital_sign = parse_vital_sign(&self.action_vital_name);
+        self.waveform_channel = parse_waveform_channel(&self.action_vital_name);
+        let processed_action_name = process_action_name(&self.subaction_name, &config.catalogue);
         self.action_name = processed_action_name.0;
         self.action_category = processed_action_name.1;
         self.shock_value = processed_action_name.2;
+        self.known_action = processed_action_name.3;
     }
 }
\ No newline at end of file
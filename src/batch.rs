@@ -0,0 +1,148 @@
+use crate::csv_processor::try_process_csv;
+use crate::input_sources::create_reader;
+use crate::plot_structures::ActionPlotPoint;
+use crate::processor_config::ProcessorConfig;
+use crate::processor_error::ProcessorError;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct SessionPoint {
+    pub session_id: String,
+    pub point: ActionPlotPoint
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct SessionError {
+    pub session_id: String,
+    pub path: String,
+    pub error: ProcessorError
+}
+
+/// Points of every session in a directory, in file name order.
+#[derive(Debug, Default, PartialEq, Clone, Serialize)]
+pub struct CohortDataset {
+    pub sessions: Vec<String>,
+    pub points: Vec<SessionPoint>,
+    /// Row level errors of sessions that were processed.
+    pub errors: Vec<SessionError>,
+    /// Sessions that could not be processed at all, e.g. unreadable files or a wrong header.
+    pub failed_sessions: Vec<SessionError>
+}
+
+enum SessionOutcome {
+    Processed(String, Vec<SessionPoint>, Vec<SessionError>),
+    Failed(SessionError),
+}
+
+/// `timeline-multiplayer-09182024.csv` is session `timeline-multiplayer-09182024`.
+pub fn session_id(path: &Path) -> String {
+    path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy().into_owned()
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv"))
+}
+
+fn process_session(path: &Path, config: &ProcessorConfig) -> SessionOutcome {
+    let session_id = session_id(path);
+    let path = path.to_string_lossy().into_owned();
+    let config = config.for_source(&path);
+    let session_error = |error| SessionError { session_id: session_id.clone(), path: path.clone(), error };
+
+    let points = match create_reader(&path).and_then(|reader| try_process_csv(reader, &config)) {
+        Ok(points) => points,
+        Err(e) => return SessionOutcome::Failed(session_error(e)),
+    };
+
+    let mut session_points = vec![];
+    let mut errors = vec![];
+    for result in points {
+        match result {
+            Ok(point) => session_points.push(SessionPoint { session_id: session_id.clone(), point }),
+            Err(e) => errors.push(session_error(e)),
+        }
+    }
+    SessionOutcome::Processed(session_id, session_points, errors)
+}
+
+/// Processes every csv file in `dir` in parallel, each session dated from its file name unless the config has a date. Only failing to list the directory is an error,
+/// a file that cannot be processed is reported in `failed_sessions` and the rest of the batch continues.
+pub fn process_directory(dir: &Path, config: &ProcessorConfig) -> Result<CohortDataset, ProcessorError> {
+    let io_error = |e: std::io::Error| ProcessorError::Io { message: format!("{}: {}", dir.display(), e) };
+    let mut paths = std::fs::read_dir(dir)
+        .map_err(io_error)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()
+        .map_err(io_error)?;
+    paths.retain(|path| is_csv(path));
+    paths.sort();
+
+    let outcomes: Vec<_> = paths.par_iter().map(|path| process_session(path, config)).collect();
+
+    let mut dataset = CohortDataset::default();
+    for outcome in outcomes {
+        match outcome {
+            SessionOutcome::Processed(session_id, points, errors) => {
+                dataset.sessions.push(session_id);
+                dataset.points.extend(points);
+                dataset.errors.extend(errors);
+            }
+            SessionOutcome::Failed(error) => dataset.failed_sessions.push(error),
+        }
+    }
+    Ok(dataset)
+}
+
+#[cfg(test)]
+mod tests {
+    mod session_id {
+        use super::super::*;
+
+        #[test]
+        fn file_stem() {
+            assert_eq!(session_id(Path::new("logs/timeline-multiplayer-09182024.csv")), "timeline-multiplayer-09182024");
+        }
+
+        #[test]
+        fn no_extension() {
+            assert_eq!(session_id(Path::new("logs/session1")), "session1");
+        }
+    }
+
+    mod process_directory {
+        use super::super::*;
+
+        fn temp_dir(name: &str) -> PathBuf {
+            let dir = std::env::temp_dir().join(format!("batch_{}_{}", std::process::id(), name));
+            std::fs::create_dir_all(&dir).unwrap();
+            dir
+        }
+
+        #[test]
+        fn bad_file_does_not_abort_batch() {
+            let dir = temp_dir("bad_file");
+            std::fs::copy("tests/data/sample.csv", dir.join("session-b.csv")).unwrap();
+            std::fs::write(dir.join("session-a.csv"), "Name,Score\nPulse Check,1\n").unwrap();
+            std::fs::write(dir.join("notes.txt"), "not a session").unwrap();
+
+            let dataset = process_directory(&dir, &ProcessorConfig::default()).unwrap();
+
+            assert_eq!(dataset.sessions, vec!["session-b"]);
+            assert!(!dataset.points.is_empty());
+            assert!(dataset.points.iter().all(|point| point.session_id == "session-b"));
+            assert_eq!(dataset.failed_sessions.len(), 1);
+            assert_eq!(dataset.failed_sessions[0].session_id, "session-a");
+            assert!(matches!(dataset.failed_sessions[0].error, ProcessorError::HeaderMismatch { .. }));
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+
+        #[test]
+        fn missing_directory() {
+            let result = process_directory(Path::new("no_such_directory"), &ProcessorConfig::default());
+
+            assert!(matches!(result, Err(ProcessorError::Io { .. })));
+        }
+    }
+}
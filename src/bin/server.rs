@@ -0,0 +1,507 @@
+use mteam_dashboard_action_processor::plot_structures::ActionPlotPoint;
+use mteam_dashboard_action_processor::plotly_figure::PlotlyFigure;
+use mteam_dashboard_action_processor::{create_reader_with, try_process_csv, HttpOptions, ProcessorConfig, ProcessorError, SessionDate};
+use serde::Serialize;
+use std::io::{Cursor, Read};
+use std::net::{IpAddr, ToSocketAddrs};
+use std::{env, thread};
+use tiny_http::{Header, Method, Request, Response, Server};
+use url::Url;
+
+const DEFAULT_PORT: u16 = 8080;
+const MAX_ROWS_TO_CHECK: usize = 5;
+const WORKER_THREADS: usize = 4;
+const MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+/// Comma separated host names that `src` may point to even when they resolve to a private address.
+const ALLOWED_HOSTS_VARIABLE: &str = "ALLOWED_SOURCE_HOSTS";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum OutputFormat {
+    Points,
+    Plotly,
+}
+
+#[derive(Serialize)]
+struct PointsResponse {
+    points: Vec<ActionPlotPoint>,
+    errors: Vec<ProcessorError>,
+}
+
+#[derive(Serialize)]
+struct PlotlyResponse {
+    figure: PlotlyFigure,
+    errors: Vec<ProcessorError>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse<'a> {
+    error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<&'a ProcessorError>,
+}
+
+#[derive(Debug, PartialEq)]
+struct HttpResponse {
+    status: u16,
+    body: String,
+}
+
+impl HttpResponse {
+    fn json<T: Serialize>(status: u16, body: &T) -> Self {
+        match serde_json::to_string(body) {
+            Ok(body) => Self { status, body },
+            Err(e) => Self::error(500, &format!("Could not serialize response: {}", e)),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            body: serde_json::to_string(&ErrorResponse { error: message.to_owned(), details: None }).unwrap_or_default(),
+        }
+    }
+
+    fn processor_error(status: u16, error: &ProcessorError) -> Self {
+        Self {
+            status,
+            body: serde_json::to_string(&ErrorResponse { error: error.to_string(), details: Some(error) }).unwrap_or_default(),
+        }
+    }
+}
+
+fn parse_query(url: &str) -> Result<(String, Vec<(String, String)>), String> {
+    let parsed = Url::parse(&format!("http://localhost{}", url)).map_err(|e| format!("Invalid request url: {}", e))?;
+    let query = parsed.query_pairs().map(|(k, v)| (k.into_owned(), v.into_owned())).collect();
+    Ok((parsed.path().to_owned(), query))
+}
+
+fn query_value<'q>(query: &'q [(String, String)], key: &str) -> Option<&'q str> {
+    query.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+fn output_format(query: &[(String, String)]) -> Result<OutputFormat, String> {
+    match query_value(query, "format") {
+        None | Some("points") => Ok(OutputFormat::Points),
+        Some("plotly") => Ok(OutputFormat::Plotly),
+        Some(other) => Err(format!("Unsupported format '{}', expected 'points' or 'plotly'", other)),
+    }
+}
+
+/// Hosts a remote `src` may be fetched from.
+#[derive(Debug, Default, Clone)]
+struct SourcePolicy {
+    allowed_hosts: Vec<String>,
+}
+
+impl SourcePolicy {
+    fn from_env() -> Self {
+        let allowed_hosts = env::var(ALLOWED_HOSTS_VARIABLE).unwrap_or_default()
+            .split(',')
+            .map(|host| host.trim().to_lowercase())
+            .filter(|host| !host.is_empty())
+            .collect();
+        Self { allowed_hosts }
+    }
+
+    /// Only http(s) urls are accepted so that the service cannot be used to read local files,
+    /// and unless the host is allowed explicitly it must not resolve to a loopback, private or link-local address
+    /// so that it cannot be used to reach the internal network of the server either.
+    /// The returned options connect to the checked addresses and do not follow redirects, which could lead anywhere.
+    fn check(&self, src: &str) -> Result<HttpOptions, HttpResponse> {
+        let url = Url::parse(src)
+            .ok()
+            .filter(|url| url.scheme() == "http" || url.scheme() == "https")
+            .ok_or_else(|| HttpResponse::error(400, "The 'src' query parameter must be an http or https url"))?;
+        let host = url.host_str().ok_or_else(|| HttpResponse::error(400, "The 'src' url has no host"))?;
+        let mut options = HttpOptions { follow_redirects: false, max_body_bytes: Some(MAX_BODY_BYTES as u64), ..Default::default() };
+        if self.allowed_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(host)) {
+            return Ok(options);
+        }
+
+        let port = url.port_or_known_default().unwrap_or(80);
+        let addresses: Vec<_> = (host.trim_start_matches('[').trim_end_matches(']'), port)
+            .to_socket_addrs()
+            .map_err(|e| HttpResponse::error(502, &format!("Could not resolve {}: {}", host, e)))?
+            .collect();
+        if let Some(address) = addresses.iter().find(|address| !is_public(address.ip())) {
+            return Err(HttpResponse::error(403, &format!("The 'src' host {} resolves to the non-public address {}", host, address.ip())));
+        }
+        options.resolved_addresses = addresses;
+        Ok(options)
+    }
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast()),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => !(ip.is_loopback() || ip.is_unspecified() || ip.is_unique_local() || ip.is_unicast_link_local()),
+        },
+    }
+}
+
+/// A source whose header cannot be read is an upstream failure, a header that can be read but is wrong is the client's.
+fn source_error_status(error: &ProcessorError) -> u16 {
+    match error {
+        ProcessorError::Io { .. } | ProcessorError::Request { .. } | ProcessorError::HttpStatus { .. } => 502,
+        _ => 422,
+    }
+}
+
+fn process_to_response<R: Read>(reader: R, format: OutputFormat, session_date: SessionDate) -> HttpResponse {
+    let config = ProcessorConfig::builder().max_rows_to_check(MAX_ROWS_TO_CHECK).session_date(session_date).build();
+    let results = match try_process_csv(reader, &config) {
+        Ok(results) => results,
+        Err(e) => return HttpResponse::processor_error(source_error_status(&e), &e),
+    };
+
+    let mut points = vec![];
+    let mut errors = vec![];
+    for result in results {
+        match result {
+            Ok(point) => points.push(point),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    match format {
+        OutputFormat::Points => HttpResponse::json(200, &PointsResponse { points, errors }),
+        OutputFormat::Plotly => HttpResponse::json(200, &PlotlyResponse { figure: PlotlyFigure::from_points(points), errors }),
+    }
+}
+
+fn handle_timeline(policy: &SourcePolicy, method: &Method, query: &[(String, String)], body: Vec<u8>) -> HttpResponse {
+    let format = match output_format(query) {
+        Ok(format) => format,
+        Err(e) => return HttpResponse::error(400, &e),
+    };
+
+    match method {
+        Method::Get => {
+            let Some(src) = query_value(query, "src") else {
+                return HttpResponse::error(400, "Missing 'src' query parameter");
+            };
+            let http_options = match policy.check(src) {
+                Ok(http_options) => http_options,
+                Err(response) => return response,
+            };
+            let session_date = match session_date(query, Some(src)) {
+                Ok(session_date) => session_date,
+                Err(e) => return HttpResponse::error(400, &e),
+            };
+            match create_reader_with(src, &http_options) {
+                Ok(reader) => process_to_response(reader, format, session_date),
+                Err(e) => HttpResponse::processor_error(502, &e),
+            }
+        }
+        Method::Post => {
+            if body.is_empty() {
+                return HttpResponse::error(400, "Request body must contain the csv data");
+            }
+            match session_date(query, None) {
+                Ok(session_date) => process_to_response(Cursor::new(body), format, session_date),
+                Err(e) => HttpResponse::error(400, &e),
+            }
+        }
+        _ => HttpResponse::error(405, "Only GET and POST are supported"),
+    }
+}
+
+/// An explicit `date` wins over a date in the name of the source.
+fn session_date(query: &[(String, String)], src: Option<&str>) -> Result<SessionDate, String> {
+    match query_value(query, "date") {
+        Some(date) => date.parse(),
+        None => Ok(src.and_then(SessionDate::from_source_name).unwrap_or_default()),
+    }
+}
+
+fn route(policy: &SourcePolicy, method: &Method, url: &str, body: Vec<u8>) -> HttpResponse {
+    let (path, query) = match parse_query(url) {
+        Ok(parsed) => parsed,
+        Err(e) => return HttpResponse::error(400, &e),
+    };
+
+    match path.as_str() {
+        "/health" => match method {
+            Method::Get => HttpResponse { status: 200, body: "{\"status\":\"ok\"}".to_owned() },
+            _ => HttpResponse::error(405, "Only GET is supported"),
+        },
+        "/timeline" => handle_timeline(policy, method, &query, body),
+        _ => HttpResponse::error(404, "Not found"),
+    }
+}
+
+/// Reads at most `MAX_BODY_BYTES`, a larger body is rejected before or while it is read.
+fn read_body<R: Read>(reader: R, body_length: Option<usize>) -> Result<Vec<u8>, HttpResponse> {
+    let too_large = || HttpResponse::error(413, &format!("Request body is larger than {} bytes", MAX_BODY_BYTES));
+    if body_length.is_some_and(|body_length| body_length > MAX_BODY_BYTES) {
+        return Err(too_large());
+    }
+    let mut body = vec![];
+    reader.take(MAX_BODY_BYTES as u64 + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| HttpResponse::error(400, &format!("Could not read request body: {}", e)))?;
+    if body.len() > MAX_BODY_BYTES {
+        return Err(too_large());
+    }
+    Ok(body)
+}
+
+fn respond(policy: &SourcePolicy, mut request: Request) {
+    let body_length = request.body_length();
+    let response = match read_body(request.as_reader(), body_length) {
+        Ok(body) => route(policy, request.method(), request.url(), body),
+        Err(response) => response,
+    };
+
+    let content_type = Header::from_bytes("Content-Type", "application/json").expect("static header is valid");
+    let http_response = Response::from_string(response.body)
+        .with_status_code(response.status)
+        .with_header(content_type);
+    if let Err(e) = request.respond(http_response) {
+        eprintln!("Could not send response: {}", e);
+    }
+}
+
+fn main() {
+    let port = env::args()
+        .nth(1)
+        .or_else(|| env::var("PORT").ok())
+        .map(|port| port.parse::<u16>().expect("Port must be a number between 0 and 65535"))
+        .unwrap_or(DEFAULT_PORT);
+
+    let server = Server::http(("0.0.0.0", port)).unwrap_or_else(|e| panic!("Could not start server on port {}: {}", port, e));
+    println!("Listening on http://0.0.0.0:{}", port);
+
+    let server = std::sync::Arc::new(server);
+    let policy = std::sync::Arc::new(SourcePolicy::from_env());
+    let workers: Vec<_> = (0..WORKER_THREADS)
+        .map(|_| {
+            let server = server.clone();
+            let policy = policy.clone();
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    respond(&policy, request);
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_CSV: &str = include_str!("../../tests/data/sample.csv");
+
+    /// The mock upstream servers listen on the loopback address.
+    fn local_upstream() -> SourcePolicy {
+        SourcePolicy { allowed_hosts: vec!["127.0.0.1".to_owned()] }
+    }
+
+    mod route {
+        use super::*;
+
+        #[test]
+        fn health() {
+            let response = route(&SourcePolicy::default(), &Method::Get, "/health", vec![]);
+
+            assert_eq!(response, HttpResponse { status: 200, body: "{\"status\":\"ok\"}".to_owned() });
+        }
+
+        #[test]
+        fn unknown_path() {
+            assert_eq!(route(&SourcePolicy::default(), &Method::Get, "/unknown", vec![]).status, 404);
+        }
+
+        #[test]
+        fn unsupported_method() {
+            assert_eq!(route(&SourcePolicy::default(), &Method::Delete, "/timeline", vec![]).status, 405);
+        }
+
+        #[test]
+        fn missing_src() {
+            assert_eq!(route(&SourcePolicy::default(), &Method::Get, "/timeline", vec![]).status, 400);
+        }
+
+        #[test]
+        fn local_file_src_rejected() {
+            assert_eq!(route(&SourcePolicy::default(), &Method::Get, "/timeline?src=tests/data/sample.csv", vec![]).status, 400);
+        }
+
+        #[test]
+        fn loopback_src_rejected() {
+            let response = route(&SourcePolicy::default(), &Method::Get, "/timeline?src=http://127.0.0.1/sample.csv", vec![]);
+
+            assert_eq!(response.status, 403);
+        }
+
+        #[test]
+        fn internal_src_rejected() {
+            for src in ["http://169.254.169.254/latest/meta-data/", "http://10.0.0.1/sample.csv", "http://[::1]:8080/sample.csv", "http://localhost/sample.csv"] {
+                assert_eq!(route(&SourcePolicy::default(), &Method::Get, &format!("/timeline?src={}", src), vec![]).status, 403, "{}", src);
+            }
+        }
+
+        #[test]
+        fn unknown_format() {
+            assert_eq!(route(&SourcePolicy::default(), &Method::Post, "/timeline?format=xml", SAMPLE_CSV.into()).status, 400);
+        }
+
+        #[test]
+        fn empty_body() {
+            assert_eq!(route(&SourcePolicy::default(), &Method::Post, "/timeline", vec![]).status, 400);
+        }
+
+        #[test]
+        fn invalid_header() {
+            let response = route(&SourcePolicy::default(), &Method::Post, "/timeline", b"a,b,c\n1,2,3\n".to_vec());
+
+            assert_eq!(response.status, 422);
+            let json: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+            assert_eq!(json["details"]["kind"], "HeaderMismatch");
+            assert_eq!(json["details"]["actual"], serde_json::json!(["a", "b", "c"]));
+        }
+
+        #[test]
+        fn posted_csv_points() {
+            let response = route(&SourcePolicy::default(), &Method::Post, "/timeline", SAMPLE_CSV.into());
+
+            assert_eq!(response.status, 200);
+            let json: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+            assert!(!json["points"].as_array().unwrap().is_empty());
+            assert!(json["errors"].as_array().unwrap().is_empty());
+        }
+
+        #[test]
+        fn posted_csv_session_date() {
+            let response = route(&SourcePolicy::default(), &Method::Post, "/timeline?date=2024-09-18", SAMPLE_CSV.into());
+
+            assert_eq!(response.status, 200);
+            let json: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+            let first_point = json["points"][0].as_object().unwrap().values().next().unwrap();
+            assert_eq!(first_point["location"]["timestamp"]["datetime"], "2024-09-18T00:00:02Z");
+        }
+
+        #[test]
+        fn invalid_session_date() {
+            assert_eq!(route(&SourcePolicy::default(), &Method::Post, "/timeline?date=09-18-2024", SAMPLE_CSV.into()).status, 400);
+        }
+
+        #[test]
+        fn posted_csv_plotly() {
+            let response = route(&SourcePolicy::default(), &Method::Post, "/timeline?format=plotly", SAMPLE_CSV.into());
+
+            assert_eq!(response.status, 200);
+            let json: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+            assert!(!json["figure"]["data"].as_array().unwrap().is_empty());
+            assert!(!json["figure"]["layout"]["shapes"].as_array().unwrap().is_empty());
+        }
+
+        #[test]
+        fn upstream_failure() {
+            let mut server = mockito::Server::new();
+            let mock = server.mock("GET", "/sample.csv").with_status(404).create();
+
+            let response = route(&local_upstream(), &Method::Get, &format!("/timeline?src={}/sample.csv", server.url()), vec![]);
+
+            mock.assert();
+            assert_eq!(response.status, 502);
+            let json: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+            assert_eq!(json["details"]["kind"], "HttpStatus");
+            assert_eq!(json["details"]["status"], 404);
+        }
+
+        #[test]
+        fn upstream_redirect_not_followed() {
+            let mut server = mockito::Server::new();
+            let mock = server.mock("GET", "/sample.csv").with_status(302).with_header("location", "http://169.254.169.254/latest/meta-data/").create();
+
+            let response = route(&local_upstream(), &Method::Get, &format!("/timeline?src={}/sample.csv", server.url()), vec![]);
+
+            mock.assert();
+            assert_eq!(response.status, 502);
+            let json: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+            assert_eq!(json["details"]["status"], 302);
+        }
+
+        #[test]
+        fn upstream_csv() {
+            let mut server = mockito::Server::new();
+            let mock = server.mock("GET", "/timeline-multiplayer-09182024.csv").with_status(200).with_body(SAMPLE_CSV).create();
+
+            let response = route(&local_upstream(), &Method::Get, &format!("/timeline?src={}/timeline-multiplayer-09182024.csv", server.url()), vec![]);
+
+            mock.assert();
+            assert_eq!(response.status, 200);
+            // The session date comes from the file name of the source.
+            let json: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+            let first_point = json["points"][0].as_object().unwrap().values().next().unwrap();
+            assert_eq!(first_point["location"]["timestamp"]["date_string"], "2024-09-18 00:00:02");
+        }
+    }
+
+    mod source_policy {
+        use super::*;
+
+        #[test]
+        fn checked_address_pinned() {
+            let options = SourcePolicy::default().check("http://93.184.216.34:8080/sample.csv").unwrap();
+
+            assert_eq!(options.resolved_addresses, vec!["93.184.216.34:8080".parse().unwrap()]);
+            assert!(!options.follow_redirects);
+            assert_eq!(options.max_body_bytes, Some(MAX_BODY_BYTES as u64));
+        }
+
+        #[test]
+        fn allowed_host_not_redirected() {
+            let options = local_upstream().check("http://127.0.0.1:8080/sample.csv").unwrap();
+
+            assert!(options.resolved_addresses.is_empty());
+            assert!(!options.follow_redirects);
+        }
+    }
+
+    mod read_body {
+        use super::*;
+
+        #[test]
+        fn declared_length_too_large() {
+            let result = read_body(Cursor::new(vec![]), Some(MAX_BODY_BYTES + 1));
+
+            assert_eq!(result.unwrap_err().status, 413);
+        }
+
+        #[test]
+        fn body_too_large() {
+            let result = read_body(std::io::repeat(b'a'), None);
+
+            assert_eq!(result.unwrap_err().status, 413);
+        }
+
+        #[test]
+        fn body_within_limit() {
+            assert_eq!(read_body(Cursor::new(b"a,b".to_vec()), Some(3)).unwrap(), b"a,b");
+        }
+    }
+
+    mod source_error_status {
+        use super::*;
+
+        #[test]
+        fn upstream_failures() {
+            assert_eq!(source_error_status(&ProcessorError::Io { message: "connection reset".to_owned() }), 502);
+            assert_eq!(source_error_status(&ProcessorError::Request { url: "http://example.com".to_owned(), message: "timeout".to_owned() }), 502);
+        }
+
+        #[test]
+        fn header_mismatch() {
+            assert_eq!(source_error_status(&ProcessorError::HeaderMismatch { expected: vec![], actual: vec![] }), 422);
+        }
+    }
+}
@@ -0,0 +1,407 @@
+use crate::action_catalogue::{ActionCatalogue, ActionKind};
+use crate::detection::{AMIODARONE, EPINEPHRINE};
+use crate::plot_structures::{ActionPlotPoint, PeriodType, PlotLocation, ProtocolDeviation, ProtocolRule};
+use crate::processor_error::ProcessorError;
+use std::borrow::Borrow;
+use std::sync::Arc;
+
+const EPINEPHRINE_MIN_INTERVAL: u32 = 180;
+const EPINEPHRINE_MAX_INTERVAL: u32 = 300;
+const SHOCKS_BEFORE_AMIODARONE: usize = 3;
+const AMIODARONE_MAX_DOSES: usize = 2;
+const CPR_AFTER_SHOCK: u32 = 120;
+const MAX_COMPRESSION_PAUSE: u32 = 10;
+
+#[derive(Debug, Clone)]
+struct PerformedAction {
+    location: PlotLocation,
+    name: String,
+}
+
+impl PerformedAction {
+    fn seconds(&self) -> u32 {
+        self.location.timestamp.total_seconds
+    }
+
+    fn deviation(&self, rule: ProtocolRule, explanation: String) -> ProtocolDeviation {
+        ProtocolDeviation {
+            location: self.location.clone(),
+            rule,
+            action_name: self.name.clone(),
+            explanation,
+        }
+    }
+}
+
+/// Collects the actions and CPR periods of a session and checks them against ACLS drug and shock timing.
+/// Erroneous actions are checked as well since they were performed.
+#[derive(Debug)]
+pub struct ComplianceChecker {
+    catalogue: Arc<ActionCatalogue>,
+    epinephrine: Vec<PerformedAction>,
+    amiodarone: Vec<PerformedAction>,
+    shocks: Vec<PerformedAction>,
+    pulse_checks: Vec<PerformedAction>,
+    cpr_periods: Vec<(u32, u32)>,
+    session_end: u32,
+}
+
+impl Default for ComplianceChecker {
+    fn default() -> Self {
+        Self::new(ActionCatalogue::builtin())
+    }
+}
+
+impl ComplianceChecker {
+    /// Drugs, shocks and pulse checks are recognized by their kind in `catalogue`.
+    pub fn new(catalogue: Arc<ActionCatalogue>) -> Self {
+        Self {
+            catalogue,
+            epinephrine: vec![],
+            amiodarone: vec![],
+            shocks: vec![],
+            pulse_checks: vec![],
+            cpr_periods: vec![],
+            session_end: 0,
+        }
+    }
+
+    pub fn observe(&mut self, point: &ActionPlotPoint) {
+        let (location, name, shock_value) = match point {
+            ActionPlotPoint::Action(action) => (&action.location, &action.name, &action.shock_value),
+            ActionPlotPoint::Error(error) => (&error.location, &error.name, &error.shock_value),
+            ActionPlotPoint::Period(period_type, start, end) => {
+                self.session_end = self.session_end.max(end.timestamp.total_seconds);
+                if *period_type == PeriodType::CPR {
+                    self.cpr_periods.push((start.timestamp.total_seconds, end.timestamp.total_seconds));
+                }
+                return;
+            }
+            ActionPlotPoint::Stage(stage_period) => {
+                self.session_end = self.session_end.max(stage_period.end.total_seconds);
+                return;
+            }
+            _ => return,
+        };
+
+        self.session_end = self.session_end.max(location.timestamp.total_seconds);
+        let performed = PerformedAction { location: location.clone(), name: name.clone() };
+        if self.catalogue.gives_drug(name, shock_value, EPINEPHRINE) {
+            self.epinephrine.push(performed);
+        } else if self.catalogue.gives_drug(name, shock_value, AMIODARONE) {
+            self.amiodarone.push(performed);
+        } else if self.catalogue.is_kind(name, shock_value, ActionKind::Shock) {
+            self.shocks.push(performed);
+        } else if self.catalogue.is_kind(name, shock_value, ActionKind::PulseCheck) {
+            self.pulse_checks.push(performed);
+        }
+    }
+
+    /// Deviations ordered by the time of the action they refer to.
+    pub fn deviations(&mut self) -> Vec<ProtocolDeviation> {
+        for actions in [&mut self.epinephrine, &mut self.amiodarone, &mut self.shocks, &mut self.pulse_checks] {
+            actions.sort_by_key(PerformedAction::seconds);
+        }
+        let cpr_periods = merge_periods(&self.cpr_periods);
+
+        let mut deviations = self.epinephrine_deviations();
+        deviations.extend(self.amiodarone_deviations());
+        deviations.extend(self.shock_deviations(&cpr_periods));
+        deviations.extend(self.pulse_check_deviations(&cpr_periods));
+        deviations.sort_by_key(|deviation| deviation.location.timestamp.total_seconds);
+        deviations
+    }
+
+    fn epinephrine_deviations(&self) -> Vec<ProtocolDeviation> {
+        self.epinephrine
+            .windows(2)
+            .filter_map(|doses| {
+                let interval = doses[1].seconds() - doses[0].seconds();
+                if interval < EPINEPHRINE_MIN_INTERVAL {
+                    Some(doses[1].deviation(ProtocolRule::EpinephrineTooSoon, format!(
+                        "Epinephrine was repeated {} after the previous dose. Give epinephrine every 3-5 minutes.",
+                        format_duration(interval)
+                    )))
+                } else if interval > EPINEPHRINE_MAX_INTERVAL {
+                    Some(doses[1].deviation(ProtocolRule::EpinephrineTooLate, format!(
+                        "Epinephrine was repeated {} after the previous dose. Give epinephrine every 3-5 minutes.",
+                        format_duration(interval)
+                    )))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn amiodarone_deviations(&self) -> Vec<ProtocolDeviation> {
+        let mut deviations = vec![];
+        for (dose_idx, dose) in self.amiodarone.iter().enumerate() {
+            let shocks = self.shocks.iter().filter(|shock| shock.seconds() <= dose.seconds()).count();
+            if shocks < SHOCKS_BEFORE_AMIODARONE {
+                deviations.push(dose.deviation(ProtocolRule::AmiodaroneBeforeThirdShock, format!(
+                    "Amiodarone was given after {} shock(s). Give the first 300mg dose of amiodarone after the third shock.",
+                    shocks
+                )));
+            }
+            if dose_idx >= AMIODARONE_MAX_DOSES {
+                deviations.push(dose.deviation(ProtocolRule::AmiodaroneDoseLimit, format!(
+                    "Amiodarone dose {} was given. Amiodarone is limited to a 300mg dose followed by one 150mg dose.",
+                    dose_idx + 1
+                )));
+            }
+        }
+        deviations
+    }
+
+    fn shock_deviations(&self, cpr_periods: &[(u32, u32)]) -> Vec<ProtocolDeviation> {
+        self.shocks
+            .iter()
+            .filter_map(|shock| {
+                let window_start = shock.seconds();
+                // A session that ends within 2 minutes of the shock is only checked up to its end.
+                let window_end = (window_start + CPR_AFTER_SHOCK).min(self.session_end);
+                let window = window_end.saturating_sub(window_start);
+                let compressions = overlap(cpr_periods, window_start, window_end);
+                if window > 0 && compressions + MAX_COMPRESSION_PAUSE < window {
+                    Some(shock.deviation(ProtocolRule::CprAfterShock, format!(
+                        "Only {} of CPR in the {} after the shock. Resume compressions immediately after a shock and continue for 2 minutes.",
+                        format_duration(compressions),
+                        format_duration(window)
+                    )))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn pulse_check_deviations(&self, cpr_periods: &[(u32, u32)]) -> Vec<ProtocolDeviation> {
+        self.pulse_checks
+            .iter()
+            .filter_map(|pulse_check| {
+                let seconds = pulse_check.seconds();
+                // A pulse check during compressions did not pause them.
+                if cpr_periods.iter().any(|(start, end)| *start < seconds && seconds < *end) {
+                    return None;
+                }
+                let pause_start = cpr_periods.iter().filter(|(_, end)| *end <= seconds).map(|(_, end)| *end).max()?;
+                let pause_end = cpr_periods.iter().find(|(start, _)| *start >= seconds).map(|(start, _)| *start)?;
+                let pause = pause_end - pause_start;
+                if pause > MAX_COMPRESSION_PAUSE {
+                    Some(pulse_check.deviation(ProtocolRule::PulseCheckTooLong, format!(
+                        "Compressions were paused for {} around the pulse check. Limit pulse checks to 10 seconds.",
+                        format_duration(pause)
+                    )))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+fn format_duration(seconds: u32) -> String {
+    if seconds < 60 {
+        format!("{}s", seconds)
+    } else {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    }
+}
+
+/// Sorted, non-overlapping periods.
+pub(crate) fn merge_periods(periods: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let mut sorted = periods.to_vec();
+    sorted.sort();
+    let mut merged: Vec<(u32, u32)> = vec![];
+    for (start, end) in sorted {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn overlap(periods: &[(u32, u32)], window_start: u32, window_end: u32) -> u32 {
+    periods
+        .iter()
+        .map(|(start, end)| (*end).min(window_end).saturating_sub((*start).max(window_start)))
+        .sum()
+}
+
+pub fn check_compliance<I>(points: I) -> Vec<ProtocolDeviation>
+where
+    I: IntoIterator,
+    I::Item: Borrow<ActionPlotPoint>,
+{
+    check_compliance_with_catalogue(points, ActionCatalogue::builtin())
+}
+
+pub fn check_compliance_with_catalogue<I>(points: I, catalogue: Arc<ActionCatalogue>) -> Vec<ProtocolDeviation>
+where
+    I: IntoIterator,
+    I::Item: Borrow<ActionPlotPoint>,
+{
+    let mut checker = ComplianceChecker::new(catalogue);
+    points.into_iter().for_each(|point| checker.observe(point.borrow()));
+    checker.deviations()
+}
+
+/// Passes the points through unchanged and appends the protocol deviations once the points are exhausted.
+pub fn with_protocol_deviations<'a, I>(points: I) -> Box<dyn Iterator<Item = Result<ActionPlotPoint, ProcessorError>> + 'a>
+where
+    I: Iterator<Item = Result<ActionPlotPoint, ProcessorError>> + 'a,
+{
+    with_protocol_deviations_with_catalogue(points, ActionCatalogue::builtin())
+}
+
+pub fn with_protocol_deviations_with_catalogue<'a, I>(points: I, catalogue: Arc<ActionCatalogue>) -> Box<dyn Iterator<Item = Result<ActionPlotPoint, ProcessorError>> + 'a>
+where
+    I: Iterator<Item = Result<ActionPlotPoint, ProcessorError>> + 'a,
+{
+    let mut checker = ComplianceChecker::new(catalogue);
+    Box::new(
+        points
+            .map(Some)
+            .chain(std::iter::once(None)) // end of stream marker
+            .flat_map(move |point| match point {
+                Some(point) => {
+                    if let Ok(point) = &point {
+                        checker.observe(point);
+                    }
+                    vec![point]
+                }
+                None => checker
+                    .deviations()
+                    .into_iter()
+                    .map(|deviation| Ok(ActionPlotPoint::ProtocolDeviation(deviation)))
+                    .collect(),
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{action, cpr, shock};
+
+    fn epinephrine(total_seconds: u32) -> ActionPlotPoint {
+        action(total_seconds, "Select Epinephrine", "Medication")
+    }
+
+    fn amiodarone(total_seconds: u32) -> ActionPlotPoint {
+        action(total_seconds, "Select Amiodarone", "Medication")
+    }
+
+    fn rules(deviations: &[ProtocolDeviation]) -> Vec<(u32, ProtocolRule)> {
+        deviations.iter().map(|deviation| (deviation.location.timestamp.total_seconds, deviation.rule.clone())).collect()
+    }
+
+    mod check_compliance {
+        use super::*;
+
+        #[test]
+        fn epinephrine_intervals() {
+            let deviations = check_compliance(vec![epinephrine(0), epinephrine(100), epinephrine(340), epinephrine(700)]);
+
+            assert_eq!(rules(&deviations), vec![
+                (100, ProtocolRule::EpinephrineTooSoon),
+                (700, ProtocolRule::EpinephrineTooLate),
+            ]);
+            assert_eq!(deviations[0].action_name, "Select Epinephrine");
+            assert_eq!(deviations[0].explanation, "Epinephrine was repeated 1m 40s after the previous dose. Give epinephrine every 3-5 minutes.");
+        }
+
+        #[test]
+        fn amiodarone_sequencing() {
+            let points = vec![
+                cpr(0, 500),
+                shock(10, "Defib (Unsynchronized Shock)"),
+                amiodarone(20),
+                shock(130, "Defib (Unsynchronized Shock)"),
+                shock(250, "Defib (Unsynchronized Shock)"),
+                amiodarone(260),
+                amiodarone(380),
+            ];
+
+            let deviations = check_compliance(points);
+
+            assert_eq!(rules(&deviations), vec![
+                (20, ProtocolRule::AmiodaroneBeforeThirdShock),
+                (380, ProtocolRule::AmiodaroneDoseLimit),
+            ]);
+            assert_eq!(deviations[0].explanation, "Amiodarone was given after 1 shock(s). Give the first 300mg dose of amiodarone after the third shock.");
+        }
+
+        #[test]
+        fn cpr_after_shock() {
+            let points = vec![shock(10, "Defib (Unsynchronized Shock)"), cpr(12, 130), shock(200, "Defib (Unsynchronized Shock)"), cpr(230, 260), cpr(290, 400)];
+
+            let deviations = check_compliance(points);
+
+            assert_eq!(rules(&deviations), vec![(200, ProtocolRule::CprAfterShock)]);
+            assert_eq!(deviations[0].explanation, "Only 1m 00s of CPR in the 2m 00s after the shock. Resume compressions immediately after a shock and continue for 2 minutes.");
+        }
+
+        #[test]
+        fn pulse_check_pause() {
+            let points = vec![
+                cpr(0, 100),
+                action(102, "Pulse Check", "Pulse Check"),
+                cpr(108, 200),
+                action(205, "Pulse Check", "Pulse Check"),
+                cpr(225, 300),
+            ];
+
+            let deviations = check_compliance(points);
+
+            assert_eq!(rules(&deviations), vec![(205, ProtocolRule::PulseCheckTooLong)]);
+            assert_eq!(deviations[0].explanation, "Compressions were paused for 25s around the pulse check. Limit pulse checks to 10 seconds.");
+        }
+
+        #[test]
+        fn scenario_catalogue() {
+            let catalogue = ActionCatalogue::from_toml_str(r#"
+                [[action]]
+                name = "Push Adrenaline"
+                kind = "medication"
+                drug = "epinephrine"
+            "#).unwrap();
+            let points = vec![action(0, "Push Adrenaline", "Push Adrenaline"), action(60, "Push Adrenaline", "Push Adrenaline"), epinephrine(70)];
+
+            let deviations = check_compliance_with_catalogue(points, Arc::new(catalogue));
+
+            assert_eq!(rules(&deviations), vec![(60, ProtocolRule::EpinephrineTooSoon)]);
+        }
+
+        #[test]
+        fn pulse_check_during_cpr() {
+            let points = vec![cpr(0, 40), cpr(45, 100), action(70, "Pulse Check", "Pulse Check"), cpr(105, 200)];
+
+            assert!(check_compliance(points).is_empty());
+        }
+
+        #[test]
+        fn compliant_session() {
+            let points = vec![cpr(0, 120), shock(121, "Defib (Unsynchronized Shock)"), cpr(123, 245), epinephrine(130), epinephrine(340), cpr(250, 600)];
+
+            assert!(check_compliance(points).is_empty());
+        }
+    }
+
+    mod with_protocol_deviations {
+        use super::*;
+
+        #[test]
+        fn deviations_appended() {
+            let points = vec![Ok(epinephrine(0)), Err(ProcessorError::Io { message: "read".to_owned() }), Ok(epinephrine(60))];
+
+            let results: Vec<_> = with_protocol_deviations(points.clone().into_iter()).collect();
+
+            assert_eq!(results.len(), 4);
+            assert_eq!(results[..3], points[..]);
+            assert!(matches!(&results[3], Ok(ActionPlotPoint::ProtocolDeviation(deviation)) if deviation.rule == ProtocolRule::EpinephrineTooSoon));
+        }
+    }
+}
@@ -1,26 +1,119 @@
-use crate::csv_reader::initialize_csv_reader;
-use crate::csv_row_processor::process_csv_row;
+use crate::csv_reader::{initialize_csv_reader, numbered_records};
+use crate::csv_row_processor::{finalize_processing, process_csv_row};
+use crate::input_sources::tail_reader::{TailOptions, TailReader, TailReset};
 use crate::plot_structures::ActionPlotPoint;
 use crate::processing_state::CsvProcessingState;
+use crate::processor_config::ProcessorConfig;
+use crate::processor_error::ProcessorError;
+use std::cell::Cell;
 use std::io::Read;
+use std::rc::Rc;
+
+/// A result with the csv line number of the row it was built from, points closed at the end of the data have none.
+pub type NumberedResult = (Option<usize>, Result<ActionPlotPoint, ProcessorError>);
+
 pub fn process_csv<'r, R>(
     reader: R,
-    max_rows_to_check: usize,
-) -> Box<dyn Iterator<Item = Result<ActionPlotPoint, String>> + 'r>
+    config: &ProcessorConfig,
+) -> Box<dyn Iterator<Item = Result<ActionPlotPoint, ProcessorError>> + 'r>
+where
+    R: Read + 'r,
+{
+    try_process_csv(reader, config).unwrap_or_else(|e| Box::new(vec![Err(e)].into_iter()))
+}
+
+/// Same as `process_csv` but reports a header validation failure up front instead of as the first item.
+pub fn try_process_csv<'r, R>(
+    reader: R,
+    config: &ProcessorConfig,
+) -> Result<Box<dyn Iterator<Item = Result<ActionPlotPoint, ProcessorError>> + 'r>, ProcessorError>
+where
+    R: Read + 'r,
+{
+    let results = try_process_csv_numbered(reader, config)?;
+    Ok(Box::new(results.map(|(_, result)| result)))
+}
+
+/// Same as `try_process_csv` but every result comes with the csv line number of the row it was built from.
+/// Buffered actions keep the line of their own row, periods closed at the end of the data have no line number.
+pub fn try_process_csv_numbered<'r, R>(
+    reader: R,
+    config: &ProcessorConfig,
+) -> Result<Box<dyn Iterator<Item = NumberedResult> + 'r>, ProcessorError>
 where
     R: Read + 'r,
 {
-    let csv_reader = match initialize_csv_reader(reader) {
-        Ok(r) => r,
-        Err(e) => return Box::new(vec![Err(e)].into_iter()),
-    };
-
-    let mut state = CsvProcessingState::new(max_rows_to_check);
-
-    Box::new(
-        csv_reader
-            .into_records()
-            .enumerate()
-            .filter_map(move |(row_idx, result)| process_csv_row(row_idx, result, &mut state)),
-    )
+    let csv_reader = initialize_csv_reader(reader)?;
+
+    let mut state = CsvProcessingState::new(config);
+
+    Ok(Box::new(
+        numbered_records(csv_reader)
+            .map(Some)
+            .chain(std::iter::once(None)) // end of stream marker
+            .flat_map(move |record| match record {
+                Some((line, result)) => process_csv_row(line, result, &mut state),
+                None => finalize_processing(&mut state),
+            }),
+    ))
+}
+
+struct TailedCsv {
+    path: String,
+    config: ProcessorConfig,
+    options: TailOptions,
+    reset: Rc<Cell<Option<TailReset>>>,
+    points: Option<Box<dyn Iterator<Item = Result<ActionPlotPoint, ProcessorError>>>>,
+    finished: bool,
+}
+
+impl Iterator for TailedCsv {
+    type Item = Result<ActionPlotPoint, ProcessorError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        if self.points.is_none() {
+            match TailReader::open(&self.path, self.options.clone(), self.reset.clone()) {
+                Ok(reader) => self.points = Some(process_csv(reader, &self.config)),
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(ProcessorError::Io { message: e.to_string() }));
+                }
+            }
+        }
+
+        let point = self.points.as_mut().and_then(|points| points.next());
+        // The read error caused by the reset is replaced by the reset itself and the file is processed again.
+        match self.reset.take() {
+            Some(reset) => {
+                self.points = None;
+                let path = self.path.clone();
+                Some(Err(match reset {
+                    TailReset::Truncated => ProcessorError::SourceTruncated { path },
+                    TailReset::Rotated => ProcessorError::SourceRotated { path },
+                }))
+            }
+            None => {
+                self.finished = point.is_none();
+                point
+            }
+        }
+    }
+}
+
+/// Processes a csv file that is still being written. Points are yielded as rows are appended and the
+/// iterator ends once the file has been idle for `options.idle_timeout`. If the file is truncated or
+/// rotated, a `SourceTruncated`/`SourceRotated` error is yielded and processing starts over.
+/// Unless the config has a session date, it is taken from the file name when it contains one.
+pub fn process_tail(path: &str, config: &ProcessorConfig, options: TailOptions) -> Box<dyn Iterator<Item = Result<ActionPlotPoint, ProcessorError>>> {
+    Box::new(TailedCsv {
+        path: path.to_owned(),
+        config: config.for_source(path),
+        options,
+        reset: Rc::default(),
+        points: None,
+        finished: false,
+    })
 }
@@ -1,13 +1,73 @@
-use std::io::Read;
-use csv::Reader;
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use csv::{Reader, StringRecord};
 use crate::action_csv_row::{COLUMN_NAMES};
+use crate::processor_error::ProcessorError;
 
-pub fn initialize_csv_reader<R: Read>(reader: R) -> Result<Reader<R>, String> {
-    let mut csv_reader = build_csv_reader(reader);
-    validate_csv_header(&mut csv_reader).map_err(|e| format!("Header parsing errors: {:?}", e))?;
+pub fn initialize_csv_reader<R: Read>(reader: R) -> Result<Reader<LineCountingReader<R>>, ProcessorError> {
+    let mut csv_reader = build_csv_reader(LineCountingReader::new(reader));
+    validate_csv_header(&mut csv_reader)?;
     Ok(csv_reader)
 }
 
+/// Remembers where the lines of the data read through it end, csv positions count records
+/// and skip blank lines, so they cannot tell the line a record is on.
+pub struct LineCountingReader<R> {
+    inner: R,
+    bytes_read: u64,
+    // Offsets of the newlines not yet passed by `line_of`.
+    newlines: VecDeque<u64>,
+    passed_newlines: usize,
+}
+
+impl<R> LineCountingReader<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, bytes_read: 0, newlines: VecDeque::new(), passed_newlines: 0 }
+    }
+
+    /// 1-based line of the byte at `offset`, offsets must not decrease from one call to the next.
+    fn line_of(&mut self, offset: u64) -> usize {
+        while self.newlines.front().is_some_and(|&newline| newline < offset) {
+            self.newlines.pop_front();
+            self.passed_newlines += 1;
+        }
+        self.passed_newlines + 1
+    }
+}
+
+impl<R: Read> Read for LineCountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        let start = self.bytes_read;
+        self.newlines.extend(buf[..read].iter().enumerate().filter(|(_, &byte)| byte == b'\n').map(|(idx, _)| start + idx as u64));
+        self.bytes_read += read as u64;
+        Ok(read)
+    }
+}
+
+/// Data records with the csv line they start on.
+pub(crate) fn numbered_records<R: Read>(mut csv_reader: Reader<LineCountingReader<R>>) -> impl Iterator<Item = (usize, Result<StringRecord, csv::Error>)> {
+    let mut line = 1;
+    std::iter::from_fn(move || {
+        let mut record = StringRecord::new();
+        let result = match csv_reader.read_record(&mut record) {
+            Ok(false) => return None,
+            Ok(true) => {
+                // The last byte read is the record terminator, unless the data ends without one.
+                let end = csv_reader.position().byte().saturating_sub(1);
+                let quoted_newlines: usize = record.iter().map(|field| field.matches('\n').count()).sum();
+                line = csv_reader.get_mut().line_of(end) - quoted_newlines;
+                Ok(record)
+            }
+            Err(e) => {
+                line += 1;
+                Err(e)
+            }
+        };
+        Some((line, result))
+    })
+}
+
 fn build_csv_reader<R: Read>(reader: R) -> Reader<R> {
     csv::ReaderBuilder::new()
         .has_headers(true)
@@ -15,47 +75,80 @@ fn build_csv_reader<R: Read>(reader: R) -> Reader<R> {
         .from_reader(reader)
 }
 
-type HeaderValidatorType = fn(&[&str], &[&str]) -> Result<(), String>;
+type HeaderValidatorType = fn(&[&str], &[&str]) -> Result<(), ProcessorError>;
 
-pub fn validate_header(headers: &[&str], expected_headers: &[&str]) -> Result<(), String> {
+pub fn validate_header(headers: &[&str], expected_headers: &[&str]) -> Result<(), ProcessorError> {
     let mut headers_iter = headers.iter().map(|h| h.to_lowercase());
     let mut expected_iter = expected_headers.iter().map(|h| h.to_lowercase());
 
     if expected_iter.all(|expected| headers_iter.next() == Some(expected)) {
         Ok(())
     } else {
-        let err = format!(
-            "Line {:?}: expected {:?} as the header row of csv but got {:?}",
-            1, expected_headers, headers
-        );
-        Err(err)
+        Err(ProcessorError::HeaderMismatch {
+            expected: expected_headers.iter().map(|h| h.to_string()).collect(),
+            actual: headers.iter().map(|h| h.to_string()).collect(),
+        })
     }
 }
 
-fn apply_validation<R: Read>(reader: &mut Reader<R>, validate: HeaderValidatorType) -> Result<(), String> {
+fn apply_validation<R: Read>(reader: &mut Reader<R>, validate: HeaderValidatorType) -> Result<(), ProcessorError> {
     match reader.headers() {
         Ok(headers) => {
             let headers = headers.iter().collect::<Vec<_>>();
             validate(&headers, &COLUMN_NAMES)
         }
-        Err(e) => Err(e.to_string())
+        Err(e) => Err(ProcessorError::from_csv_error(1, e))
     }
 }
 
-fn build_csv_header_validator<R: Read>(validate: HeaderValidatorType) -> impl Fn(Box<&mut Reader<R>>) -> Result<(), String> {
+fn build_csv_header_validator<R: Read>(validate: HeaderValidatorType) -> impl Fn(Box<&mut Reader<R>>) -> Result<(), ProcessorError> {
     move |mut reader| apply_validation(reader.as_mut(), validate)
 }
 
-pub fn validate_csv_header<R: Read>(reader: &mut Reader<R>) -> Result<(), String> {
+pub fn validate_csv_header<R: Read>(reader: &mut Reader<R>) -> Result<(), ProcessorError> {
     build_csv_header_validator(validate_header)(Box::new(reader)) 
 }
 
 #[cfg(test)]
 mod tests {
-    fn assert_header_check(headers: &[&str], actual: Result<(), String>, expected_headers: &[&str]) {
+    use crate::processor_error::ProcessorError;
+
+    mod numbered_records {
+        use crate::csv_reader::{build_csv_reader, numbered_records, LineCountingReader};
+
+        fn lines(data: &str) -> Vec<usize> {
+            numbered_records(build_csv_reader(LineCountingReader::new(data.as_bytes()))).map(|(line, _)| line).collect()
+        }
+
+        #[test]
+        fn one_record_per_line() {
+            assert_eq!(lines("a,b\n1,2\n3,4\n"), vec![2, 3]);
+        }
+
+        #[test]
+        fn blank_lines_skipped() {
+            assert_eq!(lines("a,b\n\n1,2\n\n\n3,4\n"), vec![3, 6]);
+        }
+
+        #[test]
+        fn crlf_line_endings() {
+            assert_eq!(lines("a,b\r\n1,2\r\n\r\n3,4\r\n"), vec![2, 4]);
+        }
+
+        #[test]
+        fn quoted_newlines() {
+            assert_eq!(lines("a,b\n\"1\n\n2\",3\n4,5"), vec![2, 5]);
+        }
+    }
+
+    fn assert_header_check(headers: &[&str], actual: Result<(), ProcessorError>, expected_headers: &[&str]) {
         assert!(actual.is_err());
-        let message: String = actual.unwrap_err();
-        assert_eq!(message, format!("Line {:?}: expected {:?} as the header row of csv but got {:?}", 1, expected_headers, headers));
+        let error = actual.unwrap_err();
+        assert_eq!(error.to_string(), format!("Line {:?}: expected {:?} as the header row of csv but got {:?}", 1, expected_headers, headers));
+        assert_eq!(error, ProcessorError::HeaderMismatch {
+            expected: expected_headers.iter().map(|h| h.to_string()).collect(),
+            actual: headers.iter().map(|h| h.to_string()).collect(),
+        });
     }
 
     mod invalid_header_tests {
@@ -171,6 +264,7 @@ mod tests {
 
     mod tests_apply_validation {
         use crate::csv_reader::apply_validation;
+        use crate::processor_error::ProcessorError;
         use csv::Reader;
         use std::io::{self, Read};
 
@@ -185,38 +279,38 @@ mod tests {
 
         impl Read for ErrorReader {
             fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
-                Err(io::Error::new(io::ErrorKind::Other, "Simulated read error"))
+                Err(io::Error::other("Simulated read error"))
             }
         }
 
         #[test]
         fn test_could_not_read_headers() {
             let mut csv_reader = Reader::from_reader(ErrorReader);
-            let mock_validate = |_: &[&str], _: &[&str]| -> Result<(), String> { unreachable!() };
+            let mock_validate = |_: &[&str], _: &[&str]| -> Result<(), ProcessorError> { unreachable!() };
 
             let result = apply_validation(&mut csv_reader, mock_validate);
 
             assert!(result.is_err());
-            assert_eq!(result.unwrap_err(), "Simulated read error");
+            assert_eq!(result.unwrap_err(), ProcessorError::Io { message: "Simulated read error".to_owned() });
         }
 
         #[test]
         fn test_read_invalid_headers() {
             let mut csv_reader = Reader::from_reader(ValidReader);
-            let mock_validate = |_: &[&str], _: &[&str]| -> Result<(), String> {
-                Err("Validation error".to_owned())
+            let mock_validate = |_: &[&str], _: &[&str]| -> Result<(), ProcessorError> {
+                Err(ProcessorError::HeaderMismatch { expected: vec![], actual: vec![] })
             };
 
             let result = apply_validation(&mut csv_reader, mock_validate);
 
             assert!(result.is_err());
-            assert_eq!(result.unwrap_err(), "Validation error");
+            assert_eq!(result.unwrap_err(), ProcessorError::HeaderMismatch { expected: vec![], actual: vec![] });
         }
 
         #[test]
         fn test_read_valid_headers() {
             let mut csv_reader = Reader::from_reader(ValidReader);
-            let mock_validate = |_: &[&str], _: &[&str]| -> Result<(), String> {
+            let mock_validate = |_: &[&str], _: &[&str]| -> Result<(), ProcessorError> {
                 Ok(())
             };
 
@@ -1,45 +1,128 @@
 use crate::action_csv_row::ActionCsvRow;
-use crate::plot_processors::{process_action_point, process_cpr_lines, process_erroneous_action, process_stage_boundary};
+use crate::csv_processor::NumberedResult;
+use crate::detection::{is_questionnaire_header, is_questionnaire_marker, is_rule_evaluation};
+use crate::parsing::parse_questionnaire_item;
+use crate::plot_processors::{close_open_cpr_period, close_open_stage_period, close_open_waveform_segments, flush_pending_error_marker, flush_recent_actions, process_action_point, process_cpr_lines, process_erroneous_action, process_rule_evaluation, process_speech, process_stage_boundary, process_vital_sign, process_waveform_transition};
 use crate::plot_structures::ActionPlotPoint;
-use crate::processing_state::CsvProcessingState;
+use crate::processing_state::{CsvProcessingState, CsvSection};
+use crate::processor_error::ProcessorError;
+use crate::processor_config::ProcessorConfig;
+use crate::session_date::SessionDate;
 use csv::StringRecord;
 use std::collections::VecDeque;
 
-fn parse_csv_row(result: Result<StringRecord, csv::Error>) -> Result<ActionCsvRow, String> {
-    result
-        .and_then(|raw_row| {
-            let mut csv_row: ActionCsvRow = raw_row.deserialize(None)?;
-            csv_row.post_deserialize();
-            Ok(csv_row)
-        })
-        .map_err(|e| format!("Could not deserialize row: {}", e))
+fn numbered(result: Result<ActionPlotPoint, ProcessorError>, row_number: Option<usize>) -> NumberedResult {
+    match result {
+        Err(e) => (e.row_number(), Err(e)),
+        point => (row_number, point),
+    }
+}
+
+fn parse_csv_row(line: usize, result: Result<StringRecord, csv::Error>, session_date: &SessionDate, config: &ProcessorConfig) -> Result<ActionCsvRow, ProcessorError> {
+    let raw_row = result.map_err(|e| ProcessorError::from_csv_error(line, e))?;
+    let deserialization_error = |e: csv::Error| ProcessorError::RowDeserialization {
+        row_number: line,
+        record: raw_row.iter().map(str::to_owned).collect(),
+        message: e.to_string(),
+    };
+
+    let mut csv_row: ActionCsvRow = raw_row.deserialize(None).map_err(deserialization_error)?;
+    if csv_row.timestamp.is_none() {
+        return Err(ProcessorError::TimeParse {
+            row_number: line,
+            value: raw_row.get(0).unwrap_or_default().to_owned(),
+        });
+    }
+    csv_row.row_number = line;
+    csv_row.post_deserialize(session_date, config);
+    if is_rule_evaluation(&csv_row) {
+        csv_row.rule = Some(raw_row.deserialize(None).map_err(deserialization_error)?);
+    }
+    Ok(csv_row)
 }
 
-pub fn process_csv_row(row_idx: usize, result: Result<StringRecord, csv::Error>, state: &mut CsvProcessingState) -> Option<Result<ActionPlotPoint, String>> {
-    let current_row = match parse_csv_row(result) {
+fn process_questionnaire_record(line: usize, record: &StringRecord, section: &mut CsvSection) -> Option<Vec<Result<ActionPlotPoint, ProcessorError>>> {
+    match section {
+        CsvSection::Timeline if is_questionnaire_marker(record) => {
+            *section = CsvSection::Questionnaire;
+            Some(Vec::new())
+        }
+        CsvSection::Timeline => None,
+        CsvSection::Questionnaire if is_questionnaire_header(record) => Some(Vec::new()),
+        CsvSection::Questionnaire => Some(vec![parse_questionnaire_item(record)
+            .map(ActionPlotPoint::Questionnaire)
+            .map_err(|message| ProcessorError::InvalidQuestionnaireItem {
+                row_number: line,
+                record: record.iter().map(str::to_owned).collect(),
+                message,
+            })]),
+    }
+}
+
+pub fn process_csv_row(line: usize, result: Result<StringRecord, csv::Error>, state: &mut CsvProcessingState) -> Vec<NumberedResult> {
+    let current_row_number = Some(line);
+    if let Some(points) = result.as_ref().ok().and_then(|record| process_questionnaire_record(line, record, &mut state.section)) {
+        return points.into_iter().map(|point| numbered(point, current_row_number)).collect();
+    }
+
+    let current_row = match parse_csv_row(line, result, &state.session_date, &state.config) {
         Ok(row) => row,
-        Err(e) => return Some(Err(e)),
+        Err(e) => return vec![numbered(Err(e), None)],
     };
+    state.track_progress(&current_row);
 
+    // Speech, vital samples, rhythm segments and rule evaluations are reported alongside the timeline,
+    // they do not hold back buffered actions.
+    let mut points: Vec<_> = process_speech(state.current_stage.as_ref(), &current_row)
+        .or_else(|| process_rule_evaluation(&current_row))
+        .or_else(|| process_vital_sign(line, state.current_stage.as_ref(), &current_row))
+        .or_else(|| process_waveform_transition(&mut state.waveform_segments, &state.session_date, state.current_stage.as_ref(), &current_row))
+        .map(|point| numbered(point, current_row_number))
+        .into_iter()
+        .collect();
+
+    // A buffered action keeps the line number of its own row, an erroneous action has the one of the row that completed the match.
     let point = process_stage_boundary(&mut state.stage_boundaries, &current_row)
         .or_else(|| process_cpr_lines(&mut state.cpr_points, &current_row))
-        .or_else(|| process_erroneous_action(state, row_idx, &current_row))
+        .or_else(|| process_erroneous_action(state, line, &current_row))
+        .map(|point| numbered(point, current_row_number))
         .or_else(|| state.recent_rows
             .pop_front()
-            .and_then(|recent_row| process_action_point(&recent_row))
+            .and_then(|recent_row| process_action_point(&recent_row).map(|point| numbered(point, Some(recent_row.row_number))))
             );
     
-    if !matches!(point, Some(Ok(ActionPlotPoint::Error(_)))) {
-        update_recent_actions(&current_row, &mut state.recent_rows, state.max_rows_to_check);
+    if !matches!(point, Some((_, Ok(ActionPlotPoint::Error(_))))) {
+        let evicted = update_recent_actions(&current_row, &mut state.recent_rows, state.config.max_rows_to_check);
+        points.extend(evicted.and_then(|evicted_row| {
+            process_action_point(&evicted_row).map(|point| numbered(point, Some(evicted_row.row_number)))
+        }));
     }
-    point
+    points.extend(point);
+    points
+}
+
+pub fn finalize_processing(state: &mut CsvProcessingState) -> Vec<NumberedResult> {
+    let mut points: Vec<_> = flush_recent_actions(&mut state.recent_rows)
+        .into_iter()
+        .map(|(row_number, point)| numbered(point, Some(row_number)))
+        .collect();
+    let closed = flush_pending_error_marker(&state.pending_error_marker)
+        .into_iter()
+        .chain(close_open_cpr_period(&mut state.cpr_points, state.last_timestamp.as_ref()))
+        .chain(close_open_waveform_segments(&mut state.waveform_segments, state.last_timestamp.as_ref()))
+        .chain(close_open_stage_period(&mut state.stage_boundaries, state.current_stage.as_ref(), state.last_timestamp.as_ref()));
+    points.extend(closed.map(|point| numbered(point, None)));
+    points
 }
 
-fn update_recent_actions(current_row: &ActionCsvRow, recent_rows: &mut VecDeque<ActionCsvRow>, max_rows: usize) {
+/// The oldest action is returned when the buffer is full, it can no longer be claimed by an error marker.
+fn update_recent_actions(current_row: &ActionCsvRow, recent_rows: &mut VecDeque<ActionCsvRow>, max_rows: usize) -> Option<ActionCsvRow> {
     if current_row.action_point {
         recent_rows.push_back(current_row.clone());
     }
     if recent_rows.len() > max_rows {
-        recent_rows.pop_front();
+        recent_rows.pop_front()
+    } else {
+        None
     }
 }
\ No newline at end of file
@@ -0,0 +1,343 @@
+use crate::action_csv_row::COLUMN_NAMES;
+use crate::csv_reader::validate_header;
+use crate::csv_row_processor::{finalize_processing, process_csv_row};
+use crate::plot_structures::ActionPlotPoint;
+use crate::processing_state::CsvProcessingState;
+use crate::processor_config::ProcessorConfig;
+use crate::processor_error::ProcessorError;
+use csv::{ByteRecord, StringRecord};
+use csv_core::ReadRecordResult;
+use futures::stream::{self, Stream, StreamExt};
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::pin::Pin;
+
+pub type PlotPointStream<'s> = Pin<Box<dyn Stream<Item = Result<ActionPlotPoint, ProcessorError>> + Send + 's>>;
+
+const UTF8_BOM: &str = "\u{feff}";
+
+/// A record with the csv line it starts on.
+type NumberedRecord = (usize, Result<StringRecord, ProcessorError>);
+
+/// Splits csv records out of arbitrarily sized chunks, a record may span several chunks.
+struct RecordDecoder {
+    reader: csv_core::Reader,
+    output: Vec<u8>,
+    output_len: usize,
+    ends: Vec<usize>,
+    ends_len: usize,
+    // Newlines consumed so far, the terminator of the last record excluded until the next read.
+    newlines: usize,
+    pending_newline: bool,
+    header_read: bool,
+}
+
+impl RecordDecoder {
+    fn new() -> Self {
+        Self {
+            reader: csv_core::Reader::new(),
+            output: vec![0; 1024],
+            output_len: 0,
+            ends: vec![0; 16],
+            ends_len: 0,
+            newlines: 0,
+            pending_newline: false,
+            header_read: false,
+        }
+    }
+
+    fn feed(&mut self, mut input: &[u8], records: &mut Vec<NumberedRecord>) {
+        // An empty input signals the end of data to csv_core.
+        if input.is_empty() {
+            return;
+        }
+        while let Some(consumed) = self.read(input, records) {
+            input = &input[consumed..];
+            if input.is_empty() {
+                break;
+            }
+        }
+    }
+
+    fn finish(&mut self, records: &mut Vec<NumberedRecord>) {
+        while self.read(&[], records).is_some() {}
+    }
+
+    /// Returns the number of bytes consumed, or None once csv_core reports the end of data.
+    fn read(&mut self, input: &[u8], records: &mut Vec<NumberedRecord>) -> Option<usize> {
+        let (result, consumed, written, ends_written) = self.reader.read_record(
+            input,
+            &mut self.output[self.output_len..],
+            &mut self.ends[self.ends_len..],
+        );
+        self.output_len += written;
+        self.ends_len += ends_written;
+
+        // The newline ending a record belongs to the next line, so it is only counted once the record is taken.
+        let consumed_input = &input[..consumed];
+        let terminator = match (&result, consumed_input.last()) {
+            (ReadRecordResult::Record, Some(b'\n')) => 1,
+            _ => 0,
+        };
+        self.newlines += usize::from(std::mem::take(&mut self.pending_newline))
+            + consumed_input[..consumed - terminator].iter().filter(|&&byte| byte == b'\n').count();
+        self.pending_newline = terminator == 1;
+
+        match result {
+            ReadRecordResult::InputEmpty => {}
+            ReadRecordResult::OutputFull => self.output.resize(self.output.len() * 2, 0),
+            ReadRecordResult::OutputEndsFull => self.ends.resize(self.ends.len() * 2, 0),
+            ReadRecordResult::Record => records.push(self.take_record()),
+            ReadRecordResult::End => return None,
+        }
+        Some(consumed)
+    }
+
+    fn take_record(&mut self) -> NumberedRecord {
+        let mut record = ByteRecord::new();
+        let mut start = 0;
+        for &end in &self.ends[..self.ends_len] {
+            record.push_field(&self.output[start..end]);
+            start = end;
+        }
+        let quoted_newlines = self.output[..self.output_len].iter().filter(|&&byte| byte == b'\n').count();
+        self.output_len = 0;
+        self.ends_len = 0;
+
+        let line = self.newlines + 1 - quoted_newlines;
+        let is_header = !std::mem::replace(&mut self.header_read, true);
+        let record = StringRecord::from_byte_record(record)
+            .map(|record| if is_header { strip_bom(record) } else { record })
+            .map_err(|e| ProcessorError::RowDeserialization {
+                row_number: line,
+                record: vec![],
+                message: e.to_string(),
+            });
+        (line, record)
+    }
+}
+
+fn strip_bom(record: StringRecord) -> StringRecord {
+    match record.get(0) {
+        Some(first) if first.starts_with(UTF8_BOM) => record
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| if idx == 0 { &field[UTF8_BOM.len()..] } else { field })
+            .collect(),
+        _ => record,
+    }
+}
+
+struct StreamState<S> {
+    byte_stream: S,
+    decoder: RecordDecoder,
+    processing_state: CsvProcessingState,
+    header_validated: bool,
+    pending: VecDeque<Result<ActionPlotPoint, ProcessorError>>,
+    finished: bool,
+}
+
+impl<S> StreamState<S> {
+    fn process_records(&mut self, records: Vec<NumberedRecord>) {
+        for (line, record) in records {
+            if self.finished {
+                return;
+            }
+            if !self.header_validated {
+                match record.and_then(|header| validate_header(&header.iter().collect::<Vec<_>>(), &COLUMN_NAMES)) {
+                    Ok(()) => self.header_validated = true,
+                    Err(e) => {
+                        self.pending.push_back(Err(e));
+                        self.finished = true;
+                    }
+                }
+                continue;
+            }
+            match record {
+                Ok(record) => self.pending.extend(process_csv_row(line, Ok(record), &mut self.processing_state).into_iter().map(|(_, point)| point)),
+                Err(e) => self.pending.push_back(Err(e)),
+            }
+        }
+    }
+
+    fn finalize(&mut self) {
+        if !self.finished && self.header_validated {
+            self.pending.extend(finalize_processing(&mut self.processing_state).into_iter().map(|(_, point)| point));
+        }
+        self.finished = true;
+    }
+}
+
+/// Async counterpart of `process_csv`: records are processed as soon as the chunks that complete them arrive.
+pub fn process_csv_stream<'s, S, B, E>(byte_stream: S, config: &ProcessorConfig) -> PlotPointStream<'s>
+where
+    S: Stream<Item = Result<B, E>> + Send + Unpin + 's,
+    B: AsRef<[u8]>,
+    E: Display,
+{
+    let state = StreamState {
+        byte_stream,
+        decoder: RecordDecoder::new(),
+        processing_state: CsvProcessingState::new(config),
+        header_validated: false,
+        pending: VecDeque::new(),
+        finished: false,
+    };
+
+    Box::pin(stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(point) = state.pending.pop_front() {
+                return Some((point, state));
+            }
+            if state.finished {
+                return None;
+            }
+
+            let mut records = vec![];
+            match state.byte_stream.next().await {
+                Some(Ok(chunk)) => {
+                    state.decoder.feed(chunk.as_ref(), &mut records);
+                    state.process_records(records);
+                }
+                Some(Err(e)) => {
+                    state.pending.push_back(Err(ProcessorError::Io { message: e.to_string() }));
+                    state.finished = true;
+                }
+                None => {
+                    state.decoder.finish(&mut records);
+                    state.process_records(records);
+                    state.finalize();
+                }
+            }
+        }
+    }))
+}
+
+/// Downloads the csv at `url` and processes it while the body is still being received.
+/// Unless the config has a session date, it is inferred from the file name in the url.
+pub async fn process_url_stream(url: &str, config: &ProcessorConfig) -> Result<PlotPointStream<'static>, ProcessorError> {
+    let response = reqwest::get(url).await.map_err(|e| ProcessorError::Request {
+        url: url.to_owned(),
+        message: e.to_string(),
+    })?;
+
+    if !response.status().is_success() {
+        return Err(ProcessorError::HttpStatus { url: url.to_owned(), status: response.status().as_u16() });
+    }
+
+    Ok(process_csv_stream(response.bytes_stream(), &config.for_source(url)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    fn chunked(data: &[u8], chunk_size: usize) -> impl Stream<Item = Result<Vec<u8>, String>> + Send + Unpin {
+        stream::iter(data.chunks(chunk_size).map(|chunk| Ok(chunk.to_vec())).collect::<Vec<_>>())
+    }
+
+    fn decode(chunks: &[&[u8]]) -> Vec<NumberedRecord> {
+        let mut decoder = RecordDecoder::new();
+        let mut records = vec![];
+        chunks.iter().for_each(|chunk| decoder.feed(chunk, &mut records));
+        decoder.finish(&mut records);
+        records
+    }
+
+    mod record_decoder {
+        use super::*;
+
+        #[test]
+        fn record_split_across_chunks() {
+            let records = decode(&[b"a,\"b\nc", b"\",d\n1,", b"2,3"]);
+
+            assert_eq!(records.len(), 2);
+            assert_eq!(records[0].1.as_ref().unwrap(), &StringRecord::from(vec!["a", "b\nc", "d"]));
+            assert_eq!(records[1].1.as_ref().unwrap(), &StringRecord::from(vec!["1", "2", "3"]));
+        }
+
+        #[test]
+        fn long_record() {
+            let field = "x".repeat(5000);
+            let line = format!("{},{}\n", field, (0..40).map(|i| i.to_string()).collect::<Vec<_>>().join(","));
+
+            let records = decode(&[line.as_bytes()]);
+
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0].1.as_ref().unwrap().len(), 41);
+            assert_eq!(&records[0].1.as_ref().unwrap()[0], field);
+        }
+
+        #[test]
+        fn lines_of_records() {
+            let records = decode(&[b"a,b\r\n\r", b"\n\"1\n", b"2\",3\n\n4,5"]);
+
+            assert_eq!(records.iter().map(|(line, _)| *line).collect::<Vec<_>>(), vec![1, 3, 6]);
+        }
+
+        #[test]
+        fn bom_removed_from_header() {
+            let records = decode(&["\u{feff}a,b\n".as_bytes()]);
+
+            assert_eq!(records[0].1.as_ref().unwrap(), &StringRecord::from(vec!["a", "b"]));
+        }
+
+        #[test]
+        fn invalid_utf8() {
+            let records = decode(&[b"a,b\n\xff,c\n"]);
+
+            assert!(matches!(records[1].1, Err(ProcessorError::RowDeserialization { row_number: 2, .. })));
+        }
+    }
+
+    mod process_csv_stream {
+        use super::*;
+
+        const SAMPLE_CSV: &[u8] = include_bytes!("../tests/data/sample.csv");
+
+        #[test]
+        fn same_points_as_reader() {
+            let expected: Vec<_> = crate::process_csv(SAMPLE_CSV, &ProcessorConfig::default()).collect();
+
+            let actual: Vec<_> = block_on(process_csv_stream(chunked(SAMPLE_CSV, 7), &ProcessorConfig::default()).collect());
+
+            assert_eq!(actual, expected);
+        }
+
+        #[test]
+        fn header_mismatch() {
+            let actual: Vec<_> = block_on(process_csv_stream(chunked(b"Name,Score\nPulse Check,1\n", 4), &ProcessorConfig::default()).collect());
+
+            assert_eq!(actual.len(), 1);
+            assert!(matches!(actual[0], Err(ProcessorError::HeaderMismatch { .. })));
+        }
+
+        #[test]
+        fn transport_error() {
+            let header = COLUMN_NAMES.join(",") + "\n";
+            let chunks = stream::iter(vec![Ok(header.into_bytes()), Err("connection reset")]);
+
+            let actual: Vec<_> = block_on(process_csv_stream(chunks, &ProcessorConfig::default()).collect());
+
+            assert_eq!(actual, vec![Err(ProcessorError::Io { message: "connection reset".to_owned() })]);
+        }
+
+        #[test]
+        fn points_before_end_of_data() {
+            let header = COLUMN_NAMES.join(",") + "\n";
+            let row = "0:0:5,umich1(speech),,,,,,umich1,\"check pulse\"\n";
+            let (sender, receiver) = futures::channel::mpsc::unbounded::<Result<Vec<u8>, String>>();
+            let mut points = process_csv_stream(receiver, &ProcessorConfig::default());
+
+            sender.unbounded_send(Ok(format!("{}{}", header, row).into_bytes())).unwrap();
+
+            // The speech point is yielded while the sender is still open.
+            let first = block_on(points.next());
+            assert!(matches!(first, Some(Ok(ActionPlotPoint::Speech(_)))));
+            drop(sender);
+            let rest: Vec<_> = block_on(points.collect());
+            assert!(rest.iter().all(Result::is_ok));
+        }
+    }
+}
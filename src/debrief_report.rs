@@ -0,0 +1,296 @@
+use crate::action_catalogue::ActionCatalogue;
+use crate::plot_structures::{ActionPlotPoint, ErrorInfo, PlotLocation, QuestionnaireItem};
+use crate::plotly_figure::PlotlyFigure;
+use crate::processor_error::ProcessorError;
+use crate::summary::{summarize_with_catalogue, SessionSummary};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+const PLOTLY_CDN_URL: &str = "https://cdn.plot.ly/plotly-2.35.2.min.js";
+#[cfg(feature = "vendored-plotly")]
+const PLOTLY_JS: &str = include_str!("../vendor/plotly-2.35.2.min.js");
+
+/// Where the report page gets plotly.js from.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum PlotlySource {
+    /// A script tag loading plotly.js from its CDN, the timeline needs network to show.
+    #[default]
+    Cdn,
+    /// The library vendored at `vendor/plotly-2.35.2.min.js` is written into the page, which then opens offline.
+    #[cfg(feature = "vendored-plotly")]
+    Inline,
+}
+
+/// An erroneous or missed action with the advice of its rule.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Finding {
+    pub kind: &'static str,
+    pub name: String,
+    pub timestamp: String,
+    pub username: String,
+    pub severity: String,
+    pub advice: String
+}
+
+impl Finding {
+    fn new(kind: &'static str, name: &str, location: &PlotLocation, username: &str, error_info: &ErrorInfo) -> Self {
+        Self {
+            kind,
+            name: name.to_owned(),
+            timestamp: location.timestamp.timestamp.clone(),
+            username: username.to_owned(),
+            severity: error_info.severity.to_string(),
+            advice: error_info.advice.clone(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StageFindings {
+    pub stage: (u32, String),
+    pub findings: Vec<Finding>
+}
+
+/// Debrief page of a session, rendered by `to_html` as a single file that only needs Plotly from its CDN.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DebriefReport {
+    pub title: String,
+    pub figure: PlotlyFigure,
+    pub summary: SessionSummary,
+    /// Stages with at least one finding, in stage order.
+    pub stages: Vec<StageFindings>,
+    pub questionnaire: Vec<QuestionnaireItem>
+}
+
+impl DebriefReport {
+    pub fn from_points(title: &str, points: Vec<ActionPlotPoint>) -> Self {
+        Self::from_points_with_catalogue(title, points, ActionCatalogue::builtin())
+    }
+
+    pub fn from_points_with_catalogue(title: &str, points: Vec<ActionPlotPoint>, catalogue: Arc<ActionCatalogue>) -> Self {
+        let summary = summarize_with_catalogue(&points, &catalogue);
+        let mut stages: BTreeMap<u32, StageFindings> = BTreeMap::new();
+        let mut questionnaire = vec![];
+        for point in &points {
+            let (stage, finding) = match point {
+                ActionPlotPoint::Error(error) => (
+                    &error.location.stage,
+                    Finding::new("Error", &error.name, &error.location, &error.username, &error.error_info),
+                ),
+                ActionPlotPoint::MissedAction(missed) => (
+                    &missed.location.stage,
+                    Finding::new("Missed action", &missed.name, &missed.location, missed.username.as_deref().unwrap_or_default(), &missed.error_info),
+                ),
+                ActionPlotPoint::Questionnaire(item) => {
+                    questionnaire.push(item.clone());
+                    continue;
+                }
+                _ => continue,
+            };
+            stages.entry(stage.0)
+                .or_insert_with(|| StageFindings { stage: stage.clone(), findings: vec![] })
+                .findings
+                .push(finding);
+        }
+
+        Self {
+            title: title.to_owned(),
+            figure: PlotlyFigure::from_points_with_catalogue(points, catalogue),
+            summary,
+            stages: stages.into_values().collect(),
+            questionnaire,
+        }
+    }
+
+    pub fn to_html(&self) -> Result<String, ProcessorError> {
+        self.to_html_with_plotly(PlotlySource::default())
+    }
+
+    pub fn to_html_with_plotly(&self, plotly: PlotlySource) -> Result<String, ProcessorError> {
+        // `</` cannot appear inside the script element, `<\/` is the same json string.
+        let figure = self.figure.to_json().map_err(|e| ProcessorError::Io { message: e.to_string() })?.replace("</", "<\\/");
+        let title = escape(&self.title);
+
+        let mut html = String::new();
+        let _ = writeln!(html, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>");
+        let _ = match plotly {
+            PlotlySource::Cdn => writeln!(html, "<script src=\"{}\"></script>", PLOTLY_CDN_URL),
+            #[cfg(feature = "vendored-plotly")]
+            PlotlySource::Inline => writeln!(html, "<script>{}</script>", PLOTLY_JS.trim_end()),
+        };
+        let _ = write!(
+            html,
+            "<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n\
+             <div id=\"timeline\"></div>\n\
+             <script>const figure = {figure}; Plotly.newPlot(\"timeline\", figure.data, figure.layout);</script>\n"
+        );
+        self.write_summary(&mut html);
+        self.write_findings(&mut html);
+        self.write_questionnaire(&mut html);
+        html.push_str("</body>\n</html>\n");
+        Ok(html)
+    }
+
+    fn write_summary(&self, html: &mut String) {
+        let summary = &self.summary;
+        let findings = |kind| self.stages.iter().flat_map(|stage| &stage.findings).filter(|finding| finding.kind == kind).count();
+        let rows = [
+            ("Session duration", minutes(Some(summary.session_seconds))),
+            ("Total CPR", minutes(Some(summary.total_cpr_seconds))),
+            ("CPR fraction", summary.cpr_fraction.map(|fraction| format!("{:.0}%", fraction * 100.0)).unwrap_or_else(|| "-".to_owned())),
+            ("Time to first shock", minutes(summary.time_to_first_shock)),
+            ("Time to first epinephrine", minutes(summary.time_to_first_epinephrine)),
+            ("Longest hands-off", minutes(summary.longest_hands_off.as_ref().map(|interval| interval.duration_seconds))),
+            ("Pulse checks", summary.pulse_check_count.to_string()),
+            ("Errors", findings("Error").to_string()),
+            ("Missed actions", findings("Missed action").to_string()),
+        ];
+        html.push_str("<h2>Summary</h2>\n<table>\n");
+        for (label, value) in rows {
+            let _ = writeln!(html, "<tr><th>{}</th><td>{}</td></tr>", label, escape(&value));
+        }
+        html.push_str("</table>\n");
+    }
+
+    fn write_findings(&self, html: &mut String) {
+        html.push_str("<h2>Errors and missed actions</h2>\n");
+        if self.stages.is_empty() {
+            html.push_str("<p>None</p>\n");
+        }
+        for stage in &self.stages {
+            let _ = writeln!(html, "<h3>{}. {}</h3>", stage.stage.0, escape(&stage.stage.1));
+            html.push_str("<table>\n<tr><th>Time</th><th></th><th>Action</th><th>Participant</th><th>Severity</th><th>Advice</th></tr>\n");
+            for finding in &stage.findings {
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    escape(&finding.timestamp),
+                    finding.kind,
+                    escape(&finding.name),
+                    escape(&finding.username),
+                    escape(&finding.severity),
+                    escape(&finding.advice)
+                );
+            }
+            html.push_str("</table>\n");
+        }
+    }
+
+    fn write_questionnaire(&self, html: &mut String) {
+        html.push_str("<h2>Questionnaire</h2>\n");
+        if self.questionnaire.is_empty() {
+            html.push_str("<p>No questionnaire results</p>\n");
+            return;
+        }
+        html.push_str("<table>\n<tr><th>Question</th><th>Rating</th><th>Scale</th></tr>\n");
+        for item in &self.questionnaire {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}-{}</td></tr>",
+                escape(&item.question),
+                item.rating,
+                item.scale.min,
+                item.scale.max
+            );
+        }
+        html.push_str("</table>\n");
+    }
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin-bottom:1em}\
+th,td{border:1px solid #ccc;padding:4px 8px;text-align:left;vertical-align:top}";
+
+fn minutes(seconds: Option<u32>) -> String {
+    seconds.map_or_else(|| "-".to_owned(), |seconds| format!("{}:{:02}", seconds / 60, seconds % 60))
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plot_structures::{MissedAction, RatingScale, Severity};
+    use crate::test_support::stage_location;
+
+    fn missed_action(stage: (u32, &str), advice: &str) -> ActionPlotPoint {
+        ActionPlotPoint::MissedAction(MissedAction {
+            location: stage_location(75, stage),
+            name: "Epinephrine".to_owned(),
+            username: None,
+            error_info: ErrorInfo {
+                action_rule: "Epinephrine".to_owned(),
+                severity: Severity::CriticalError,
+                expectation: String::new(),
+                violation: String::new(),
+                advice: advice.to_owned(),
+            },
+        })
+    }
+
+    mod from_points {
+        use super::*;
+
+        #[test]
+        fn findings_by_stage() {
+            let points = vec![
+                missed_action((2, "V-Fib"), "Give epinephrine"),
+                missed_action((1, "V-Tach 2D"), "Shock first"),
+                ActionPlotPoint::Questionnaire(QuestionnaireItem { question: "Teamwork".to_owned(), rating: 4, scale: RatingScale { min: 1, max: 5 } }),
+            ];
+
+            let report = DebriefReport::from_points("Session", points);
+
+            assert_eq!(report.stages.iter().map(|stage| stage.stage.0).collect::<Vec<_>>(), vec![1, 2]);
+            assert_eq!(report.stages[1].findings[0].advice, "Give epinephrine");
+            assert_eq!(report.questionnaire.len(), 1);
+        }
+    }
+
+    mod to_html {
+        use super::*;
+
+        #[test]
+        fn escaped_text() {
+            let report = DebriefReport::from_points("Session <1>", vec![missed_action((1, "V-Tach 2D"), "Use < 2 minutes</script>")]);
+
+            let html = report.to_html().unwrap();
+
+            assert!(html.contains("<title>Session &lt;1&gt;</title>"));
+            assert!(html.contains("<td>Use &lt; 2 minutes&lt;/script&gt;</td>"));
+            assert!(html.contains("<td>00:01:15</td><td>Missed action</td>"));
+            assert!(!html.contains("minutes</script>"));
+        }
+
+        #[test]
+        fn cdn_plotly() {
+            let html = DebriefReport::from_points("Session", vec![]).to_html().unwrap();
+
+            assert!(html.contains(&format!("<script src=\"{}\"></script>", PLOTLY_CDN_URL)));
+        }
+
+        #[cfg(feature = "vendored-plotly")]
+        #[test]
+        fn inline_plotly() {
+            let html = DebriefReport::from_points("Session", vec![]).to_html_with_plotly(PlotlySource::Inline).unwrap();
+
+            assert!(PLOTLY_JS.len() > 1024 * 1024);
+            assert!(PLOTLY_JS.contains("newPlot"));
+            assert!(html.contains(&format!("<script>{}</script>", PLOTLY_JS.trim_end())));
+            assert!(!html.contains(PLOTLY_CDN_URL));
+        }
+    }
+}
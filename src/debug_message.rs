@@ -6,7 +6,7 @@ macro_rules! print_debug_message {
     ($($arg:tt)*) => {
         #[cfg(debug_assertions)]
         {
-            println!($($arg)*);
+            eprintln!($($arg)*);
         }
     };
 }
@@ -1,17 +1,28 @@
 use std::borrow::ToOwned;
 use crate::action_csv_row::ActionCsvRow;
 use crate::utils;
-use crate::plot_structures::{CsvRowTime, PlotLocation};
-
-const CPR_START_MARKERS: [&'static str; 2] = ["begin cpr", "enter cpr"];
-const CPR_END_MARKERS: [&'static str; 2]  = ["stop cpr", "end cpr"];
-const ERROR_MARKER_TIME_THRESHOLD: u32 = 2;
+use crate::plot_structures::PlotLocation;
+use crate::processor_config::ProcessorConfig;
+use csv::StringRecord;
+
+const RULE_TRIGGERED: &str = "Error-Triggered";
+const RULE_NOT_TRIGGERED: &str = "Error-Did-Not-Trigger";
+const SPEECH_ROW_SUFFIX: &str = "(speech)";
+const SPEECH_NO_MATCH_MARKER: &str = "NOMATCH:";
+// The simulator export spells the section title this way.
+const QUESTIONNAIRE_SECTION_MARKER: &str = "questionnare results";
+const QUESTIONNAIRE_HEADER: [&str; 3] = ["description", "rating", "rating scale"];
+// Drugs of the catalogue medications the protocol checks look for.
+pub const EPINEPHRINE: &str = "epinephrine";
+pub const AMIODARONE: &str = "amiodarone";
+// Rule rows use this in place of a username when no participant is involved.
+pub const NO_USER: &str = "NA";
 
 pub fn is_action_row(csv_row: &ActionCsvRow) -> bool {
     csv_row.parsed_stage.is_some() &&
         !csv_row.subaction_time.trim().is_empty() &&
         !csv_row.subaction_name.is_empty() &&
-        !is_missed_action(csv_row) &&
+        !is_rule_evaluation(csv_row) &&
         csv_row.cpr_boundary.is_none()
 }
 
@@ -24,37 +35,64 @@ pub fn is_stage_boundary(csv_row: &ActionCsvRow) -> bool {
         csv_row.new_value.is_empty()
 }
 
-pub fn cpr_boundary(csv_row: &ActionCsvRow) -> Option<String> {
+pub fn cpr_boundary(csv_row: &ActionCsvRow, config: &ProcessorConfig) -> Option<String> {
     let normalized_action_name = utils::normalize_whitespace(csv_row.subaction_name.to_lowercase().as_str());
-    if CPR_START_MARKERS.contains(&&*normalized_action_name) { Some("START".to_owned()) } else if
-        CPR_END_MARKERS.contains(&&*normalized_action_name) { Some("END".to_owned()) } else { None }
+    if config.cpr_start_markers.contains(&normalized_action_name) { Some("START".to_owned()) } else if
+        config.cpr_end_markers.contains(&normalized_action_name) { Some("END".to_owned()) } else { None }
+}
+pub fn is_rule_evaluation(csv_row: &ActionCsvRow) -> bool {
+    let outcome = csv_row.old_value.trim();
+    (outcome == RULE_TRIGGERED || outcome == RULE_NOT_TRIGGERED) &&
+        csv_row.score.trim().starts_with("Action-Was-")
+}
+
+pub fn is_rule_triggered(csv_row: &ActionCsvRow) -> bool {
+    csv_row.old_value.trim() == RULE_TRIGGERED
 }
+
 pub fn is_error_action_marker(csv_row: &ActionCsvRow) -> bool {
-    csv_row.old_value.trim() == "Error-Triggered" &&
+    csv_row.old_value.trim() == RULE_TRIGGERED &&
         csv_row.score.trim() == "Action-Was-Performed"
 }
 
 pub fn is_missed_action(csv_row: &ActionCsvRow) -> bool {
-    csv_row.old_value.trim() == "Error-Triggered" &&
+    csv_row.old_value.trim() == RULE_TRIGGERED &&
         csv_row.score.trim() == "Action-Was-Not-Performed"
 }
 
+pub fn is_speech_row(csv_row: &ActionCsvRow) -> bool {
+    csv_row.action_vital_name.trim().ends_with(SPEECH_ROW_SUFFIX)
+}
+
+pub fn is_unrecognized_speech(csv_row: &ActionCsvRow) -> bool {
+    csv_row.speech_command.trim().starts_with(SPEECH_NO_MATCH_MARKER)
+}
+
+pub fn is_questionnaire_marker(record: &StringRecord) -> bool {
+    record.iter().filter(|field| !field.trim().is_empty()).count() == 1 &&
+        record.get(0).is_some_and(|field| utils::normalize_whitespace(field).to_lowercase() == QUESTIONNAIRE_SECTION_MARKER)
+}
+
+pub fn is_questionnaire_header(record: &StringRecord) -> bool {
+    record.iter()
+        .map(|field| field.trim().to_lowercase())
+        .eq(QUESTIONNAIRE_HEADER.iter().map(|header| header.to_string()))
+}
+
 pub fn check_cpr(csv_row: &ActionCsvRow) -> Option<(String, PlotLocation)> {
-    csv_row.cpr_boundary.clone().and_then(|cpr_boundary| {
-        Some((cpr_boundary, PlotLocation::new(csv_row)))
-    })
+    csv_row.cpr_boundary.clone().map(|cpr_boundary| (cpr_boundary, PlotLocation::new(csv_row)))
 }
 
-pub fn can_mark_each_other(csv_row1: &ActionCsvRow, csv_row2: &ActionCsvRow) -> bool{
-    let marker_time: u32 = csv_row1.timestamp.clone().unwrap_or(CsvRowTime::default()).total_seconds;
-    let current_time: u32 = csv_row2.timestamp.clone().unwrap_or(CsvRowTime::default()).total_seconds;
+pub fn can_mark_each_other(csv_row1: &ActionCsvRow, csv_row2: &ActionCsvRow, time_threshold: u32) -> bool{
+    let marker_time: u32 = csv_row1.timestamp.clone().unwrap_or_default().total_seconds;
+    let current_time: u32 = csv_row2.timestamp.clone().unwrap_or_default().total_seconds;
 
-    marker_time.abs_diff(current_time)<=ERROR_MARKER_TIME_THRESHOLD
+    marker_time.abs_diff(current_time)<=time_threshold
 }
 
-pub fn is_erroneous_action(csv_row: &ActionCsvRow, error_marker_row: &ActionCsvRow) -> bool{
+pub fn is_erroneous_action(csv_row: &ActionCsvRow, error_marker_row: &ActionCsvRow, time_threshold: u32) -> bool{
     csv_row.action_point && error_marker_row.username == csv_row.action_vital_name &&
-        can_mark_each_other(csv_row, error_marker_row)
+        can_mark_each_other(csv_row, error_marker_row, time_threshold)
 }
 
 #[cfg(test)]
@@ -120,6 +158,19 @@ mod tests {
             assert!(!is_action_row(&csv_row));
         }
 
+        #[test]
+        fn is_false_error_marker() {
+            let csv_row = ActionCsvRow {
+                parsed_stage: Some((1,"Action".to_owned())),
+                subaction_time: "Error".to_owned(),
+                subaction_name: "Action-Should-Not-Be-Performed".to_owned(),
+                score: "Action-Was-Performed".to_owned(),
+                old_value: "Error-Triggered".to_owned(),
+                ..Default::default()
+            };
+            assert!(!is_action_row(&csv_row));
+        }
+
         #[test]
         fn is_false_cpr_boundary() {
             let csv_row = ActionCsvRow {
@@ -225,6 +276,7 @@ mod tests {
     mod test_cpr_boundary {
         use crate::action_csv_row::ActionCsvRow;
         use crate::detection::cpr_boundary;
+        use crate::processor_config::ProcessorConfig;
 
         #[test]
         fn is_start() {
@@ -232,7 +284,7 @@ mod tests {
                 subaction_name: "begin cpr".to_owned(),
                 ..Default::default()
             };
-            assert_eq!(cpr_boundary(&csv_row), Some("START".to_owned()));
+            assert_eq!(cpr_boundary(&csv_row, &ProcessorConfig::default()), Some("START".to_owned()));
         }
 
         #[test]
@@ -241,7 +293,7 @@ mod tests {
                 subaction_name: "stop cpr".to_owned(),
                 ..Default::default()
             };
-            assert_eq!(cpr_boundary(&csv_row), Some("END".to_owned()));
+            assert_eq!(cpr_boundary(&csv_row, &ProcessorConfig::default()), Some("END".to_owned()));
         }
 
         #[test]
@@ -250,7 +302,7 @@ mod tests {
                 subaction_name: "other action".to_owned(),
                 ..Default::default()
             };
-            assert_eq!(cpr_boundary(&csv_row), None);
+            assert_eq!(cpr_boundary(&csv_row, &ProcessorConfig::default()), None);
         }
 
         #[test]
@@ -259,7 +311,7 @@ mod tests {
                 subaction_name: "Begin CPR".to_owned(),
                 ..Default::default()
             };
-            assert_eq!(cpr_boundary(&csv_row), Some("START".to_owned()));
+            assert_eq!(cpr_boundary(&csv_row, &ProcessorConfig::default()), Some("START".to_owned()));
         }
 
         #[test]
@@ -268,7 +320,7 @@ mod tests {
                 subaction_name: "Stop CPR".to_owned(),
                 ..Default::default()
             };
-            assert_eq!(cpr_boundary(&csv_row), Some("END".to_owned()));
+            assert_eq!(cpr_boundary(&csv_row, &ProcessorConfig::default()), Some("END".to_owned()));
         }
 
         #[test]
@@ -277,7 +329,62 @@ mod tests {
                 subaction_name: "  begin cpr  ".to_owned(),
                 ..Default::default()
             };
-            assert_eq!(cpr_boundary(&csv_row), Some("START".to_owned()));
+            assert_eq!(cpr_boundary(&csv_row, &ProcessorConfig::default()), Some("START".to_owned()));
+        }
+
+        #[test]
+        fn configured_markers() {
+            let config = ProcessorConfig::builder()
+                .cpr_start_markers(["Start Compressions"])
+                .cpr_end_markers(["Pause Compressions"])
+                .build();
+            let start_row = ActionCsvRow {
+                subaction_name: "start compressions".to_owned(),
+                ..Default::default()
+            };
+            let default_marker_row = ActionCsvRow {
+                subaction_name: "begin cpr".to_owned(),
+                ..Default::default()
+            };
+
+            assert_eq!(cpr_boundary(&start_row, &config), Some("START".to_owned()));
+            assert_eq!(cpr_boundary(&default_marker_row, &config), None);
+        }
+    }
+
+    mod test_is_rule_evaluation {
+        use crate::action_csv_row::ActionCsvRow;
+        use crate::detection::{is_rule_evaluation, is_rule_triggered};
+
+        #[test]
+        fn is_true_triggered() {
+            let csv_row = ActionCsvRow {
+                old_value: "Error-Triggered".to_owned(),
+                score: "Action-Was-Not-Performed".to_owned(),
+                ..Default::default()
+            };
+            assert!(is_rule_evaluation(&csv_row));
+            assert!(is_rule_triggered(&csv_row));
+        }
+
+        #[test]
+        fn is_true_not_triggered() {
+            let csv_row = ActionCsvRow {
+                old_value: "Error-Did-Not-Trigger".to_owned(),
+                score: "Action-Was-Performed".to_owned(),
+                ..Default::default()
+            };
+            assert!(is_rule_evaluation(&csv_row));
+            assert!(!is_rule_triggered(&csv_row));
+        }
+
+        #[test]
+        fn is_false_action_row() {
+            let csv_row = ActionCsvRow {
+                score: "100".to_owned(),
+                ..Default::default()
+            };
+            assert!(!is_rule_evaluation(&csv_row));
         }
     }
 
@@ -391,6 +498,84 @@ mod tests {
         }
     }
 
+    mod test_is_speech_row {
+        use crate::action_csv_row::ActionCsvRow;
+        use crate::detection::is_speech_row;
+
+        #[test]
+        fn is_true() {
+            let csv_row = ActionCsvRow {
+                action_vital_name: "umich4(speech)".to_owned(),
+                ..Default::default()
+            };
+            assert!(is_speech_row(&csv_row));
+        }
+
+        #[test]
+        fn is_false() {
+            let csv_row = ActionCsvRow {
+                action_vital_name: "(1)V-Tach 2D(action)".to_owned(),
+                ..Default::default()
+            };
+            assert!(!is_speech_row(&csv_row));
+        }
+    }
+
+    mod test_is_unrecognized_speech {
+        use crate::action_csv_row::ActionCsvRow;
+        use crate::detection::is_unrecognized_speech;
+
+        #[test]
+        fn is_true() {
+            let csv_row = ActionCsvRow {
+                speech_command: "NOMATCH: Speech could not be recognized. ".to_owned(),
+                ..Default::default()
+            };
+            assert!(is_unrecognized_speech(&csv_row));
+        }
+
+        #[test]
+        fn is_false() {
+            let csv_row = ActionCsvRow {
+                speech_command: "Checking for pulse.".to_owned(),
+                ..Default::default()
+            };
+            assert!(!is_unrecognized_speech(&csv_row));
+        }
+    }
+
+    mod test_is_questionnaire_marker {
+        use crate::detection::is_questionnaire_marker;
+        use csv::StringRecord;
+
+        #[test]
+        fn is_true() {
+            assert!(is_questionnaire_marker(&StringRecord::from(vec!["Questionnare Results"])));
+            assert!(is_questionnaire_marker(&StringRecord::from(vec![" questionnare results ", ""])));
+        }
+
+        #[test]
+        fn is_false() {
+            assert!(!is_questionnaire_marker(&StringRecord::from(vec!["0:0:2", "umich4(speech)"])));
+            assert!(!is_questionnaire_marker(&StringRecord::from(vec!["Questionnare Results", "5"])));
+        }
+    }
+
+    mod test_is_questionnaire_header {
+        use crate::detection::is_questionnaire_header;
+        use csv::StringRecord;
+
+        #[test]
+        fn is_true() {
+            assert!(is_questionnaire_header(&StringRecord::from(vec!["Description", "Rating", "Rating Scale"])));
+        }
+
+        #[test]
+        fn is_false() {
+            assert!(!is_questionnaire_header(&StringRecord::from(vec!["How effective was tha team leader?", "5", "0 - 7"])));
+        }
+    }
+
     mod test_check_cpr{
         use crate::action_csv_row::ActionCsvRow;
         use crate::detection::check_cpr;
@@ -403,6 +588,7 @@ mod tests {
                     total_seconds: 3600,
                     date_string: "2024-12-24 01:00:00".to_owned(),
                     timestamp: "01:00:00".to_owned(),
+                    ..Default::default()
                 },
                 stage: (1,"Stage 1".to_owned())
             };
@@ -422,6 +608,7 @@ mod tests {
                     total_seconds: 3600,
                     date_string: "2024-12-24 01:00:00".to_owned(),
                     timestamp: "01:00:00".to_owned(),
+                    ..Default::default()
                 },
                 stage: (1,"Stage 1".to_owned())
             };
@@ -444,6 +631,7 @@ mod tests {
                     total_seconds: 3600,
                     date_string: "2024-12-24 01:00:00".to_owned(),
                     timestamp: "01:00:00".to_owned(),
+                    ..Default::default()
                 },
                 stage: (1,"Stage 1".to_owned())
             };
@@ -462,7 +650,9 @@ mod tests {
 
     mod test_can_mark_each_other {
         use crate::action_csv_row::ActionCsvRow;
-        use crate::detection::{can_mark_each_other, ERROR_MARKER_TIME_THRESHOLD};
+        use crate::detection::can_mark_each_other;
+
+        const ERROR_MARKER_TIME_THRESHOLD: u32 = 2;
         use crate::plot_structures::CsvRowTime;
 
         #[test]
@@ -473,6 +663,7 @@ mod tests {
                     total_seconds: time,
                     date_string: "2024-12-24 01:00:00".to_owned(),
                     timestamp: "01:00:00".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
@@ -481,10 +672,11 @@ mod tests {
                     total_seconds: time + ERROR_MARKER_TIME_THRESHOLD,
                     date_string: "2024-12-24 01:00:02".to_owned(),
                     timestamp: "01:00:02".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
-            assert!(can_mark_each_other(&csv_row1, &csv_row2));
+            assert!(can_mark_each_other(&csv_row1, &csv_row2, ERROR_MARKER_TIME_THRESHOLD));
         }
 
         #[test]
@@ -495,6 +687,7 @@ mod tests {
                     total_seconds: 3600,
                     date_string: "2024-12-24 01:00:00".to_owned(),
                     timestamp: "01:00:00".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
@@ -503,10 +696,11 @@ mod tests {
                     total_seconds: time - ERROR_MARKER_TIME_THRESHOLD,
                     date_string: "2024-12-24 01:00:02".to_owned(),
                     timestamp: "01:00:02".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
-            assert!(can_mark_each_other(&csv_row1, &csv_row2));
+            assert!(can_mark_each_other(&csv_row1, &csv_row2, ERROR_MARKER_TIME_THRESHOLD));
         }
 
         #[test]
@@ -517,6 +711,7 @@ mod tests {
                     total_seconds: time,
                     date_string: "2024-12-24 01:00:00".to_owned(),
                     timestamp: "01:00:00".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
@@ -525,10 +720,11 @@ mod tests {
                     total_seconds: time + ERROR_MARKER_TIME_THRESHOLD + 1,
                     date_string: "2024-12-24 01:00:03".to_owned(),
                     timestamp: "01:00:03".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
-            assert!(!can_mark_each_other(&csv_row1, &csv_row2));
+            assert!(!can_mark_each_other(&csv_row1, &csv_row2, ERROR_MARKER_TIME_THRESHOLD));
         }
 
         #[test]
@@ -539,6 +735,7 @@ mod tests {
                     total_seconds: time,
                     date_string: "2024-12-24 01:00:00".to_owned(),
                     timestamp: "01:00:00".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
@@ -547,10 +744,11 @@ mod tests {
                     total_seconds: time - ERROR_MARKER_TIME_THRESHOLD - 1,
                     date_string: "2024-12-24 01:00:03".to_owned(),
                     timestamp: "01:00:03".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
-            assert!(!can_mark_each_other(&csv_row1, &csv_row2));
+            assert!(!can_mark_each_other(&csv_row1, &csv_row2, ERROR_MARKER_TIME_THRESHOLD));
         }
 
         #[test]
@@ -564,10 +762,11 @@ mod tests {
                     total_seconds: 3600,
                     date_string: "2024-12-24 01:00:00".to_owned(),
                     timestamp: "01:00:00".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
-            assert!(!can_mark_each_other(&csv_row1, &csv_row2));
+            assert!(!can_mark_each_other(&csv_row1, &csv_row2, ERROR_MARKER_TIME_THRESHOLD));
         }
 
         #[test]
@@ -580,13 +779,15 @@ mod tests {
                 timestamp: None,
                 ..Default::default()
             };
-            assert!(can_mark_each_other(&csv_row1, &csv_row2));
+            assert!(can_mark_each_other(&csv_row1, &csv_row2, ERROR_MARKER_TIME_THRESHOLD));
         }
     }
 
     mod test_is_erroneous_action {
         use crate::action_csv_row::ActionCsvRow;
-        use crate::detection::{is_erroneous_action, ERROR_MARKER_TIME_THRESHOLD};
+        use crate::detection::is_erroneous_action;
+
+        const ERROR_MARKER_TIME_THRESHOLD: u32 = 2;
         use crate::plot_structures::CsvRowTime;
 
         fn create_csv_row(time: u32) -> (u32, ActionCsvRow) {
@@ -598,6 +799,7 @@ mod tests {
                     total_seconds: time,
                     date_string: "2024-12-24 01:00:00".to_owned(),
                     timestamp: "01:00:00".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
@@ -614,11 +816,12 @@ mod tests {
                     total_seconds: time-ERROR_MARKER_TIME_THRESHOLD,
                     date_string: "2024-12-24 01:00:02".to_owned(),
                     timestamp: "01:00:02".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
 
-            assert!(is_erroneous_action(&csv_row, &error_marker_row));
+            assert!(is_erroneous_action(&csv_row, &error_marker_row, ERROR_MARKER_TIME_THRESHOLD));
         }
 
         #[test]
@@ -631,6 +834,7 @@ mod tests {
                     total_seconds: time,
                     date_string: "2024-12-24 01:00:00".to_owned(),
                     timestamp: "01:00:00".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
@@ -640,10 +844,11 @@ mod tests {
                     total_seconds: time+ERROR_MARKER_TIME_THRESHOLD,
                     date_string: "2024-12-24 01:00:02".to_owned(),
                     timestamp: "01:00:02".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
-            assert!(!is_erroneous_action(&csv_row, &error_marker_row));
+            assert!(!is_erroneous_action(&csv_row, &error_marker_row, ERROR_MARKER_TIME_THRESHOLD));
         }
 
         #[test]
@@ -656,10 +861,11 @@ mod tests {
                     total_seconds: time+ERROR_MARKER_TIME_THRESHOLD+1,
                     date_string: "2024-12-24 01:00:05".to_owned(),
                     timestamp: "01:00:05".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
-            assert!(!is_erroneous_action(&csv_row, &error_marker_row));
+            assert!(!is_erroneous_action(&csv_row, &error_marker_row, ERROR_MARKER_TIME_THRESHOLD));
         }
 
         #[test]
@@ -672,10 +878,11 @@ mod tests {
                     total_seconds: time-ERROR_MARKER_TIME_THRESHOLD-1,
                     date_string: "2024-12-24 01:00:05".to_owned(),
                     timestamp: "01:00:05".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
-            assert!(!is_erroneous_action(&csv_row, &error_marker_row));
+            assert!(!is_erroneous_action(&csv_row, &error_marker_row, ERROR_MARKER_TIME_THRESHOLD));
         }
 
         #[test]
@@ -688,6 +895,7 @@ mod tests {
                     total_seconds: time,
                     date_string: "2024-12-24 01:00:00".to_owned(),
                     timestamp: "01:00:00".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
@@ -697,10 +905,11 @@ mod tests {
                     total_seconds: time+ERROR_MARKER_TIME_THRESHOLD-1,
                     date_string: "2024-12-24 01:00:02".to_owned(),
                     timestamp: "01:00:02".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
-            assert!(!is_erroneous_action(&csv_row, &error_marker_row));
+            assert!(!is_erroneous_action(&csv_row, &error_marker_row, ERROR_MARKER_TIME_THRESHOLD));
         }
     }
-}
\ No newline at end of file
+}
@@ -1,10 +1,34 @@
 use reqwest::blocking::Client;
-use std::io::{BufReader, Cursor};
+use reqwest::redirect::Policy;
+use std::io::{BufReader, Cursor, Read};
+use std::net::SocketAddr;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpOptions {
+    /// When false a redirect response fails like any other non-success status.
+    pub follow_redirects: bool,
+    /// Addresses the host of the url is connected to instead of resolving it again.
+    pub resolved_addresses: Vec<SocketAddr>,
+    /// The download fails once the body is longer.
+    pub max_body_bytes: Option<u64>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            follow_redirects: true,
+            resolved_addresses: Vec::new(),
+            max_body_bytes: None,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub(crate) enum HttpReaderError {
     RequestError(reqwest::Error),
-    HttpStatusError(reqwest::StatusCode)
+    HttpStatusError(reqwest::StatusCode),
+    ReadError(std::io::Error),
+    BodyTooLarge(u64)
 }
 
 impl std::fmt::Display for HttpReaderError {
@@ -12,21 +36,44 @@ impl std::fmt::Display for HttpReaderError {
         match self {
             HttpReaderError::RequestError(e) => write!(f, "Request error: {}", e),
             HttpReaderError::HttpStatusError(status) => write!(f, "HTTP status error: {}", status),
+            HttpReaderError::ReadError(e) => write!(f, "Could not read the response body: {}", e),
+            HttpReaderError::BodyTooLarge(limit) => write!(f, "Response body is larger than {} bytes", limit),
         }
     }
 }
 
 impl std::error::Error for HttpReaderError {}
 
-pub(crate) fn create_http_reader(url: &str) -> Result<BufReader<Box<dyn std::io::Read + Send + Sync>>, HttpReaderError> {
-    let client = Client::new();
+pub(crate) fn create_http_reader(url: &str, options: &HttpOptions) -> Result<BufReader<Box<dyn std::io::Read + Send + Sync>>, HttpReaderError> {
+    let mut builder = Client::builder();
+    if !options.follow_redirects {
+        builder = builder.redirect(Policy::none());
+    }
+    if let Some(host) = reqwest::Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_owned)) {
+        if !options.resolved_addresses.is_empty() {
+            builder = builder.resolve_to_addrs(&host, &options.resolved_addresses);
+        }
+    }
+    let client = builder.build().map_err(HttpReaderError::RequestError)?;
     let response = client.get(url).send().map_err(HttpReaderError::RequestError)?;
 
     if !response.status().is_success() {
         return Err(HttpReaderError::HttpStatusError(response.status()));
     }
 
-    let body = response.bytes().map_err(HttpReaderError::RequestError)?;
+    let mut body = Vec::new();
+    match options.max_body_bytes {
+        // One byte more than allowed tells a body of exactly the limit from a longer one.
+        Some(limit) => {
+            response.take(limit + 1).read_to_end(&mut body).map_err(HttpReaderError::ReadError)?;
+            if body.len() as u64 > limit {
+                return Err(HttpReaderError::BodyTooLarge(limit));
+            }
+        }
+        None => {
+            body = response.bytes().map_err(HttpReaderError::RequestError)?.to_vec();
+        }
+    }
     let reader: Box<dyn std::io::Read + Send + Sync> = Box::new(Cursor::new(body));
     let buf_reader = BufReader::new(reader);
 
@@ -50,7 +97,7 @@ mod tests {
 
         let url = format!("{}{}", server.url(), "/data.csv"); // Correct way to build the URL
 
-        let reader = create_http_reader(&url)?;
+        let reader = create_http_reader(&url, &HttpOptions::default())?;
 
         let mut csv_reader = csv::ReaderBuilder::new().has_headers(true).from_reader(reader);
 
@@ -71,9 +118,46 @@ mod tests {
             .with_status(404)
             .create();
         let url = format!("{}{}", server.url(), "/notfound");
-        let result = create_http_reader(&url);
+        let result = create_http_reader(&url, &HttpOptions::default());
         assert!(result.is_err());
         mock.assert();
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn redirect_not_followed() {
+        let mut server = Server::new();
+        server.mock("GET", "/moved").with_status(302).with_header("location", "/data.csv").create();
+        let target = server.mock("GET", "/data.csv").with_status(200).with_body("col1\nval1\n").expect(0).create();
+        let options = HttpOptions { follow_redirects: false, ..Default::default() };
+
+        let result = create_http_reader(&format!("{}/moved", server.url()), &options);
+
+        assert!(matches!(result, Err(HttpReaderError::HttpStatusError(status)) if status.as_u16() == 302));
+        target.assert();
+    }
+
+    #[test]
+    fn resolved_address_used() {
+        let mut server = Server::new();
+        let mock = server.mock("GET", "/data.csv").with_status(200).with_body("col1\nval1\n").create();
+        let options = HttpOptions { resolved_addresses: vec![server.socket_address()], ..Default::default() };
+
+        let result = create_http_reader(&format!("http://upstream.invalid:{}/data.csv", server.socket_address().port()), &options);
+
+        assert!(result.is_ok());
+        mock.assert();
+    }
+
+    #[test]
+    fn body_over_limit() {
+        let mut server = Server::new();
+        server.mock("GET", "/data.csv").with_status(200).with_body("col1\nval1\n").create();
+        let url = format!("{}/data.csv", server.url());
+
+        let limited = |max_body_bytes| create_http_reader(&url, &HttpOptions { max_body_bytes: Some(max_body_bytes), ..Default::default() });
+
+        assert!(matches!(limited(9), Err(HttpReaderError::BodyTooLarge(9))));
+        assert!(limited(10).is_ok());
+    }
+}
@@ -1,30 +1,51 @@
 mod file_reader;
-mod http_reader;
+pub(crate) mod http_reader;
+pub(crate) mod tail_reader;
 
-use file_reader::create_file_reader;
-use http_reader::create_http_reader;
+use crate::processor_error::ProcessorError;
+use file_reader::{create_file_reader, FileReaderError};
+use http_reader::{create_http_reader, HttpOptions, HttpReaderError};
 use std::io::Read;
 use url::Url;
 
-pub fn create_reader(src: &str) -> Result<Box<dyn Read>, String> {
+fn file_reader_error(err: FileReaderError) -> ProcessorError {
+    match err {
+        FileReaderError::IoError(e) => ProcessorError::Io { message: e.to_string() },
+    }
+}
+
+fn http_reader_error(url: &str, err: HttpReaderError) -> ProcessorError {
+    match err {
+        HttpReaderError::RequestError(e) => ProcessorError::Request { url: url.to_owned(), message: e.to_string() },
+        HttpReaderError::HttpStatusError(status) => ProcessorError::HttpStatus { url: url.to_owned(), status: status.as_u16() },
+        err @ (HttpReaderError::ReadError(_) | HttpReaderError::BodyTooLarge(_)) => ProcessorError::Request { url: url.to_owned(), message: err.to_string() },
+    }
+}
+
+pub fn create_reader(src: &str) -> Result<Box<dyn Read>, ProcessorError> {
+    create_reader_with(src, &HttpOptions::default())
+}
+
+/// Same as `create_reader`, with `http_options` applying when `src` is an http or https url.
+pub fn create_reader_with(src: &str, http_options: &HttpOptions) -> Result<Box<dyn Read>, ProcessorError> {
     // Check if the input is a valid URL
     if let Ok(url) = Url::parse(src) {
         if url.scheme() == "http" || url.scheme() == "https" {
             // Create an HTTP reader
-            match create_http_reader(src) {
+            match create_http_reader(src, http_options) {
                 Ok(reader) => Ok(Box::new(reader)),
-                Err(err) => Err(format!("Error creating HTTP reader: {}", err)),
+                Err(err) => Err(http_reader_error(src, err)),
             }
         } else {
             // If the URL scheme is not HTTP or HTTPS, treat it as a file path
             create_file_reader(src)
-                .map_err(|err| format!("Error creating file reader: {}", err))
+                .map_err(file_reader_error)
                 .map(|reader| Box::new(reader) as Box<dyn Read>)
         }
     } else {
         // If the input is not a valid URL, treat it as a file path
         create_file_reader(src)
-            .map_err(|err| format!("Error creating file reader: {}", err))
+            .map_err(file_reader_error)
             .map(|reader| Box::new(reader) as Box<dyn Read>)
     }
 }
@@ -76,6 +97,18 @@ mod tests {
     fn test_create_reader_file_not_found() {
         let result = create_reader("non_existent_file.txt");
 
-        assert!(result.is_err());
+        assert!(matches!(result, Err(ProcessorError::Io { .. })));
+    }
+
+    #[test]
+    fn test_create_reader_http_status() {
+        let mut server = mockito::Server::new();
+        let mock = server.mock("GET", "/missing.csv").with_status(404).create();
+        let url = format!("{}/missing.csv", server.url());
+
+        let result = create_reader(&url);
+
+        mock.assert();
+        assert_eq!(result.err(), Some(ProcessorError::HttpStatus { url, status: 404 }));
     }
 }
\ No newline at end of file
@@ -0,0 +1,203 @@
+use std::cell::Cell;
+use std::fs::{File, Metadata};
+use std::io::{Error as IoError, Read};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TailOptions {
+    /// The file is considered complete once it has not grown for this long.
+    pub idle_timeout: Duration,
+    pub poll_interval: Duration,
+}
+
+impl Default for TailOptions {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(30),
+            poll_interval: Duration::from_millis(250),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TailReset {
+    Truncated,
+    Rotated,
+}
+
+/// Follows a file that is still being written like `tail -f`. Reading blocks until more data is appended,
+/// so a partially written last line is completed before the csv reader sees it. End of data is reported
+/// once the file has been idle for `idle_timeout`. When the file is truncated (it becomes shorter than what
+/// has already been read) or replaced, the reader fails with an error and records the reason in `reset`.
+pub(crate) struct TailReader {
+    path: String,
+    file: File,
+    identity: Option<(u64, u64)>,
+    position: u64,
+    options: TailOptions,
+    last_growth: Instant,
+    reset: Rc<Cell<Option<TailReset>>>,
+}
+
+#[cfg(unix)]
+fn file_identity(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+impl TailReader {
+    pub(crate) fn open(path: &str, options: TailOptions, reset: Rc<Cell<Option<TailReset>>>) -> Result<Self, IoError> {
+        let file = File::open(path)?;
+        let identity = file_identity(&file.metadata()?);
+        Ok(Self {
+            path: path.to_owned(),
+            file,
+            identity,
+            position: 0,
+            options,
+            last_growth: Instant::now(),
+            reset,
+        })
+    }
+
+    fn check_reset(&self) -> Option<TailReset> {
+        // The path may briefly not exist while the file is being rotated.
+        if let Ok(metadata) = std::fs::metadata(&self.path) {
+            if self.identity.is_some() && file_identity(&metadata) != self.identity {
+                return Some(TailReset::Rotated);
+            }
+        }
+        match self.file.metadata() {
+            Ok(metadata) if metadata.len() < self.position => Some(TailReset::Truncated),
+            _ => None,
+        }
+    }
+}
+
+impl Read for TailReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let read = self.file.read(buf)?;
+            if read > 0 {
+                self.position += read as u64;
+                self.last_growth = Instant::now();
+                return Ok(read);
+            }
+            if let Some(reset) = self.check_reset() {
+                self.reset.set(Some(reset));
+                return Err(IoError::other(format!("{} was {:?} while being followed", self.path, reset)));
+            }
+            if self.last_growth.elapsed() >= self.options.idle_timeout {
+                return Ok(0);
+            }
+            thread::sleep(self.options.poll_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    fn test_options() -> TailOptions {
+        TailOptions {
+            idle_timeout: Duration::from_millis(300),
+            poll_interval: Duration::from_millis(10),
+        }
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("tail_reader_{}_{}", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn append(path: &str, content: &str) {
+        let mut file = OpenOptions::new().append(true).create(true).open(path).unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+    }
+
+    mod read {
+        use super::*;
+
+        #[test]
+        fn follows_appended_data() {
+            let path = temp_path("appended");
+            append(&path, "a,b\n1,");
+            let mut reader = TailReader::open(&path, test_options(), Rc::default()).unwrap();
+
+            let writer_path = path.clone();
+            let writer = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                append(&writer_path, "2\n");
+            });
+            let mut content = String::new();
+            reader.read_to_string(&mut content).unwrap();
+            writer.join().unwrap();
+
+            assert_eq!(content, "a,b\n1,2\n");
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[test]
+        fn idle_timeout_ends_data() {
+            let path = temp_path("idle");
+            append(&path, "a,b\n");
+            let mut reader = TailReader::open(&path, test_options(), Rc::default()).unwrap();
+            let started = Instant::now();
+
+            let mut content = String::new();
+            reader.read_to_string(&mut content).unwrap();
+
+            assert_eq!(content, "a,b\n");
+            assert!(started.elapsed() >= test_options().idle_timeout);
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[test]
+        fn truncation_detected() {
+            let path = temp_path("truncated");
+            append(&path, "a,b\n1,2\n");
+            let reset = Rc::new(Cell::new(None));
+            let mut reader = TailReader::open(&path, test_options(), reset.clone()).unwrap();
+            let mut buf = [0u8; 64];
+            assert_eq!(reader.read(&mut buf).unwrap(), 8);
+
+            OpenOptions::new().write(true).open(&path).unwrap().set_len(0).unwrap();
+
+            assert!(reader.read(&mut buf).is_err());
+            assert_eq!(reset.get(), Some(TailReset::Truncated));
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn rotation_detected() {
+            let path = temp_path("rotated");
+            let rotated_path = temp_path("rotated.1");
+            append(&path, "a,b\n");
+            let reset = Rc::new(Cell::new(None));
+            let mut reader = TailReader::open(&path, test_options(), reset.clone()).unwrap();
+            let mut buf = [0u8; 64];
+            assert_eq!(reader.read(&mut buf).unwrap(), 4);
+
+            std::fs::rename(&path, &rotated_path).unwrap();
+            append(&path, "c,d\n");
+
+            assert!(reader.read(&mut buf).is_err());
+            assert_eq!(reset.get(), Some(TailReset::Rotated));
+            std::fs::remove_file(&path).unwrap();
+            std::fs::remove_file(&rotated_path).unwrap();
+        }
+    }
+}
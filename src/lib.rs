@@ -6,13 +6,43 @@ mod csv_row_processor;
 mod processing_state;
 mod plot_processors;
 mod action_csv_row;
+mod rule_csv_row;
 mod utils;
 pub mod debug_message;
 pub mod plot_structures;
+pub mod plotly_figure;
+pub mod processor_error;
+pub mod summary;
+pub mod compliance;
+pub mod participants;
+pub mod batch;
+pub mod session_date;
+pub mod action_catalogue;
+pub mod processor_config;
+pub mod point_csv;
+pub mod ndjson_sink;
+pub mod debrief_report;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_export;
+#[cfg(feature = "parquet")]
+pub mod point_tables;
 pub(crate) mod csv_processor;
-pub use csv_processor::process_csv;
+pub mod csv_stream;
+#[cfg(test)]
+mod test_support;
+pub use csv_processor::{process_csv, process_tail, try_process_csv, try_process_csv_numbered, NumberedResult};
+pub use input_sources::{create_reader, create_reader_with};
+pub use input_sources::http_reader::HttpOptions;
+pub use input_sources::tail_reader::TailOptions;
+pub use processor_config::{MarkerMatching, ProcessorConfig, ProcessorConfigBuilder};
+pub use processor_error::ProcessorError;
+pub use session_date::SessionDate;
 use crate::plot_structures::ActionPlotPoint;
 
-pub fn process(src: &str) -> Box<dyn Iterator<Item = Result<ActionPlotPoint, String>>>{
-    process_csv(input_sources::create_reader(src).unwrap(), 5)
+/// Processes a file path or url, the session date is inferred from its name.
+pub fn process(src: &str) -> Box<dyn Iterator<Item = Result<ActionPlotPoint, ProcessorError>>>{
+    match input_sources::create_reader(src) {
+        Ok(reader) => process_csv(reader, &ProcessorConfig::default().for_source(src)),
+        Err(e) => Box::new(std::iter::once(Err(e))),
+    }
 }
\ No newline at end of file
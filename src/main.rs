@@ -1,67 +1,401 @@
-use mteam_dashboard_action_processor::debug_message::print_debug_message;
+use clap::{Parser, Subcommand, ValueEnum};
+use mteam_dashboard_action_processor::batch::{process_directory, session_id};
+use mteam_dashboard_action_processor::debrief_report::{DebriefReport, PlotlySource};
+use mteam_dashboard_action_processor::ndjson_sink::NdjsonSink;
 use mteam_dashboard_action_processor::plot_structures::ActionPlotPoint;
-use mteam_dashboard_action_processor::process as build_csv_reader;
-use std::{env, io};
-
-fn display_menu() -> String {
-    println!("No argument provided (give file url or path on command line whem running). Please select an option:");
-    println!("1. Enter file with path to parse");
-    println!("2. Enter URL for processing the streaming CSV text");
-    println!("3. Run with hard-coded timeline-multiplayer-09182024.csv file");
-    println!("4. Run with hard-coded https://dl.dropboxusercontent.com/scl/fi/6os941r9qnk19nkd22415/timeline-multiplayer-09182024.csv?rlkey=4lpfpmkf62fnua597t7bh3p17&st=1v2zw6n3&dl=0");
-
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).expect("Failed to read line");
-
-    match input.trim() {
-        "1" => {
-            println!("Enter file path:");
-            let mut file_path = String::new();
-            io::stdin().read_line(&mut file_path).expect("Failed to read line");
-            file_path
-        }
-        "2" => {
-            println!("Enter URL:");
-            let mut url = String::new();
-            io::stdin().read_line(&mut url).expect("Failed to read line");
-            url
-        }
-        "3" => {
-            "timeline-multiplayer-09182024.csv".to_owned()
-        }
-        "4" => {
-            "https://dl.dropboxusercontent.com/scl/fi/6os941r9qnk19nkd22415/timeline-multiplayer-09182024.csv?rlkey=4lpfpmkf62fnua597t7bh3p17&st=1v2zw6n3&dl=0".to_owned()
-        }
-        _ => { println!("Invalid option"); std::process::exit(1); }
+use mteam_dashboard_action_processor::plotly_figure::PlotlyFigure;
+use mteam_dashboard_action_processor::point_csv::write_points_csv;
+#[cfg(feature = "parquet")]
+use mteam_dashboard_action_processor::point_tables::PointTables;
+#[cfg(feature = "sqlite")]
+use mteam_dashboard_action_processor::sqlite_export::SqliteExporter;
+use mteam_dashboard_action_processor::summary::summarize_with_catalogue;
+use mteam_dashboard_action_processor::{create_reader, try_process_csv, try_process_csv_numbered, ProcessorConfig, ProcessorError};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Processes mteam dashboard timeline csv files. SRC is a file path, an http(s) url or `-` for stdin.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// Processor settings as toml, e.g. `max_rows_to_check`, `cpr_start_markers` or `catalogue`
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// File to write to instead of stdout
+    #[arg(short, long, global = true)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Emits the plot points, row errors are reported on stderr except in ndjson where they are lines of their own
+    Process {
+        src: String,
+        #[arg(long, value_enum, default_value_t = PointFormat::Json)]
+        format: PointFormat,
+        /// `source_id` of the ndjson lines, defaults to the file name of SRC without extension
+        #[arg(long)]
+        source_id: Option<String>,
+    },
+    /// Prints the session metrics as json
+    Summary { src: String },
+    /// Checks the header and every row, exits with 1 when any of them is invalid. Actions missing from the catalogue are only a warning
+    Validate { src: String },
+    /// Emits the plotly figure json
+    Plotly { src: String },
+    /// Writes the debrief report of a session as a single html page
+    Report {
+        src: String,
+        /// Page title, defaults to the file name of SRC without extension
+        #[arg(long)]
+        title: Option<String>,
+        /// Writes the vendored plotly.js into the page instead of loading it from its CDN
+        #[cfg(feature = "vendored-plotly")]
+        #[arg(long)]
+        inline_plotly: bool,
+    },
+    /// Processes every csv file in a directory into one cohort dataset
+    Batch { dir: PathBuf },
+    /// Exports the sessions into a SQLite database, a session exported before is replaced
+    #[cfg(feature = "sqlite")]
+    Sqlite {
+        database: PathBuf,
+        #[arg(required = true)]
+        srcs: Vec<String>,
+    },
+    /// Writes one parquet file per kind of point into DIR, a directory SRC is written as one cohort with a `session_id` column
+    #[cfg(feature = "parquet")]
+    Parquet { src: String, dir: PathBuf },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum PointFormat {
+    /// `{"points": [...], "errors": [...]}`
+    Json,
+    /// One point or error per line, written as the rows are processed
+    Ndjson,
+    Csv,
+}
+
+#[derive(Serialize)]
+struct PointsOutput {
+    points: Vec<ActionPlotPoint>,
+    errors: Vec<ProcessorError>,
+}
+
+fn load_config(path: Option<&Path>) -> Result<ProcessorConfig, ProcessorError> {
+    path.map_or_else(|| Ok(ProcessorConfig::default()), ProcessorConfig::load)
+}
+
+fn open_source(src: &str) -> Result<Box<dyn Read>, ProcessorError> {
+    if src == "-" {
+        Ok(Box::new(io::stdin()))
+    } else {
+        create_reader(src)
     }
 }
-fn process_csv_input(csv_iterator: Box<dyn Iterator<Item=Result<ActionPlotPoint, String>>>) {
-    for (row_idx, result) in csv_iterator.enumerate() {
-        let _item_number = row_idx + 1;
+
+fn open_output(path: Option<&Path>) -> Result<Box<dyn Write>, ProcessorError> {
+    match path {
+        Some(path) => File::create(path)
+            .map(|file| Box::new(BufWriter::new(file)) as Box<dyn Write>)
+            .map_err(|e| ProcessorError::Io { message: format!("{}: {}", path.display(), e) }),
+        None => Ok(Box::new(io::stdout().lock())),
+    }
+}
+
+/// Points of `src`, the errors of the rows are returned separately. Only an unusable source is an error.
+fn read_points(src: &str, config: &ProcessorConfig) -> Result<(Vec<ActionPlotPoint>, Vec<ProcessorError>), ProcessorError> {
+    let mut points = vec![];
+    let mut errors = vec![];
+    for result in try_process_csv(open_source(src)?, &config.for_source(src))? {
         match result {
-            // Ok(_)=> { print_debug_message!("{}", item_number); },
-            Ok(ActionPlotPoint::Error(_error_point)) => {
-                print_debug_message!("{} Error: {:#?}", _item_number, _error_point);
+            Ok(point) => points.push(point),
+            Err(e) => errors.push(e),
+        }
+    }
+    Ok((points, errors))
+}
+
+fn report_errors(errors: &[ProcessorError]) {
+    for error in errors {
+        eprintln!("{}", error);
+    }
+}
+
+fn write_json<T: Serialize>(output: Option<&Path>, value: &T) -> Result<(), ProcessorError> {
+    let io_error = |e: io::Error| ProcessorError::Io { message: e.to_string() };
+    let mut writer = open_output(output)?;
+    serde_json::to_writer(&mut writer, value).map_err(|e| ProcessorError::Io { message: e.to_string() })?;
+    writeln!(writer).map_err(io_error)?;
+    writer.flush().map_err(io_error)
+}
+
+fn write_ndjson(src: &str, source_id: &str, config: &ProcessorConfig, output: Option<&Path>) -> Result<(), ProcessorError> {
+    let results = try_process_csv_numbered(open_source(src)?, &config.for_source(src))?;
+    NdjsonSink::new(open_output(output)?, source_id).write_all(results).map(|_| ())
+}
+
+fn default_source_id(src: &str) -> String {
+    session_id(Path::new(src.split(['?', '#']).next().unwrap_or(src)))
+}
+
+fn run(cli: Cli) -> Result<ExitCode, ProcessorError> {
+    let config = load_config(cli.config.as_deref())?;
+    let output = cli.output.as_deref();
+
+    match cli.command {
+        Command::Process { src, format: PointFormat::Ndjson, source_id } => {
+            write_ndjson(&src, &source_id.unwrap_or_else(|| default_source_id(&src)), &config, output)?;
+        }
+        Command::Process { src, format, .. } => {
+            let (points, errors) = read_points(&src, &config)?;
+            if format == PointFormat::Csv {
+                write_points_csv(open_output(output)?, &points)?;
+                report_errors(&errors);
+            } else {
+                write_json(output, &PointsOutput { points, errors })?;
+            }
+        }
+        Command::Summary { src } => {
+            let (points, errors) = read_points(&src, &config)?;
+            report_errors(&errors);
+            write_json(output, &summarize_with_catalogue(&points, &config.catalogue))?;
+        }
+        Command::Validate { src } => {
+            let (points, errors) = read_points(&src, &config)?;
+            report_errors(&errors);
+            let unknown_actions = config.catalogue.unknown_actions(&points);
+            if !unknown_actions.is_empty() {
+                eprintln!("warning: actions not in the action catalogue: {}", unknown_actions.into_iter().collect::<Vec<_>>().join(", "));
             }
-            Ok(ActionPlotPoint::Action(_action_point)) => {
-                print_debug_message!("{} Action: {:#?}", _item_number, _action_point);
+            eprintln!("{}: {} points, {} errors", src, points.len(), errors.len());
+            if !errors.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Plotly { src } => {
+            let (points, errors) = read_points(&src, &config)?;
+            report_errors(&errors);
+            write_json(output, &PlotlyFigure::from_points_with_catalogue(points, config.catalogue.clone()))?;
+        }
+        Command::Report { src, title, #[cfg(feature = "vendored-plotly")] inline_plotly } => {
+            let (points, errors) = read_points(&src, &config)?;
+            report_errors(&errors);
+            let title = title.unwrap_or_else(|| default_source_id(&src));
+            let plotly = PlotlySource::default();
+            #[cfg(feature = "vendored-plotly")]
+            let plotly = if inline_plotly { PlotlySource::Inline } else { plotly };
+            let html = DebriefReport::from_points_with_catalogue(&title, points, config.catalogue.clone()).to_html_with_plotly(plotly)?;
+            let mut writer = open_output(output)?;
+            writer.write_all(html.as_bytes()).and_then(|_| writer.flush()).map_err(|e| ProcessorError::Io { message: e.to_string() })?;
+        }
+        Command::Batch { dir } => {
+            let dataset = process_directory(&dir, &config)?;
+            for failed in &dataset.failed_sessions {
+                eprintln!("{}: {}", failed.path, failed.error);
+            }
+            write_json(output, &dataset)?;
+            eprintln!("{} sessions, {} points, {} failed sessions", dataset.sessions.len(), dataset.points.len(), dataset.failed_sessions.len());
+        }
+        #[cfg(feature = "sqlite")]
+        Command::Sqlite { database, srcs } => {
+            let mut exporter = SqliteExporter::open(&database)?;
+            for src in srcs {
+                let (points, errors) = read_points(&src, &config)?;
+                report_errors(&errors);
+                exporter.export_session(&default_source_id(&src), &src, &config.session_date_for(Some(&src)), &points)?;
+                eprintln!("{}: {} points", src, points.len());
+            }
+        }
+        #[cfg(feature = "parquet")]
+        Command::Parquet { src, dir } => {
+            let tables = if Path::new(&src).is_dir() {
+                let dataset = process_directory(Path::new(&src), &config)?;
+                for failed in &dataset.failed_sessions {
+                    eprintln!("{}: {}", failed.path, failed.error);
+                }
+                PointTables::from_cohort(&dataset)?
+            } else {
+                let (points, errors) = read_points(&src, &config)?;
+                report_errors(&errors);
+                PointTables::from_points(&points)?
+            };
+            for path in tables.write_parquet(&dir)? {
+                eprintln!("{}", path.display());
             }
-            // Ok(ActionPlotPoint::Period(PeriodType::Stage, start, end)) => { print_debug_message!("{} stage_boundary: {:#?}", item_number, (start,end)); },
-            // Ok(ActionPlotPoint::MissedAction(missed_action)) => { print_debug_message!("{} missed_action: {:?}", item_number, missed_action); },
-            // Ok(ActionPlotPoint::Period(PeriodType::CPR, start, end)) => { print_debug_message!("{} stage_boundary: {:#?}", item_number, (start,end)); },
-            Err(_e) => { print_debug_message!("{} error: {}", _item_number, _e); }
-            _ => {}
         }
     }
+    Ok(ExitCode::SUCCESS)
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+fn main() -> ExitCode {
+    run(Cli::parse()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        ExitCode::FAILURE
+    })
+}
 
-    if args.len() == 2 {
-        process_csv_input(build_csv_reader(&args[1].trim()));
-    } else {
-        let src = display_menu();
-        process_csv_input(build_csv_reader(src.trim()));
+#[cfg(test)]
+mod tests {
+    mod cli {
+        use super::super::*;
+
+        #[test]
+        fn process_with_global_options() {
+            let cli = Cli::try_parse_from(["processor", "process", "timeline.csv", "--format", "ndjson", "--output", "points.ndjson", "--config", "config.toml"]).unwrap();
+
+            assert!(matches!(cli.command, Command::Process { ref src, format: PointFormat::Ndjson, source_id: None } if src == "timeline.csv"));
+            assert_eq!(cli.output, Some(PathBuf::from("points.ndjson")));
+            assert_eq!(cli.config, Some(PathBuf::from("config.toml")));
+        }
+
+        #[test]
+        fn json_is_default_format() {
+            let cli = Cli::try_parse_from(["processor", "process", "timeline.csv"]).unwrap();
+
+            assert!(matches!(cli.command, Command::Process { format: PointFormat::Json, .. }));
+        }
+
+        #[test]
+        fn default_source_id_without_query() {
+            assert_eq!(default_source_id("https://example.com/logs/timeline-multiplayer-09182024.csv?dl=0"), "timeline-multiplayer-09182024");
+            assert_eq!(default_source_id("tests/data/sample.csv"), "sample");
+        }
+
+        #[test]
+        fn source_required() {
+            assert!(Cli::try_parse_from(["processor", "summary"]).is_err());
+            assert!(Cli::try_parse_from(["processor", "timeline.csv"]).is_err());
+        }
     }
-}
\ No newline at end of file
+
+    mod run {
+        use super::super::*;
+
+        fn temp_path(name: &str) -> PathBuf {
+            std::env::temp_dir().join(format!("cli_{}_{}", std::process::id(), name))
+        }
+
+        #[test]
+        fn validate_invalid_header() {
+            let src = temp_path("invalid_header.csv");
+            std::fs::write(&src, "Name,Score\nPulse Check,1\n").unwrap();
+            let cli = Cli::try_parse_from(["processor", "validate", src.to_str().unwrap()]).unwrap();
+
+            let result = run(cli);
+
+            assert!(matches!(result, Err(ProcessorError::HeaderMismatch { .. })));
+            std::fs::remove_file(&src).unwrap();
+        }
+
+        #[test]
+        fn validate_unknown_actions_only_warned() {
+            let catalogue = temp_path("catalogue.toml");
+            let config = temp_path("catalogue_config.toml");
+            std::fs::write(&catalogue, "[[action]]\nname = \"Check Pulse\"\n").unwrap();
+            std::fs::write(&config, format!("catalogue = {:?}\n", catalogue.file_name().unwrap())).unwrap();
+            let cli = Cli::try_parse_from(["processor", "--config", config.to_str().unwrap(), "validate", "tests/data/sample.csv"]).unwrap();
+
+            assert_eq!(run(cli).unwrap(), ExitCode::SUCCESS);
+            std::fs::remove_file(&catalogue).unwrap();
+            std::fs::remove_file(&config).unwrap();
+        }
+
+        #[test]
+        fn summary_to_output_file() {
+            let output = temp_path("summary.json");
+            let cli = Cli::try_parse_from(["processor", "summary", "tests/data/sample.csv", "--output", output.to_str().unwrap()]).unwrap();
+
+            assert_eq!(run(cli).unwrap(), ExitCode::SUCCESS);
+
+            let summary: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+            assert_eq!(summary["session_seconds"], 670);
+            std::fs::remove_file(&output).unwrap();
+        }
+
+        #[test]
+        fn process_ndjson_format() {
+            let output = temp_path("points.ndjson");
+            let cli = Cli::try_parse_from(["processor", "process", "tests/data/sample.csv", "--format", "ndjson", "-o", output.to_str().unwrap()]).unwrap();
+
+            run(cli).unwrap();
+
+            let lines: Vec<serde_json::Value> = std::fs::read_to_string(&output).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+            assert!(lines.iter().all(|line| line["source_id"] == "sample"));
+            assert!(lines.iter().any(|line| line["type"] == "Stage" && line["row_number"] == 78));
+            std::fs::remove_file(&output).unwrap();
+        }
+
+        #[test]
+        fn process_csv_format() {
+            let output = temp_path("points.csv");
+            let cli = Cli::try_parse_from(["processor", "process", "tests/data/sample.csv", "--format", "csv", "-o", output.to_str().unwrap()]).unwrap();
+
+            run(cli).unwrap();
+
+            let csv = std::fs::read_to_string(&output).unwrap();
+            assert!(csv.starts_with("kind,seconds,timestamp,"));
+            assert!(csv.lines().any(|line| line.starts_with("Stage,0,00:00:00,115,1,V-Tach 2D,")));
+            std::fs::remove_file(&output).unwrap();
+        }
+
+        #[test]
+        fn report_html() {
+            let output = temp_path("report.html");
+            let cli = Cli::try_parse_from(["processor", "report", "tests/data/sample.csv", "-o", output.to_str().unwrap()]).unwrap();
+
+            run(cli).unwrap();
+
+            let html = std::fs::read_to_string(&output).unwrap();
+            assert!(html.contains("<title>sample</title>"));
+            assert!(html.contains("Plotly.newPlot"));
+            assert!(html.contains("<h2>Questionnaire</h2>\n<table>"));
+            assert!(html.contains("<script src=\"https://cdn.plot.ly/plotly-2.35.2.min.js\"></script>"));
+            std::fs::remove_file(&output).unwrap();
+        }
+
+        #[cfg(feature = "sqlite")]
+        #[test]
+        fn sqlite_twice() {
+            let database = temp_path("sessions.db");
+            for _ in 0..2 {
+                let cli = Cli::try_parse_from(["processor", "sqlite", database.to_str().unwrap(), "tests/data/sample.csv"]).unwrap();
+                run(cli).unwrap();
+            }
+
+            let connection = rusqlite::Connection::open(&database).unwrap();
+            let sessions: Vec<String> = connection.prepare("SELECT session_id FROM sessions").unwrap()
+                .query_map([], |row| row.get(0)).unwrap()
+                .collect::<Result<_, _>>().unwrap();
+            let stages: i64 = connection.query_row("SELECT COUNT(*) FROM stages", [], |row| row.get(0)).unwrap();
+            let violations: i64 = connection.query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| row.get(0)).unwrap();
+            assert_eq!(sessions, vec!["sample"]);
+            assert!(stages > 0);
+            assert_eq!(violations, 0);
+            std::fs::remove_file(&database).unwrap();
+        }
+
+        #[cfg(feature = "parquet")]
+        #[test]
+        fn parquet_cohort() {
+            let src = temp_path("parquet_cohort");
+            let dir = temp_path("parquet_tables");
+            std::fs::create_dir_all(&src).unwrap();
+            std::fs::copy("tests/data/sample.csv", src.join("sample.csv")).unwrap();
+            let cli = Cli::try_parse_from(["processor", "parquet", src.to_str().unwrap(), dir.to_str().unwrap()]).unwrap();
+
+            run(cli).unwrap();
+
+            assert!(dir.join("actions.parquet").is_file());
+            assert!(dir.join("stages.parquet").is_file());
+            std::fs::remove_dir_all(&src).unwrap();
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
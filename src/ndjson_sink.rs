@@ -0,0 +1,152 @@
+use crate::csv_processor::NumberedResult;
+use crate::plot_structures::ActionPlotPoint;
+use crate::processor_error::ProcessorError;
+use serde::Serialize;
+use serde_json::Value;
+use std::io::Write;
+
+/// `type` of the lines that report a processing error.
+pub const ERROR_LINE_TYPE: &str = "ProcessorError";
+
+#[derive(Serialize)]
+struct NdjsonLine<'a, T: Serialize> {
+    #[serde(rename = "type")]
+    line_type: &'a str,
+    source_id: &'a str,
+    row_number: Option<usize>,
+    data: T,
+}
+
+/// Writes one json object per line as the results are produced, e.g.
+/// `{"type":"Action","source_id":"timeline-multiplayer-09182024","row_number":12,"data":{...}}`.
+/// `type` is the `ActionPlotPoint` variant, or `ProcessorError` for an error whose `data` has the error `kind`.
+pub struct NdjsonSink<W: Write> {
+    writer: W,
+    source_id: String,
+}
+
+impl<W: Write> NdjsonSink<W> {
+    pub fn new(writer: W, source_id: &str) -> Self {
+        Self { writer, source_id: source_id.to_owned() }
+    }
+
+    pub fn write_point(&mut self, row_number: Option<usize>, point: &ActionPlotPoint) -> Result<(), ProcessorError> {
+        // The variant is already in `type`, so only its content is written as `data`.
+        let data = match serde_json::to_value(point).map_err(json_error)? {
+            Value::Object(variant) => variant.into_iter().next().map(|(_, content)| content).unwrap_or_default(),
+            value => value,
+        };
+        self.write_line(point.kind(), row_number, data)
+    }
+
+    pub fn write_error(&mut self, error: &ProcessorError) -> Result<(), ProcessorError> {
+        self.write_line(ERROR_LINE_TYPE, error.row_number(), error)
+    }
+
+    pub fn write_result(&mut self, row_number: Option<usize>, result: &Result<ActionPlotPoint, ProcessorError>) -> Result<(), ProcessorError> {
+        match result {
+            Ok(point) => self.write_point(row_number, point),
+            Err(e) => self.write_error(e),
+        }
+    }
+
+    /// Writes every result and flushes the writer, returns the number of lines written.
+    pub fn write_all<I>(&mut self, results: I) -> Result<usize, ProcessorError>
+    where
+        I: IntoIterator<Item = NumberedResult>,
+    {
+        let mut lines = 0;
+        for (row_number, result) in results {
+            self.write_result(row_number, &result)?;
+            lines += 1;
+        }
+        self.flush()?;
+        Ok(lines)
+    }
+
+    pub fn flush(&mut self) -> Result<(), ProcessorError> {
+        self.writer.flush().map_err(io_error)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_line<T: Serialize>(&mut self, line_type: &str, row_number: Option<usize>, data: T) -> Result<(), ProcessorError> {
+        let line = NdjsonLine { line_type, source_id: &self.source_id, row_number, data };
+        serde_json::to_writer(&mut self.writer, &line).map_err(json_error)?;
+        self.writer.write_all(b"\n").map_err(io_error)
+    }
+}
+
+fn io_error(e: std::io::Error) -> ProcessorError {
+    ProcessorError::Io { message: e.to_string() }
+}
+
+fn json_error(e: serde_json::Error) -> ProcessorError {
+    ProcessorError::Io { message: e.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plot_structures::{PeriodType, PlotLocation, SpeechEvent};
+    use crate::test_support::location;
+
+    fn lines(sink: NdjsonSink<Vec<u8>>) -> Vec<Value> {
+        String::from_utf8(sink.into_inner()).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    mod write_point {
+        use super::*;
+
+        #[test]
+        fn tagged_content() {
+            let mut sink = NdjsonSink::new(vec![], "session-a");
+            let point = ActionPlotPoint::Speech(SpeechEvent {
+                location: location(5),
+                speaker: "umich1".to_owned(),
+                transcript: "check pulse".to_owned(),
+                no_match: false,
+            });
+
+            sink.write_point(Some(4), &point).unwrap();
+
+            let lines = lines(sink);
+            assert_eq!(lines.len(), 1);
+            assert_eq!(lines[0]["type"], "Speech");
+            assert_eq!(lines[0]["source_id"], "session-a");
+            assert_eq!(lines[0]["row_number"], 4);
+            assert_eq!(lines[0]["data"]["transcript"], "check pulse");
+            assert_eq!(lines[0]["data"]["location"]["timestamp"]["total_seconds"], 5);
+        }
+
+        #[test]
+        fn period_without_row_number() {
+            let mut sink = NdjsonSink::new(vec![], "session-a");
+
+            sink.write_point(None, &ActionPlotPoint::Period(PeriodType::CPR, PlotLocation::default(), PlotLocation::default())).unwrap();
+
+            let lines = lines(sink);
+            assert_eq!(lines[0]["type"], "Period");
+            assert_eq!(lines[0]["row_number"], Value::Null);
+            assert_eq!(lines[0]["data"][0], "CPR");
+        }
+    }
+
+    mod write_error {
+        use super::*;
+
+        #[test]
+        fn row_number_of_error() {
+            let mut sink = NdjsonSink::new(vec![], "session-a");
+
+            sink.write_error(&ProcessorError::TimeParse { row_number: 3, value: "0:x:9".to_owned() }).unwrap();
+
+            let lines = lines(sink);
+            assert_eq!(lines[0]["type"], ERROR_LINE_TYPE);
+            assert_eq!(lines[0]["row_number"], 3);
+            assert_eq!(lines[0]["data"]["kind"], "TimeParse");
+        }
+    }
+}
@@ -1,14 +1,18 @@
-use chrono::{Datelike, Utc};
 use lazy_static::lazy_static;
 use regex::Regex;
 use crate::utils;
-use crate::plot_structures::CsvRowTime;
+use crate::action_catalogue::ActionCatalogue;
+use crate::session_date::SessionDate;
+use crate::plot_structures::{CsvRowTime, QuestionnaireItem, RatingScale, Rhythm, RhythmFamily, Severity, VitalSign, VitalValue, WaveformChannel};
+use csv::StringRecord;
 
 lazy_static! {
     static ref ACTION_NAME_REGEX: Regex = Regex::new(r"^\s*\((\d+)\)\s*(.+?)\s*\(action\)\s*$").unwrap();
+    static ref RATING_SCALE_REGEX: Regex = Regex::new(r"^\s*(\d+)\s*-\s*(\d+)\s*$").unwrap();
+    static ref VITAL_NAME_REGEX: Regex = Regex::new(r"^\s*(.+?)\s*\(vital\)\s*$").unwrap();
     static ref SHOCK_VALUE_REGEX: Regex = Regex::new(r"(.*?)(\b\d+[Jj]\b)(.*)").unwrap(); 
 }
-pub fn parse_time(input: &str) -> Option<CsvRowTime> {
+pub fn parse_time(input: &str, session_date: &SessionDate) -> Option<CsvRowTime> {
     // Split the input into hours, minutes, and seconds
     let parts: Vec<&str> = input.split(':').collect();
     if parts.len() != 3 {
@@ -28,26 +32,7 @@ pub fn parse_time(input: &str) -> Option<CsvRowTime> {
     // Calculate total seconds
     let total_seconds = hours * 3600 + minutes * 60 + seconds;
 
-    // Get today's UTC date
-    let today = Utc::now();
-    let date_string = format!(
-        "{}-{:02}-{:02} {:02}:{:02}:{:02}",
-        today.year(),
-        today.month(),
-        today.day(),
-        hours,
-        minutes,
-        seconds
-    );
-
-    // Format the input into HH:MM:SS
-    let formatted_input = format!("{:02}:{:02}:{:02}", hours, minutes, seconds);
-
-    Some(CsvRowTime {
-        total_seconds,
-        date_string,
-        timestamp: formatted_input,
-    })
+    Some(CsvRowTime::new(total_seconds, session_date))
 }
 
 pub fn extract_stage_name(input: &str) -> Option<(u32, String)> {
@@ -60,6 +45,102 @@ pub fn extract_stage_name(input: &str) -> Option<(u32, String)> {
     })
 }
 
+pub fn parse_vital_sign(input: &str) -> Option<VitalSign> {
+    let name = VITAL_NAME_REGEX.captures(input)?.get(1)?.as_str();
+    let vital_sign = match name {
+        "HR" => VitalSign::HeartRate,
+        "BP" => VitalSign::BloodPressure,
+        "PR" => VitalSign::PulseRate,
+        "RR" => VitalSign::RespiratoryRate,
+        "ETCO2" => VitalSign::EndTidalCO2,
+        "Temperature" => VitalSign::Temperature,
+        "Systolic" => VitalSign::Systolic,
+        "Diastolic" => VitalSign::Diastolic,
+        // Waveform channels describe the displayed rhythm, not a measurement.
+        _ if name.ends_with("_Waveform") => return None,
+        _ => VitalSign::Other(name.to_owned()),
+    };
+    Some(vital_sign)
+}
+
+pub fn parse_waveform_channel(input: &str) -> Option<WaveformChannel> {
+    match VITAL_NAME_REGEX.captures(input)?.get(1)?.as_str() {
+        "HR_Waveform" => Some(WaveformChannel::Hr),
+        "PR_Waveform" => Some(WaveformChannel::Pr),
+        "RR_Waveform" => Some(WaveformChannel::Rr),
+        _ => None,
+    }
+}
+
+pub fn parse_rhythm(input: &str) -> Rhythm {
+    match input.trim() {
+        "CR_NSR" => Rhythm::NormalSinus,
+        "CR_VTach" => Rhythm::VTach,
+        "CR_VFib" => Rhythm::VFib,
+        "CR_Asystole" => Rhythm::Asystole,
+        "CR_SinLike" => Rhythm::SinLike,
+        "O2_NW" => Rhythm::OxygenNormal,
+        "O2_PP" => Rhythm::OxygenPoorPerfusion,
+        "CO2_Norm" => Rhythm::CO2Normal,
+        "CO2_CA" => Rhythm::CO2CardiacArrest,
+        "CO2_CPR" => Rhythm::CO2Cpr,
+        code => Rhythm::Other(code.to_owned()),
+    }
+}
+
+/// Rhythm family of a stage from the start of its name, e.g. `V-Fib 4C.1 - AMIO` is a V-Fib stage.
+pub fn parse_rhythm_family(stage_name: &str) -> Option<RhythmFamily> {
+    let name = stage_name.to_lowercase().replace(['-', ' '], "");
+    [
+        ("vtach", RhythmFamily::VTach),
+        ("vfib", RhythmFamily::VFib),
+        ("asystole", RhythmFamily::Asystole),
+        ("rosc", RhythmFamily::Rosc),
+    ]
+    .into_iter()
+    .find_map(|(prefix, family)| name.starts_with(prefix).then_some(family))
+}
+
+pub fn parse_vital_value(input: &str) -> Option<VitalValue> {
+    match input.trim() {
+        "--" => Some(VitalValue::NotMeasurable),
+        value => value.parse::<f64>().ok().map(VitalValue::Measured),
+    }
+}
+
+pub fn parse_severity(input: &str) -> Severity {
+    match input.trim() {
+        "Warning" => Severity::Warning,
+        "Error" => Severity::Error,
+        "CriticalError" => Severity::CriticalError,
+        severity => Severity::Unknown(severity.to_owned()),
+    }
+}
+
+pub fn parse_rating_scale(input: &str) -> Option<RatingScale> {
+    let captures = RATING_SCALE_REGEX.captures(input)?;
+    let min = captures.get(1)?.as_str().parse::<u32>().ok()?;
+    let max = captures.get(2)?.as_str().parse::<u32>().ok()?;
+    Some(RatingScale { min, max })
+}
+
+pub fn parse_questionnaire_item(record: &StringRecord) -> Result<QuestionnaireItem, String> {
+    let (question, rating, scale) = match (record.get(0), record.get(1), record.get(2)) {
+        (Some(question), Some(rating), Some(scale)) => (question, rating, scale),
+        _ => return Err(format!("Could not parse questionnaire item: expected 3 columns but got {:?}", record)),
+    };
+    let rating = rating.trim().parse::<u32>()
+        .map_err(|e| format!("Could not parse questionnaire rating {:?}: {}", rating, e))?;
+    let scale = parse_rating_scale(scale)
+        .ok_or_else(|| format!("Could not parse questionnaire rating scale {:?}", scale))?;
+
+    Ok(QuestionnaireItem {
+        question: utils::normalize_whitespace(question),
+        rating,
+        scale,
+    })
+}
+
 pub fn extract_shock_value(input: &str) -> (String, String) {
     match SHOCK_VALUE_REGEX.captures(input).map(|captures| {
         let before = captures.get(1).map_or("", |m| m.as_str()).trim();
@@ -79,55 +160,57 @@ pub fn extract_shock_value(input: &str) -> (String, String) {
     }
 }
 
-pub fn process_action_name(input: &str) -> (String, String, String) {
+/// Returns the action name with its shock value, the category, the shock value and whether the action is in the catalogue.
+/// An action that is not in the catalogue is its own category.
+pub fn process_action_name(input: &str, catalogue: &ActionCatalogue) -> (String, String, String, bool) {
     let (normalized_action_name, joule) = extract_shock_value(utils::capitalize_words(input).replace("UNAVAILABLE", "").trim());
-    let corrected_action_name = match normalized_action_name.as_str() {
-        "Ascultate Lungs" => "Auscultate Lungs",
-        "SYNCHRONIZED Shock" => "Synchronized Shock",
-        _ => normalized_action_name.as_str(),
-    };
-
-    let category = match corrected_action_name {
-        "Select Amiodarone" => "Medication",
-        "Select Calcium" => "Medication",
-        "Select Epinephrine" => "Medication",
-        "Select Lidocaine" => "Medication",
-        _ => corrected_action_name,
+    let entry = catalogue.lookup(&normalized_action_name);
+    let (corrected_action_name, category) = match entry {
+        Some(entry) => (entry.name.as_str(), entry.category()),
+        None => (normalized_action_name.as_str(), normalized_action_name.as_str()),
     };
 
     let action_name = if joule.is_empty() { corrected_action_name.to_owned() } else { format!("{} {}", corrected_action_name, joule) };
-    (action_name, category.to_owned(), joule)
+    (action_name, category.to_owned(), joule, entry.is_some())
 }
 
 #[cfg(test)]
 mod tests {
     mod test_parse_time {
         use super::super::*;
-        use chrono::DateTime;
+        use chrono::NaiveDate;
 
         #[test]
         fn valid_time() {
             let timestamp = "12:34:56";
-            let parsed_time = parse_time(timestamp).unwrap();
-            let now: DateTime<Utc> = Utc::now();
-            let formatted_date_string = now.format("%Y-%m-%d").to_string() + " " + timestamp;
+            let session_date = SessionDate::new(NaiveDate::from_ymd_opt(2024, 9, 18).unwrap());
+            let parsed_time = parse_time(timestamp, &session_date).unwrap();
 
             assert_eq!(parsed_time.timestamp, timestamp);
             assert_eq!(parsed_time.total_seconds, 12 * 3600 + 34 * 60 + 56);
-            assert_eq!(parsed_time.date_string,formatted_date_string);
+            assert_eq!(parsed_time.date_string, "2024-09-18 12:34:56");
+            assert_eq!(parsed_time.datetime.to_rfc3339(), "2024-09-18T12:34:56+00:00");
+        }
+
+        #[test]
+        fn reproducible_without_session_date() {
+            let parsed_time = parse_time("0:1:5", &SessionDate::default()).unwrap();
+
+            assert_eq!(parsed_time.timestamp, "00:01:05");
+            assert_eq!(parsed_time.date_string, "1970-01-01 00:01:05");
         }
 
         #[test]
         fn invalid_time() {
             let timestamp = "invalid time";
-            let result = parse_time(timestamp);
+            let result = parse_time(timestamp, &SessionDate::default());
             assert!(result.is_none());
         }
 
         #[test]
         fn empty_time() {
             let timestamp = "";
-            let result = parse_time(timestamp);
+            let result = parse_time(timestamp, &SessionDate::default());
             assert!(result.is_none());
         }
     }
@@ -171,6 +254,203 @@ mod tests {
         }
     }
 
+    mod test_parse_vital_sign {
+        use super::super::*;
+
+        #[test]
+        fn known_vitals() {
+            let test_cases = [
+                ("HR(vital)", VitalSign::HeartRate),
+                ("BP(vital)", VitalSign::BloodPressure),
+                ("PR(vital)", VitalSign::PulseRate),
+                ("RR(vital)", VitalSign::RespiratoryRate),
+                ("ETCO2(vital)", VitalSign::EndTidalCO2),
+                ("Temperature(vital)", VitalSign::Temperature),
+                ("Systolic(vital)", VitalSign::Systolic),
+                ("Diastolic(vital)", VitalSign::Diastolic),
+            ];
+
+            for (input, expected) in test_cases {
+                assert_eq!(parse_vital_sign(input), Some(expected));
+            }
+        }
+
+        #[test]
+        fn unknown_vital() {
+            assert_eq!(parse_vital_sign("SpO2(vital)"), Some(VitalSign::Other("SpO2".to_owned())));
+            // Systolic and diastolic pressure changed together, not a mean pressure.
+            assert_eq!(parse_vital_sign("SDTogether(vital)"), Some(VitalSign::Other("SDTogether".to_owned())));
+        }
+
+        #[test]
+        fn waveform() {
+            assert_eq!(parse_vital_sign("HR_Waveform(vital)"), None);
+        }
+
+        #[test]
+        fn not_vital() {
+            assert_eq!(parse_vital_sign("umich1(speech)"), None);
+            assert_eq!(parse_vital_sign("(1)V-Tach 2D(action)"), None);
+            assert_eq!(parse_vital_sign(""), None);
+        }
+    }
+
+    mod test_parse_waveform_channel {
+        use super::super::*;
+
+        #[test]
+        fn channels() {
+            assert_eq!(parse_waveform_channel("HR_Waveform(vital)"), Some(WaveformChannel::Hr));
+            assert_eq!(parse_waveform_channel("PR_Waveform(vital)"), Some(WaveformChannel::Pr));
+            assert_eq!(parse_waveform_channel("RR_Waveform(vital)"), Some(WaveformChannel::Rr));
+        }
+
+        #[test]
+        fn not_waveform() {
+            assert_eq!(parse_waveform_channel("HR(vital)"), None);
+            assert_eq!(parse_waveform_channel("HR_Waveform"), None);
+        }
+    }
+
+    mod test_parse_rhythm {
+        use super::super::*;
+
+        #[test]
+        fn known_rhythms() {
+            let test_cases = [
+                ("CR_NSR", Rhythm::NormalSinus),
+                ("CR_VTach", Rhythm::VTach),
+                ("CR_VFib", Rhythm::VFib),
+                ("CR_Asystole", Rhythm::Asystole),
+                ("CR_SinLike", Rhythm::SinLike),
+                ("O2_NW", Rhythm::OxygenNormal),
+                ("O2_PP", Rhythm::OxygenPoorPerfusion),
+                ("CO2_Norm", Rhythm::CO2Normal),
+                ("CO2_CA", Rhythm::CO2CardiacArrest),
+                ("CO2_CPR", Rhythm::CO2Cpr),
+            ];
+
+            for (input, expected) in test_cases {
+                assert_eq!(parse_rhythm(input), expected);
+            }
+        }
+
+        #[test]
+        fn unknown_rhythm() {
+            assert_eq!(parse_rhythm("CR_Torsades"), Rhythm::Other("CR_Torsades".to_owned()));
+        }
+    }
+
+    mod test_parse_rhythm_family {
+        use super::super::*;
+
+        #[test]
+        fn sample_stages() {
+            let test_cases = [
+                ("V-Tach 2D", Some(RhythmFamily::VTach)),
+                ("V-Fib 4C.1 - AMIO", Some(RhythmFamily::VFib)),
+                ("Asystole 1D No.1", Some(RhythmFamily::Asystole)),
+                ("ROSC 5B - Stemi", Some(RhythmFamily::Rosc)),
+                ("VTach 3", Some(RhythmFamily::VTach)),
+                ("Bradycardia 1A", None),
+            ];
+
+            for (input, expected) in test_cases {
+                assert_eq!(parse_rhythm_family(input), expected, "{}", input);
+            }
+        }
+    }
+
+    mod test_parse_vital_value {
+        use super::super::*;
+
+        #[test]
+        fn measured() {
+            assert_eq!(parse_vital_value("178"), Some(VitalValue::Measured(178.0)));
+            assert_eq!(parse_vital_value(" 97.8 "), Some(VitalValue::Measured(97.8)));
+        }
+
+        #[test]
+        fn not_measurable() {
+            assert_eq!(parse_vital_value("--"), Some(VitalValue::NotMeasurable));
+        }
+
+        #[test]
+        fn invalid() {
+            assert_eq!(parse_vital_value(""), None);
+            assert_eq!(parse_vital_value("high"), None);
+        }
+    }
+
+    mod test_parse_severity {
+        use super::super::*;
+
+        #[test]
+        fn known_severities() {
+            assert_eq!(parse_severity("Warning"), Severity::Warning);
+            assert_eq!(parse_severity("Error"), Severity::Error);
+            assert_eq!(parse_severity(" CriticalError "), Severity::CriticalError);
+        }
+
+        #[test]
+        fn unknown_severity() {
+            assert_eq!(parse_severity("Fatal"), Severity::Unknown("Fatal".to_owned()));
+        }
+    }
+
+    mod test_parse_rating_scale {
+        use super::super::*;
+
+        #[test]
+        fn valid() {
+            assert_eq!(parse_rating_scale("0 - 7"), Some(RatingScale { min: 0, max: 7 }));
+            assert_eq!(parse_rating_scale("1-10"), Some(RatingScale { min: 1, max: 10 }));
+        }
+
+        #[test]
+        fn invalid() {
+            assert_eq!(parse_rating_scale("0 to 7"), None);
+            assert_eq!(parse_rating_scale(""), None);
+        }
+    }
+
+    mod test_parse_questionnaire_item {
+        use super::super::*;
+
+        #[test]
+        fn valid() {
+            let record = StringRecord::from(vec!["How effective was tha team leader?", "5", "0 - 7"]);
+
+            let expected = QuestionnaireItem {
+                question: "How effective was tha team leader?".to_owned(),
+                rating: 5,
+                scale: RatingScale { min: 0, max: 7 },
+            };
+            assert_eq!(parse_questionnaire_item(&record), Ok(expected));
+        }
+
+        #[test]
+        fn missing_columns() {
+            let record = StringRecord::from(vec!["How effective was tha team leader?", "5"]);
+
+            assert!(parse_questionnaire_item(&record).is_err());
+        }
+
+        #[test]
+        fn invalid_rating() {
+            let record = StringRecord::from(vec!["How effective was tha team leader?", "high", "0 - 7"]);
+
+            assert!(parse_questionnaire_item(&record).is_err());
+        }
+
+        #[test]
+        fn invalid_scale() {
+            let record = StringRecord::from(vec!["How effective was tha team leader?", "5", "seven"]);
+
+            assert!(parse_questionnaire_item(&record).is_err());
+        }
+    }
+
     mod test_etract_shock_value {
         use super::super::*;
 
@@ -270,10 +550,32 @@ mod tests {
                 ("View Cardiac Arrest Guidelines", ("View Cardiac Arrest Guidelines".to_owned(), "View Cardiac Arrest Guidelines".to_owned(), "".to_owned())),
             ];
 
-            for (input, expected) in test_cases {
-                let result = process_action_name(input);
-                assert_eq!(result, expected);
+            let catalogue = ActionCatalogue::default();
+            for (input, (action_name, category, joule)) in test_cases {
+                let result = process_action_name(input, &catalogue);
+                assert_eq!(result, (action_name, category, joule, true));
             }
         }
+
+        #[test]
+        fn unknown_action() {
+            let result = process_action_name("Order CT scan", &ActionCatalogue::default());
+
+            assert_eq!(result, ("Order CT Scan".to_owned(), "Order CT Scan".to_owned(), "".to_owned(), false));
+        }
+
+        #[test]
+        fn scenario_catalogue() {
+            let catalogue = ActionCatalogue::from_toml_str(r#"
+                [[action]]
+                name = "Give Aspirin"
+                aliases = ["Select Aspirin"]
+                category = "Medication"
+            "#).unwrap();
+
+            let result = process_action_name("Select aspirin", &catalogue);
+
+            assert_eq!(result, ("Give Aspirin".to_owned(), "Medication".to_owned(), "".to_owned(), true));
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,177 @@
+use crate::plot_structures::{ActionPlotPoint, ErrorInfo};
+use serde::Serialize;
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default, PartialEq, Clone, Serialize)]
+pub struct ParticipantSummary {
+    pub username: String,
+    /// Performed actions, erroneous ones included.
+    pub actions_by_category: BTreeMap<String, u32>,
+    /// Erroneous and missed actions attributed to the participant.
+    pub errors_by_severity: BTreeMap<String, u32>,
+    pub missed_actions: u32,
+    pub speech_utterances: u32,
+    pub unrecognized_utterances: u32,
+    pub no_match_rate: Option<f64>
+}
+
+impl ParticipantSummary {
+    fn count_error(&mut self, error_info: &ErrorInfo) {
+        *self.errors_by_severity.entry(error_info.severity.to_string()).or_default() += 1;
+    }
+}
+
+fn participant<'m>(participants: &'m mut BTreeMap<String, ParticipantSummary>, username: &str) -> &'m mut ParticipantSummary {
+    participants.entry(username.to_owned()).or_insert_with(|| ParticipantSummary {
+        username: username.to_owned(),
+        ..Default::default()
+    })
+}
+
+/// Per-participant breakdown ordered by username.
+pub fn summarize_participants<I>(points: I) -> Vec<ParticipantSummary>
+where
+    I: IntoIterator,
+    I::Item: Borrow<ActionPlotPoint>,
+{
+    let mut participants: BTreeMap<String, ParticipantSummary> = BTreeMap::new();
+
+    for point in points {
+        match point.borrow() {
+            ActionPlotPoint::Action(action) if !action.username.is_empty() => {
+                *participant(&mut participants, &action.username).actions_by_category.entry(action.action_category.clone()).or_default() += 1;
+            }
+            ActionPlotPoint::Error(error) if !error.username.is_empty() => {
+                let summary = participant(&mut participants, &error.username);
+                *summary.actions_by_category.entry(error.action_category.clone()).or_default() += 1;
+                summary.count_error(&error.error_info);
+            }
+            ActionPlotPoint::MissedAction(missed) => {
+                if let Some(username) = &missed.username {
+                    let summary = participant(&mut participants, username);
+                    summary.missed_actions += 1;
+                    summary.count_error(&missed.error_info);
+                }
+            }
+            ActionPlotPoint::Speech(speech) if !speech.speaker.is_empty() => {
+                let summary = participant(&mut participants, &speech.speaker);
+                summary.speech_utterances += 1;
+                if speech.no_match {
+                    summary.unrecognized_utterances += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    participants
+        .into_values()
+        .map(|mut summary| {
+            if summary.speech_utterances > 0 {
+                summary.no_match_rate = Some(summary.unrecognized_utterances as f64 / summary.speech_utterances as f64);
+            }
+            summary
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plot_structures::{Action, ErroneousAction, MissedAction, PlotLocation, Severity, SpeechEvent};
+
+    fn error_info(severity: Severity) -> ErrorInfo {
+        ErrorInfo {
+            action_rule: "EKG".to_owned(),
+            severity,
+            expectation: "Action-Should-Not-Be-Performed".to_owned(),
+            violation: "Action-Was-Performed".to_owned(),
+            advice: String::new(),
+        }
+    }
+
+    fn action(username: &str, action_category: &str) -> ActionPlotPoint {
+        ActionPlotPoint::Action(Action {
+            location: PlotLocation::default(),
+            name: action_category.to_owned(),
+            action_category: action_category.to_owned(),
+            shock_value: String::new(),
+            username: username.to_owned(),
+        })
+    }
+
+    fn speech(speaker: &str, no_match: bool) -> ActionPlotPoint {
+        ActionPlotPoint::Speech(SpeechEvent {
+            location: PlotLocation::default(),
+            speaker: speaker.to_owned(),
+            transcript: String::new(),
+            no_match,
+        })
+    }
+
+    mod summarize_participants {
+        use super::*;
+
+        #[test]
+        fn breakdown_per_participant() {
+            let points = vec![
+                action("umich1", "Pulse Check"),
+                action("umich1", "Pulse Check"),
+                action("umich2", "Medication"),
+                ActionPlotPoint::Error(ErroneousAction {
+                    location: PlotLocation::default(),
+                    name: "Order EKG".to_owned(),
+                    action_category: "Order EKG".to_owned(),
+                    shock_value: String::new(),
+                    username: "umich1".to_owned(),
+                    error_info: error_info(Severity::Error),
+                }),
+                ActionPlotPoint::MissedAction(MissedAction {
+                    location: PlotLocation::default(),
+                    name: "labcheck_anytime".to_owned(),
+                    username: Some("umich2".to_owned()),
+                    error_info: error_info(Severity::Warning),
+                }),
+                ActionPlotPoint::MissedAction(MissedAction {
+                    location: PlotLocation::default(),
+                    name: "glucose_anytime".to_owned(),
+                    username: None,
+                    error_info: error_info(Severity::Warning),
+                }),
+                speech("umich1", false),
+                speech("umich1", true),
+                speech("umich1", false),
+                speech("umich1", false),
+            ];
+
+            let participants = summarize_participants(&points);
+
+            assert_eq!(participants, vec![
+                ParticipantSummary {
+                    username: "umich1".to_owned(),
+                    actions_by_category: BTreeMap::from([("Pulse Check".to_owned(), 2), ("Order EKG".to_owned(), 1)]),
+                    errors_by_severity: BTreeMap::from([("Error".to_owned(), 1)]),
+                    missed_actions: 0,
+                    speech_utterances: 4,
+                    unrecognized_utterances: 1,
+                    no_match_rate: Some(0.25),
+                },
+                ParticipantSummary {
+                    username: "umich2".to_owned(),
+                    actions_by_category: BTreeMap::from([("Medication".to_owned(), 1)]),
+                    errors_by_severity: BTreeMap::from([("Warning".to_owned(), 1)]),
+                    missed_actions: 1,
+                    speech_utterances: 0,
+                    unrecognized_utterances: 0,
+                    no_match_rate: None,
+                },
+            ]);
+        }
+
+        #[test]
+        fn no_participants() {
+            assert!(summarize_participants(Vec::<ActionPlotPoint>::new()).is_empty());
+        }
+    }
+}
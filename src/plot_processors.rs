@@ -1,23 +1,27 @@
 use crate::action_csv_row::ActionCsvRow;
 use crate::debug_message::print_debug_message;
-use crate::detection::{can_mark_each_other, check_cpr, is_erroneous_action, is_error_action_marker, is_missed_action, is_stage_boundary};
-use crate::plot_structures::{Action, ActionPlotPoint, ErroneousAction, MissedAction, PeriodType, PlotLocation};
+use crate::detection::{can_mark_each_other, check_cpr, is_erroneous_action, is_error_action_marker, is_missed_action, is_rule_evaluation, is_stage_boundary};
+use crate::parsing::{parse_rhythm, parse_vital_value};
+use crate::plot_structures::{Action, ActionPlotPoint, CsvRowTime, ErroneousAction, MissedAction, PeriodType, PlotLocation, Rhythm, RuleEvaluation, SpeechEvent, StagePeriod, VitalSample, WaveformChannel};
 use crate::processing_state::CsvProcessingState;
+use crate::processor_config::MarkerMatching;
+use crate::processor_error::ProcessorError;
+use crate::session_date::SessionDate;
 use std::cell::RefCell;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 
-fn check_pending_erroneous_action_marker(pending_error_marker: &RefCell<Option<(usize, ActionCsvRow)>>, _row_idx: usize, current_row: &ActionCsvRow) -> Option<ActionPlotPoint> {
+fn check_pending_erroneous_action_marker(pending_error_marker: &RefCell<Option<(usize, ActionCsvRow)>>, _line: usize, current_row: &ActionCsvRow, time_threshold: u32) -> Option<ActionPlotPoint> {
     let pending_error_marker_value = pending_error_marker.borrow().clone();
-    if let Some((_marker_index, error_marker_row)) = pending_error_marker_value {
+    if let Some((_marker_line, error_marker_row)) = pending_error_marker_value {
         // Check if the current row is an erroneous action row.
-        if is_erroneous_action(&current_row, &error_marker_row) {
-            print_debug_message!("Error marker at row {} points to erroneous action at row {}", _marker_index+2, _row_idx+2);
+        if is_erroneous_action(current_row, &error_marker_row, time_threshold) {
+            print_debug_message!("Error marker at row {} points to erroneous action at row {}", _marker_line, _line);
             *pending_error_marker.borrow_mut() = None; // Clear the state as the error has been resolved.
-            let point = ActionPlotPoint::Error(ErroneousAction::new(&current_row, &error_marker_row));
+            let point = ActionPlotPoint::Error(ErroneousAction::new(current_row, &error_marker_row));
             return Some(point);
-        } else if !can_mark_each_other(&current_row, &error_marker_row) {
+        } else if !can_mark_each_other(current_row, &error_marker_row, time_threshold) {
             // If row count threshold is exceeded, log and forget the marker.
-            print_debug_message!("Error marker at row {} could not find an erroneous action row within ERROR_MARKER_TIME_THRESHOLD time threshold", _marker_index+2);
+            print_debug_message!("Error marker at row {} could not find an erroneous action row within {} seconds", _marker_line, time_threshold);
             *pending_error_marker.borrow_mut() = None;
         }
     }
@@ -27,15 +31,16 @@ fn check_pending_erroneous_action_marker(pending_error_marker: &RefCell<Option<(
 fn seek_erroneous_action_in_visited_rows(
     visited_rows_buffer: &mut VecDeque<ActionCsvRow>,
     error_marker_row: &ActionCsvRow,
-    _error_marker_row_idx: usize,
-) -> Option<Result<ActionPlotPoint, String>> {
+    _error_marker_line: usize,
+    look_back_window: u32,
+) -> Option<Result<ActionPlotPoint, ProcessorError>> {
     for recent_index in (0..visited_rows_buffer.len()).rev() {
         if let Some(recent_row) = visited_rows_buffer.get(recent_index) {
-            if is_erroneous_action(recent_row, error_marker_row) {
+            if is_erroneous_action(recent_row, error_marker_row, look_back_window) {
                 print_debug_message!(
                     "Error marker at row {} points backward to erroneous action at row {}",
-                    _error_marker_row_idx + 2,
-                    (_error_marker_row_idx - recent_index) + 2
+                    _error_marker_line,
+                    recent_row.row_number
                 );
 
                 // Remove the erroneous row from the buffer
@@ -51,21 +56,33 @@ fn seek_erroneous_action_in_visited_rows(
     None
 }
 
-pub fn process_erroneous_action(state: &mut CsvProcessingState, row_idx: usize, current_row: &ActionCsvRow, ) -> Option<Result<ActionPlotPoint, String>> {
+pub fn process_erroneous_action(state: &mut CsvProcessingState, line: usize, current_row: &ActionCsvRow, ) -> Option<Result<ActionPlotPoint, ProcessorError>> {
     if let Some(error_point) = check_pending_erroneous_action_marker(
         &state.pending_error_marker,
-        row_idx,
+        line,
         current_row,
+        state.config.error_marker_time_threshold,
     ) {
         return Some(Ok(error_point));
     }
 
     if is_error_action_marker(current_row) {
-        seek_erroneous_action_in_visited_rows(&mut state.recent_rows, current_row, row_idx)
-            .or_else(|| {
-                *state.pending_error_marker.borrow_mut() = Some((row_idx, current_row.clone()));
-                None
-            })
+        let marker_matching = state.config.marker_matching;
+        let backward_match = match marker_matching {
+            MarkerMatching::ForwardOnly => None,
+            _ => seek_erroneous_action_in_visited_rows(&mut state.recent_rows, current_row, line, state.config.look_back_window),
+        };
+        backward_match.or_else(|| {
+            if marker_matching == MarkerMatching::BackwardOnly {
+                // Reported like a marker that is still pending at the end of the data in the other modes.
+                return Some(Err(ProcessorError::UnresolvedErrorMarker {
+                    row_number: line,
+                    marker: current_row.action_vital_name.clone(),
+                }));
+            }
+            *state.pending_error_marker.borrow_mut() = Some((line, current_row.clone()));
+            None
+        })
     } else if is_missed_action(current_row) {
         Some(Ok(ActionPlotPoint::MissedAction(MissedAction::new(current_row))))
     } else {
@@ -73,7 +90,7 @@ pub fn process_erroneous_action(state: &mut CsvProcessingState, row_idx: usize,
     }
 }
 
-pub fn process_action_point(current_row: &ActionCsvRow) -> Option<Result<ActionPlotPoint, String>> {
+pub fn process_action_point(current_row: &ActionCsvRow) -> Option<Result<ActionPlotPoint, ProcessorError>> {
     if current_row.action_point {
         Some(Ok(ActionPlotPoint::Action(Action::new(current_row))))
     } else {
@@ -81,30 +98,24 @@ pub fn process_action_point(current_row: &ActionCsvRow) -> Option<Result<ActionP
     }
 }
 
-pub fn process_stage_boundary(stage_boundary_points: &mut Vec<PlotLocation>, csv_row: &ActionCsvRow) -> Option<Result<ActionPlotPoint, String>> {
+pub fn process_stage_boundary(stage_boundary_points: &mut Vec<PlotLocation>, csv_row: &ActionCsvRow) -> Option<Result<ActionPlotPoint, ProcessorError>> {
     if !is_stage_boundary(csv_row) {
         return None;
     }
 
-    let start_location = stage_boundary_points.pop().map_or_else(
-        || PlotLocation::new(csv_row),
-        |mut location| {
-            location.stage = csv_row.parsed_stage.clone().unwrap();
-            location
-        },
-    );
-
-    stage_boundary_points.push(PlotLocation::new(csv_row));
-
-    Some(Ok(ActionPlotPoint::Period(
-        PeriodType::Stage,
-        start_location,
-        PlotLocation::new(csv_row), // No more Option here
-    )))
+    // A boundary row ends the stage it names, which started at the previous boundary.
+    let end_location = PlotLocation::new(csv_row);
+    let start = stage_boundary_points
+        .pop()
+        .map_or_else(|| end_location.timestamp.clone(), |location| location.timestamp);
+
+    stage_boundary_points.push(end_location.clone());
+
+    Some(Ok(ActionPlotPoint::Stage(StagePeriod::new(end_location.stage, start, end_location.timestamp))))
 }
 
-pub fn process_cpr_lines(cpr_points: &mut Vec<(PlotLocation, PlotLocation)>, csv_row: &ActionCsvRow) -> Option<Result<ActionPlotPoint, String>> {
-    match check_cpr(&csv_row) {
+pub fn process_cpr_lines(cpr_points: &mut Vec<(PlotLocation, PlotLocation)>, csv_row: &ActionCsvRow) -> Option<Result<ActionPlotPoint, ProcessorError>> {
+    match check_cpr(csv_row) {
         Some(_) => {
             let location = PlotLocation::new(csv_row);
             match cpr_points.pop() {
@@ -125,12 +136,128 @@ pub fn process_cpr_lines(cpr_points: &mut Vec<(PlotLocation, PlotLocation)>, csv
     }
 }
 
+pub fn process_vital_sign(line: usize, current_stage: Option<&(u32, String)>, csv_row: &ActionCsvRow) -> Option<Result<ActionPlotPoint, ProcessorError>> {
+    let vital_sign = csv_row.vital_sign.clone()?;
+    match (parse_vital_value(&csv_row.old_value), parse_vital_value(&csv_row.new_value)) {
+        (Some(old_value), Some(new_value)) => Some(Ok(ActionPlotPoint::Vital(VitalSample {
+            location: PlotLocation::in_stage(csv_row, current_stage),
+            vital_sign,
+            old_value,
+            new_value,
+        }))),
+        _ => Some(Err(ProcessorError::InvalidVitalValue {
+            row_number: line,
+            name: csv_row.action_vital_name.clone(),
+            old_value: csv_row.old_value.clone(),
+            new_value: csv_row.new_value.clone(),
+        })),
+    }
+}
+
+pub fn process_rule_evaluation(csv_row: &ActionCsvRow) -> Option<Result<ActionPlotPoint, ProcessorError>> {
+    if is_rule_evaluation(csv_row) {
+        Some(Ok(ActionPlotPoint::RuleEvaluation(RuleEvaluation::new(csv_row))))
+    } else {
+        None
+    }
+}
+
+pub fn process_speech(current_stage: Option<&(u32, String)>, csv_row: &ActionCsvRow) -> Option<Result<ActionPlotPoint, ProcessorError>> {
+    if csv_row.speech_point {
+        Some(Ok(ActionPlotPoint::Speech(SpeechEvent::new(csv_row, current_stage))))
+    } else {
+        None
+    }
+}
+
+pub fn process_waveform_transition(
+    waveform_segments: &mut BTreeMap<WaveformChannel, (Rhythm, PlotLocation)>,
+    session_date: &SessionDate,
+    current_stage: Option<&(u32, String)>,
+    csv_row: &ActionCsvRow,
+) -> Option<Result<ActionPlotPoint, ProcessorError>> {
+    let channel = csv_row.waveform_channel.clone()?;
+    let new_rhythm = parse_rhythm(&csv_row.new_value);
+    let location = PlotLocation::in_stage(csv_row, current_stage);
+
+    // Before the first transition the channel has shown the old rhythm since the start of the session.
+    let (rhythm, start_location) = waveform_segments
+        .remove(&channel)
+        .unwrap_or_else(|| {
+            let session_start = PlotLocation { timestamp: CsvRowTime::new(0, session_date), ..Default::default() };
+            (parse_rhythm(&csv_row.old_value), session_start)
+        });
+
+    if rhythm == new_rhythm {
+        waveform_segments.insert(channel, (rhythm, start_location));
+        return None;
+    }
+
+    waveform_segments.insert(channel.clone(), (new_rhythm, location.clone()));
+    Some(Ok(ActionPlotPoint::Period(PeriodType::waveform(&channel, rhythm), start_location, location)))
+}
+
+pub fn flush_recent_actions(recent_rows: &mut VecDeque<ActionCsvRow>) -> Vec<(usize, Result<ActionPlotPoint, ProcessorError>)> {
+    recent_rows
+        .drain(..)
+        .filter_map(|recent_row| process_action_point(&recent_row).map(|point| (recent_row.row_number, point)))
+        .collect()
+}
+
+pub fn flush_pending_error_marker(pending_error_marker: &RefCell<Option<(usize, ActionCsvRow)>>) -> Option<Result<ActionPlotPoint, ProcessorError>> {
+    pending_error_marker.borrow_mut().take().map(|(marker_line, error_marker_row)| {
+        Err(ProcessorError::UnresolvedErrorMarker {
+            row_number: marker_line,
+            marker: error_marker_row.action_vital_name,
+        })
+    })
+}
+
+pub fn close_open_cpr_period(cpr_points: &mut Vec<(PlotLocation, PlotLocation)>, last_timestamp: Option<&CsvRowTime>) -> Option<Result<ActionPlotPoint, ProcessorError>> {
+    let last_timestamp = last_timestamp?;
+    cpr_points.pop().map(|(start_location, _)| {
+        let end_location = PlotLocation {
+            timestamp: last_timestamp.clone(),
+            stage: start_location.stage.clone(),
+        };
+        Ok(ActionPlotPoint::Period(PeriodType::CPR, start_location, end_location))
+    })
+}
+
+pub fn close_open_waveform_segments(waveform_segments: &mut BTreeMap<WaveformChannel, (Rhythm, PlotLocation)>, last_timestamp: Option<&CsvRowTime>) -> Vec<Result<ActionPlotPoint, ProcessorError>> {
+    let Some(last_timestamp) = last_timestamp else {
+        return Vec::new();
+    };
+    std::mem::take(waveform_segments)
+        .into_iter()
+        .map(|(channel, (rhythm, start_location))| {
+            let end_location = PlotLocation {
+                timestamp: last_timestamp.clone(),
+                stage: start_location.stage.clone(),
+            };
+            Ok(ActionPlotPoint::Period(PeriodType::waveform(&channel, rhythm), start_location, end_location))
+        })
+        .collect()
+}
+
+pub fn close_open_stage_period(stage_boundary_points: &mut Vec<PlotLocation>, current_stage: Option<&(u32, String)>, last_timestamp: Option<&CsvRowTime>) -> Option<Result<ActionPlotPoint, ProcessorError>> {
+    let (current_stage, last_timestamp) = (current_stage?, last_timestamp?);
+    // The last boundary row closed the stage it names, so there is nothing left open unless a later row named the next one.
+    if current_stage.1.is_empty() || stage_boundary_points.last().is_some_and(|location| &location.stage == current_stage) {
+        return None;
+    }
+
+    let start_location = stage_boundary_points.pop()?;
+
+    Some(Ok(ActionPlotPoint::Stage(StagePeriod::new(current_stage.clone(), start_location.timestamp, last_timestamp.clone()))))
+}
+
 #[cfg(test)]
 mod tests{
     mod process_stage_boundary {
         use super::super::*;
         use crate::action_csv_row::ActionCsvRow;
-        use crate::plot_structures::ActionPlotPoint;
+        use crate::plot_structures::{ActionPlotPoint, RhythmFamily};
 
         #[test]
         fn stage_begin() {
@@ -145,35 +272,39 @@ mod tests{
             let result = process_stage_boundary(&mut stage_boundary_points, &csv_row);
             
             assert!(result.is_some());
-            if let Some(Ok(ActionPlotPoint::Period(PeriodType::Stage, start, end))) = result {
-                assert_eq!(start.stage, (1, "Stage 1".to_owned()));
-                assert_eq!(end.stage, (1, "Stage 1".to_owned()));
+            if let Some(Ok(ActionPlotPoint::Stage(stage_period))) = result {
+                assert_eq!(stage_period.stage, (1, "Stage 1".to_owned()));
             } else {
-                panic!("Expected ActionPlotPoint::Period with PeriodType::Stage");
+                panic!("Expected ActionPlotPoint::Stage");
             }
         }
 
         #[test]
         fn stage_end() {
             let mut stage_boundary_points = vec![PlotLocation::new(&ActionCsvRow {
-                action_vital_name: "Stage 1".to_owned(),
-                parsed_stage: Some((1, "Stage 1".to_owned())),
+                action_vital_name: "(1)V-Tach 2D(action)".to_owned(),
+                parsed_stage: Some((1, "V-Tach 2D".to_owned())),
+                timestamp: Some(CsvRowTime { total_seconds: 115, ..Default::default() }),
                 ..Default::default()
             })];
             let csv_row = ActionCsvRow {
-                action_vital_name: "Stage 2".to_owned(),
-                parsed_stage: Some((2, "Stage 2".to_owned())),
+                action_vital_name: "(2)V-Tach 2A.1(action)".to_owned(),
+                parsed_stage: Some((2, "V-Tach 2A.1".to_owned())),
+                timestamp: Some(CsvRowTime { total_seconds: 276, ..Default::default() }),
                 ..Default::default()
             };
 
             let result = process_stage_boundary(&mut stage_boundary_points, &csv_row);
            
             assert!(result.is_some());
-            if let Some(Ok(ActionPlotPoint::Period(PeriodType::Stage, start, end))) = result {
-                assert_eq!(start.stage, (2, "Stage 2".to_owned()));
-                assert_eq!(end.stage, (2, "Stage 2".to_owned()));
+            if let Some(Ok(ActionPlotPoint::Stage(stage_period))) = result {
+                assert_eq!(stage_period.stage, (2, "V-Tach 2A.1".to_owned()));
+                assert_eq!(stage_period.start.total_seconds, 115);
+                assert_eq!(stage_period.end.total_seconds, 276);
+                assert_eq!(stage_period.duration_seconds, 161);
+                assert_eq!(stage_period.rhythm_family, Some(RhythmFamily::VTach));
             } else {
-                panic!("Expected ActionPlotPoint::Period with PeriodType::Stage");
+                panic!("Expected ActionPlotPoint::Stage");
             }
         }
 
@@ -205,6 +336,7 @@ mod tests{
                     total_seconds:120,
                     timestamp: "00:02:00".to_owned(),
                     date_string: "2021-01-01 00:02:00".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
@@ -231,6 +363,7 @@ mod tests{
                     total_seconds:120,
                     timestamp: "00:02:00".to_owned(),
                     date_string: "2021-01-01 00:02:00".to_owned(),
+                    ..Default::default()
                 }),
                 ..Default::default()
             };
@@ -301,7 +434,36 @@ mod tests{
     }
     
     mod process_erroneous_action {
-        
+        use super::super::*;
+        use crate::processor_config::ProcessorConfig;
+
+        fn error_marker_row() -> ActionCsvRow {
+            ActionCsvRow {
+                timestamp: Some(CsvRowTime::new(10, &Default::default())),
+                action_vital_name: "EKG".to_owned(),
+                old_value: "Error-Triggered".to_owned(),
+                score: "Action-Was-Performed".to_owned(),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn backward_only_unmatched_marker() {
+            let mut state = CsvProcessingState::new(&ProcessorConfig::builder().marker_matching(MarkerMatching::BackwardOnly).build());
+
+            let result = process_erroneous_action(&mut state, 9, &error_marker_row());
+
+            assert_eq!(result, Some(Err(ProcessorError::UnresolvedErrorMarker { row_number: 9, marker: "EKG".to_owned() })));
+            assert!(state.pending_error_marker.borrow().is_none());
+        }
+
+        #[test]
+        fn unmatched_marker_pending() {
+            let mut state = CsvProcessingState::new(&ProcessorConfig::default());
+
+            assert_eq!(process_erroneous_action(&mut state, 7, &error_marker_row()), None);
+            assert!(state.pending_error_marker.borrow().is_some());
+        }
     }
 
     mod seek_erroneous_action_in_visited_rows{
@@ -314,6 +476,7 @@ mod tests{
                 total_seconds: sec,
                 timestamp: "".to_owned(),
                 date_string: "".to_owned(),
+                ..Default::default()
             })
         }
         fn create_test_row(sec: u32) -> ActionCsvRow {
@@ -350,7 +513,7 @@ mod tests{
             buffer.push_back(create_test_row(2));
             let error_marker_row = create_error_marker_row(5);
 
-            let result = seek_erroneous_action_in_visited_rows(&mut buffer, &error_marker_row, 5);
+            let result = seek_erroneous_action_in_visited_rows(&mut buffer, &error_marker_row, 5, 2);
 
             assert!(result.is_none());
             assert_eq!(buffer.len(), 2); // Buffer should remain unchanged
@@ -364,7 +527,7 @@ mod tests{
             buffer.push_back(erroneous_row.clone());
             let error_marker_row = create_error_marker_row(4);
 
-            let result = seek_erroneous_action_in_visited_rows(&mut buffer, &error_marker_row, 5);
+            let result = seek_erroneous_action_in_visited_rows(&mut buffer, &error_marker_row, 5, 2);
 
             assert!(result.is_some());
             if let Some(Ok(ActionPlotPoint::Error(erroneous_action))) = result {
@@ -386,7 +549,7 @@ mod tests{
             buffer.push_back(most_recent_erroneous.clone());
             let error_marker_row = create_error_marker_row(5);
 
-            let result = seek_erroneous_action_in_visited_rows(&mut buffer, &error_marker_row, 5);
+            let result = seek_erroneous_action_in_visited_rows(&mut buffer, &error_marker_row, 5, 2);
 
             assert!(result.is_some());
             if let Some(Ok(ActionPlotPoint::Error(erroneous_action))) = result {
@@ -403,11 +566,486 @@ mod tests{
             let mut buffer: VecDeque<ActionCsvRow> = VecDeque::new();
             let error_marker_row = create_error_marker_row(5);
 
-            let result = seek_erroneous_action_in_visited_rows(&mut buffer, &error_marker_row, 5);
+            let result = seek_erroneous_action_in_visited_rows(&mut buffer, &error_marker_row, 5, 2);
 
             assert!(result.is_none());
             assert!(buffer.is_empty()); // Buffer should remain empty
         }
 
     }
-}
\ No newline at end of file
+
+    mod process_vital_sign {
+        use super::super::*;
+        use crate::plot_structures::{VitalSign, VitalValue};
+
+        fn create_vital_row(old_value: &str, new_value: &str) -> ActionCsvRow {
+            ActionCsvRow {
+                timestamp: Some(CsvRowTime { total_seconds: 49, ..Default::default() }),
+                action_vital_name: "HR(vital)".to_owned(),
+                old_value: old_value.to_owned(),
+                new_value: new_value.to_owned(),
+                vital_sign: Some(VitalSign::HeartRate),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn vital_sample() {
+            let stage = (1, "V-Tach 2D".to_owned());
+
+            let result = process_vital_sign(0, Some(&stage), &create_vital_row("178", "192"));
+
+            if let Some(Ok(ActionPlotPoint::Vital(sample))) = result {
+                assert_eq!(sample.vital_sign, VitalSign::HeartRate);
+                assert_eq!(sample.old_value, VitalValue::Measured(178.0));
+                assert_eq!(sample.new_value, VitalValue::Measured(192.0));
+                assert_eq!(sample.location.timestamp.total_seconds, 49);
+                assert_eq!(sample.location.stage, stage);
+            } else {
+                panic!("Expected ActionPlotPoint::Vital");
+            }
+        }
+
+        #[test]
+        fn not_measurable_value() {
+            let result = process_vital_sign(0, None, &create_vital_row("--", "0"));
+
+            if let Some(Ok(ActionPlotPoint::Vital(sample))) = result {
+                assert_eq!(sample.old_value, VitalValue::NotMeasurable);
+                assert_eq!(sample.new_value, VitalValue::Measured(0.0));
+                assert_eq!(sample.location.stage, (0, "".to_owned()));
+            } else {
+                panic!("Expected ActionPlotPoint::Vital");
+            }
+        }
+
+        #[test]
+        fn invalid_value() {
+            let result = process_vital_sign(9, None, &create_vital_row("high", "0"));
+
+            assert_eq!(result, Some(Err(ProcessorError::InvalidVitalValue {
+                row_number: 9,
+                name: "HR(vital)".to_owned(),
+                old_value: "high".to_owned(),
+                new_value: "0".to_owned(),
+            })));
+        }
+
+        #[test]
+        fn not_vital_row() {
+            let csv_row = ActionCsvRow {
+                action_vital_name: "umich1(speech)".to_owned(),
+                ..Default::default()
+            };
+
+            assert!(process_vital_sign(0, None, &csv_row).is_none());
+        }
+    }
+
+    mod process_rule_evaluation {
+        use super::super::*;
+        use crate::plot_structures::Severity;
+        use crate::rule_csv_row::RuleCsvRow;
+
+        fn create_rule_row(trigger_state: &str, outcome: &str) -> ActionCsvRow {
+            ActionCsvRow {
+                timestamp: Some(CsvRowTime { total_seconds: 115, ..Default::default() }),
+                action_vital_name: "sync_defib_100+".to_owned(),
+                score: outcome.to_owned(),
+                old_value: trigger_state.to_owned(),
+                parsed_stage: Some((1, "V-Tach 2D".to_owned())),
+                rule: Some(RuleCsvRow {
+                    timestamp: "0:1:55".to_owned(),
+                    rule_id: "sync_defib_100+".to_owned(),
+                    severity: "CriticalError".to_owned(),
+                    expectation: "Action-Should-Be-Performed".to_owned(),
+                    outcome: outcome.to_owned(),
+                    trigger_state: trigger_state.to_owned(),
+                    username: "NA".to_owned(),
+                    stage: "(1)V-Tach 2D(action)".to_owned(),
+                    advice: "UNstable V.Tach WITH Pulse needs >100J SYNCHRONIZED Shock".to_owned(),
+                }),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn rule_not_triggered() {
+            let csv_row = create_rule_row("Error-Did-Not-Trigger", "Action-Was-Performed");
+
+            let result = process_rule_evaluation(&csv_row);
+
+            if let Some(Ok(ActionPlotPoint::RuleEvaluation(evaluation))) = result {
+                assert_eq!(evaluation.rule_id, "sync_defib_100+");
+                assert_eq!(evaluation.severity, Severity::CriticalError);
+                assert_eq!(evaluation.expectation, "Action-Should-Be-Performed");
+                assert_eq!(evaluation.outcome, "Action-Was-Performed");
+                assert!(!evaluation.triggered);
+                assert_eq!(evaluation.location.stage, (1, "V-Tach 2D".to_owned()));
+                assert_eq!(evaluation.advice, "UNstable V.Tach WITH Pulse needs >100J SYNCHRONIZED Shock");
+            } else {
+                panic!("Expected ActionPlotPoint::RuleEvaluation");
+            }
+        }
+
+        #[test]
+        fn rule_triggered() {
+            let csv_row = create_rule_row("Error-Triggered", "Action-Was-Not-Performed");
+
+            let result = process_rule_evaluation(&csv_row);
+
+            assert!(matches!(result, Some(Ok(ActionPlotPoint::RuleEvaluation(evaluation))) if evaluation.triggered));
+        }
+
+        #[test]
+        fn missed_action_error_info() {
+            let csv_row = create_rule_row("Error-Triggered", "Action-Was-Not-Performed");
+
+            let missed_action = MissedAction::new(&csv_row);
+
+            assert_eq!(missed_action.name, "sync_defib_100+");
+            assert_eq!(missed_action.username, None);
+            assert_eq!(missed_action.error_info.action_rule, "sync_defib_100+");
+            assert_eq!(missed_action.error_info.severity, Severity::CriticalError);
+            assert_eq!(missed_action.error_info.expectation, "Action-Should-Be-Performed");
+            assert_eq!(missed_action.error_info.violation, "Action-Was-Not-Performed");
+            assert_eq!(missed_action.error_info.advice, "UNstable V.Tach WITH Pulse needs >100J SYNCHRONIZED Shock");
+        }
+
+        #[test]
+        fn missed_action_username() {
+            let mut csv_row = create_rule_row("Error-Triggered", "Action-Was-Not-Performed");
+            csv_row.rule.as_mut().unwrap().username = "umich2".to_owned();
+
+            assert_eq!(MissedAction::new(&csv_row).username, Some("umich2".to_owned()));
+        }
+
+        #[test]
+        fn not_rule_row() {
+            let csv_row = ActionCsvRow {
+                action_point: true,
+                score: "100".to_owned(),
+                ..Default::default()
+            };
+
+            assert!(process_rule_evaluation(&csv_row).is_none());
+        }
+    }
+
+    mod process_speech {
+        use super::super::*;
+
+        #[test]
+        fn speech_event() {
+            let csv_row = ActionCsvRow {
+                timestamp: Some(CsvRowTime { total_seconds: 33, ..Default::default() }),
+                action_vital_name: "umich1(speech)".to_owned(),
+                username: "umich1".to_owned(),
+                speech_command: "Checking for pulse. ".to_owned(),
+                speech_point: true,
+                ..Default::default()
+            };
+            let stage = (1, "V-Tach 2D".to_owned());
+
+            let result = process_speech(Some(&stage), &csv_row);
+
+            if let Some(Ok(ActionPlotPoint::Speech(speech))) = result {
+                assert_eq!(speech.speaker, "umich1");
+                assert_eq!(speech.transcript, "Checking for pulse.");
+                assert!(!speech.no_match);
+                assert_eq!(speech.location.timestamp.total_seconds, 33);
+                assert_eq!(speech.location.stage, stage);
+            } else {
+                panic!("Expected ActionPlotPoint::Speech");
+            }
+        }
+
+        #[test]
+        fn unrecognized_speech() {
+            let csv_row = ActionCsvRow {
+                username: "umich2".to_owned(),
+                speech_command: "NOMATCH: Speech could not be recognized.".to_owned(),
+                speech_point: true,
+                ..Default::default()
+            };
+
+            let result = process_speech(None, &csv_row);
+
+            assert!(matches!(result, Some(Ok(ActionPlotPoint::Speech(speech))) if speech.no_match));
+        }
+
+        #[test]
+        fn not_speech_row() {
+            let csv_row = ActionCsvRow {
+                action_point: true,
+                ..Default::default()
+            };
+
+            assert!(process_speech(None, &csv_row).is_none());
+        }
+    }
+
+    mod process_waveform_transition {
+        use super::super::*;
+        use crate::test_support;
+        use std::collections::BTreeMap;
+
+        fn create_waveform_row(sec: u32, old_value: &str, new_value: &str) -> ActionCsvRow {
+            ActionCsvRow {
+                timestamp: Some(CsvRowTime { total_seconds: sec, ..Default::default() }),
+                action_vital_name: "HR_Waveform(vital)".to_owned(),
+                old_value: old_value.to_owned(),
+                new_value: new_value.to_owned(),
+                waveform_channel: Some(WaveformChannel::Hr),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn first_transition_closes_initial_rhythm() {
+            let mut waveform_segments = BTreeMap::new();
+
+            let result = process_waveform_transition(&mut waveform_segments, &SessionDate::default(), None, &create_waveform_row(49, "CR_NSR", "CR_VTach"));
+
+            if let Some(Ok(ActionPlotPoint::Period(PeriodType::HrWaveform(Rhythm::NormalSinus), start, end))) = result {
+                assert_eq!(start.timestamp.total_seconds, 0);
+                assert_eq!(end.timestamp.total_seconds, 49);
+            } else {
+                panic!("Expected ActionPlotPoint::Period with PeriodType::HrWaveform");
+            }
+            let (rhythm, start) = waveform_segments.get(&WaveformChannel::Hr).unwrap();
+            assert_eq!(*rhythm, Rhythm::VTach);
+            assert_eq!(start.timestamp.total_seconds, 49);
+        }
+
+        #[test]
+        fn initial_rhythm_starts_on_session_date() {
+            let mut waveform_segments = BTreeMap::new();
+
+            let result = process_waveform_transition(&mut waveform_segments, &test_support::session_date(), None, &create_waveform_row(49, "CR_NSR", "CR_VTach"));
+
+            if let Some(Ok(ActionPlotPoint::Period(_, start, _))) = result {
+                assert_eq!(start.timestamp.date_string, "2024-09-18 00:00:00");
+                assert_eq!(start.timestamp, test_support::time(0));
+            } else {
+                panic!("Expected ActionPlotPoint::Period");
+            }
+        }
+
+        #[test]
+        fn unchanged_rhythm_keeps_segment_open() {
+            let start = PlotLocation { timestamp: CsvRowTime { total_seconds: 49, ..Default::default() }, ..Default::default() };
+            let mut waveform_segments = BTreeMap::from([(WaveformChannel::Hr, (Rhythm::VTach, start))]);
+
+            let result = process_waveform_transition(&mut waveform_segments, &SessionDate::default(), None, &create_waveform_row(115, "CR_VTach", "CR_VTach"));
+
+            assert!(result.is_none());
+            assert_eq!(waveform_segments.get(&WaveformChannel::Hr).unwrap().1.timestamp.total_seconds, 49);
+        }
+
+        #[test]
+        fn transition_closes_open_segment() {
+            let start = PlotLocation { timestamp: CsvRowTime { total_seconds: 49, ..Default::default() }, ..Default::default() };
+            let mut waveform_segments = BTreeMap::from([(WaveformChannel::Hr, (Rhythm::VTach, start))]);
+            let stage = (2, "V-Tach 2A.1".to_owned());
+
+            let result = process_waveform_transition(&mut waveform_segments, &SessionDate::default(), Some(&stage), &create_waveform_row(140, "CR_VTach", "CR_SinLike"));
+
+            if let Some(Ok(ActionPlotPoint::Period(PeriodType::HrWaveform(Rhythm::VTach), start, end))) = result {
+                assert_eq!(start.timestamp.total_seconds, 49);
+                assert_eq!(end.timestamp.total_seconds, 140);
+                assert_eq!(end.stage, stage);
+            } else {
+                panic!("Expected ActionPlotPoint::Period with PeriodType::HrWaveform");
+            }
+            assert_eq!(waveform_segments.get(&WaveformChannel::Hr).unwrap().0, Rhythm::SinLike);
+        }
+
+        #[test]
+        fn not_waveform_row() {
+            let mut waveform_segments = BTreeMap::new();
+            let csv_row = ActionCsvRow {
+                action_vital_name: "HR(vital)".to_owned(),
+                ..Default::default()
+            };
+
+            assert!(process_waveform_transition(&mut waveform_segments, &SessionDate::default(), None, &csv_row).is_none());
+            assert!(waveform_segments.is_empty());
+        }
+    }
+
+    mod close_open_waveform_segments {
+        use super::super::*;
+        use std::collections::BTreeMap;
+
+        #[test]
+        fn closes_every_channel() {
+            let start = PlotLocation { timestamp: CsvRowTime { total_seconds: 49, ..Default::default() }, ..Default::default() };
+            let mut waveform_segments = BTreeMap::from([
+                (WaveformChannel::Rr, (Rhythm::CO2Cpr, start.clone())),
+                (WaveformChannel::Hr, (Rhythm::VFib, start)),
+            ]);
+            let last_timestamp = CsvRowTime { total_seconds: 670, ..Default::default() };
+
+            let result = close_open_waveform_segments(&mut waveform_segments, Some(&last_timestamp));
+
+            assert_eq!(result.len(), 2);
+            assert!(matches!(&result[0], Ok(ActionPlotPoint::Period(PeriodType::HrWaveform(Rhythm::VFib), _, end)) if end.timestamp.total_seconds == 670));
+            assert!(matches!(&result[1], Ok(ActionPlotPoint::Period(PeriodType::RrWaveform(Rhythm::CO2Cpr), _, end)) if end.timestamp.total_seconds == 670));
+            assert!(waveform_segments.is_empty());
+        }
+    }
+
+    mod flush_recent_actions {
+        use super::super::*;
+        use std::collections::VecDeque;
+
+        #[test]
+        fn drains_buffer_in_order() {
+            let mut recent_rows = VecDeque::from(vec![
+                ActionCsvRow { action_point: true, action_name: "Pulse Check".to_owned(), row_number: 7, ..Default::default() },
+                ActionCsvRow { action_point: true, action_name: "Order EKG".to_owned(), row_number: 9, ..Default::default() },
+            ]);
+
+            let result = flush_recent_actions(&mut recent_rows);
+
+            let names: Vec<(usize, String)> = result.into_iter().map(|(row_number, point)| match point {
+                Ok(ActionPlotPoint::Action(action)) => (row_number, action.name),
+                other => panic!("Expected ActionPlotPoint::Action, got {:?}", other),
+            }).collect();
+            assert_eq!(names, vec![(7, "Pulse Check".to_owned()), (9, "Order EKG".to_owned())]);
+            assert!(recent_rows.is_empty());
+        }
+
+        #[test]
+        fn empty_buffer() {
+            let mut recent_rows = VecDeque::new();
+
+            assert!(flush_recent_actions(&mut recent_rows).is_empty());
+        }
+    }
+
+    mod flush_pending_error_marker {
+        use super::super::*;
+        use std::cell::RefCell;
+
+        #[test]
+        fn reports_unresolved_marker() {
+            let error_marker_row = ActionCsvRow {
+                action_vital_name: "EKG".to_owned(),
+                ..Default::default()
+            };
+            let pending_error_marker = RefCell::new(Some((5, error_marker_row)));
+
+            let result = flush_pending_error_marker(&pending_error_marker);
+
+            assert_eq!(
+                result,
+                Some(Err(ProcessorError::UnresolvedErrorMarker { row_number: 5, marker: "EKG".to_owned() }))
+            );
+            assert!(pending_error_marker.borrow().is_none());
+        }
+
+        #[test]
+        fn no_pending_marker() {
+            let pending_error_marker = RefCell::new(None);
+
+            assert!(flush_pending_error_marker(&pending_error_marker).is_none());
+        }
+    }
+
+    mod close_open_cpr_period {
+        use super::super::*;
+
+        fn create_location(sec: u32, stage: u32) -> PlotLocation {
+            PlotLocation {
+                timestamp: CsvRowTime { total_seconds: sec, ..Default::default() },
+                stage: (stage, format!("Stage {}", stage)),
+            }
+        }
+
+        #[test]
+        fn closes_at_last_timestamp() {
+            let start = create_location(120, 2);
+            let mut cpr_points = vec![(start.clone(), start)];
+            let last_timestamp = CsvRowTime { total_seconds: 300, ..Default::default() };
+
+            let result = close_open_cpr_period(&mut cpr_points, Some(&last_timestamp));
+
+            if let Some(Ok(ActionPlotPoint::Period(PeriodType::CPR, start, end))) = result {
+                assert_eq!(start.timestamp.total_seconds, 120);
+                assert_eq!(end.timestamp.total_seconds, 300);
+                assert_eq!(end.stage, (2, "Stage 2".to_owned()));
+            } else {
+                panic!("Expected ActionPlotPoint::Period with PeriodType::CPR");
+            }
+            assert!(cpr_points.is_empty());
+        }
+
+        #[test]
+        fn no_open_period() {
+            let mut cpr_points = Vec::new();
+            let last_timestamp = CsvRowTime::default();
+
+            assert!(close_open_cpr_period(&mut cpr_points, Some(&last_timestamp)).is_none());
+        }
+    }
+
+    mod close_open_stage_period {
+        use super::super::*;
+
+        fn create_boundary(sec: u32, stage: u32) -> PlotLocation {
+            PlotLocation {
+                timestamp: CsvRowTime { total_seconds: sec, ..Default::default() },
+                stage: (stage, format!("Stage {}", stage)),
+            }
+        }
+
+        #[test]
+        fn closes_stage_without_end_boundary() {
+            let mut stage_boundary_points = vec![create_boundary(100, 1)];
+            let current_stage = (2, "Stage 2".to_owned());
+            let last_timestamp = CsvRowTime { total_seconds: 250, ..Default::default() };
+
+            let result = close_open_stage_period(&mut stage_boundary_points, Some(&current_stage), Some(&last_timestamp));
+
+            if let Some(Ok(ActionPlotPoint::Stage(stage_period))) = result {
+                assert_eq!(stage_period.start.total_seconds, 100);
+                assert_eq!(stage_period.end.total_seconds, 250);
+                assert_eq!(stage_period.duration_seconds, 150);
+                assert_eq!(stage_period.stage, current_stage);
+            } else {
+                panic!("Expected ActionPlotPoint::Stage");
+            }
+        }
+
+        #[test]
+        fn last_stage_already_closed() {
+            let mut stage_boundary_points = vec![create_boundary(100, 2)];
+            let current_stage = (2, "Stage 2".to_owned());
+            let last_timestamp = CsvRowTime { total_seconds: 250, ..Default::default() };
+
+            let result = close_open_stage_period(&mut stage_boundary_points, Some(&current_stage), Some(&last_timestamp));
+
+            assert!(result.is_none());
+            assert_eq!(stage_boundary_points.len(), 1);
+        }
+
+        #[test]
+        fn next_stage_not_named() {
+            let mut stage_boundary_points = vec![create_boundary(100, 2)];
+            let current_stage = (3, String::new());
+            let last_timestamp = CsvRowTime { total_seconds: 250, ..Default::default() };
+
+            assert!(close_open_stage_period(&mut stage_boundary_points, Some(&current_stage), Some(&last_timestamp)).is_none());
+        }
+
+        #[test]
+        fn no_stage_seen() {
+            let mut stage_boundary_points = vec![PlotLocation::default()];
+            let last_timestamp = CsvRowTime::default();
+
+            assert!(close_open_stage_period(&mut stage_boundary_points, None, Some(&last_timestamp)).is_none());
+        }
+    }
+}
@@ -1,27 +1,38 @@
 use crate::action_csv_row::ActionCsvRow;
-use chrono::{DateTime, Datelike, TimeZone, Utc};
+use crate::detection::{is_rule_triggered, is_unrecognized_speech, NO_USER};
+use crate::parsing::{parse_rhythm_family, parse_severity};
+use crate::session_date::SessionDate;
+use chrono::{DateTime, FixedOffset};
 use serde::Serialize;
+use std::fmt::{Display, Formatter};
 
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct CsvRowTime {
     pub total_seconds: u32,
     pub date_string: String,
     pub timestamp: String,
+    pub datetime: DateTime<FixedOffset>,
 }
 
-impl Default for CsvRowTime {
-    fn default() -> Self {
-        let now_utc: DateTime<Utc> = Utc::now();
-        let beginning_of_day_utc: DateTime<Utc> = Utc.with_ymd_and_hms(now_utc.year(), now_utc.month(), now_utc.day(), 0, 0, 0).unwrap();
-
+impl CsvRowTime {
+    pub fn new(total_seconds: u32, session_date: &SessionDate) -> Self {
+        let datetime = session_date.datetime(total_seconds);
         CsvRowTime {
-            total_seconds: 0,
-            date_string: beginning_of_day_utc.format("%Y-%m-%d %H:%M:%S").to_string(),
-            timestamp: String::from("00:00:00")
+            total_seconds,
+            // Wall clock time of the session, plotly does not understand utc offsets.
+            date_string: datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
+            timestamp: format!("{:02}:{:02}:{:02}", total_seconds / 3600, total_seconds / 60 % 60, total_seconds % 60),
+            datetime,
         }
     }
 }
 
+impl Default for CsvRowTime {
+    fn default() -> Self {
+        CsvRowTime::new(0, &SessionDate::default())
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Serialize)]
 pub struct PlotLocation {
     pub timestamp: CsvRowTime,
@@ -31,25 +42,54 @@ pub struct PlotLocation {
 impl PlotLocation {
     pub fn new(row: &ActionCsvRow) -> Self {
         Self {
-            timestamp: row.timestamp.clone().unwrap_or(CsvRowTime::default()),
+            timestamp: row.timestamp.clone().unwrap_or_default(),
             stage: row.parsed_stage.clone().unwrap_or(PlotLocation::default().stage),
         }
     }
+
+    pub fn in_stage(row: &ActionCsvRow, stage: Option<&(u32, String)>) -> Self {
+        Self {
+            timestamp: row.timestamp.clone().unwrap_or_default(),
+            stage: stage.cloned().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Serialize)]
+pub enum Severity {
+    Warning,
+    Error,
+    CriticalError,
+    Unknown(String)
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Unknown(severity) => write!(f, "{}", severity),
+            _ => write!(f, "{:?}", self),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Serialize, Clone)]
 pub struct ErrorInfo {
     pub action_rule: String,
+    pub severity: Severity,
+    pub expectation: String,
     pub violation: String,
     pub advice: String
 }
 
 impl ErrorInfo {
     pub fn new(row: &ActionCsvRow) -> Self {
+        let rule = row.rule.clone().unwrap_or_default();
         Self {
-            action_rule: row.subaction_name.clone(),
-            violation: row.score.clone(),
-            advice: row.speech_command.clone(),
+            action_rule: rule.rule_id,
+            severity: parse_severity(&rule.severity),
+            expectation: rule.expectation,
+            violation: rule.outcome,
+            advice: rule.advice.trim().to_owned(),
         }
     }
 }
@@ -59,19 +99,21 @@ pub struct Action {
     pub location: PlotLocation,
     pub name: String,
     pub action_category: String,
-    pub shock_value: String
+    pub shock_value: String,
+    pub username: String
 }
 
 impl Action {
     pub fn new(row: &ActionCsvRow) -> Self {
         Self {
             location:PlotLocation {
-                timestamp: row.timestamp.clone().unwrap_or(CsvRowTime::default()),
+                timestamp: row.timestamp.clone().unwrap_or_default(),
                 stage: row.parsed_stage.clone().unwrap_or(PlotLocation::default().stage),
             },
             name: row.action_name.clone(),
             action_category: row.action_category.clone(),
             shock_value: row.shock_value.clone(),
+            username: row.username.clone(),
         }
     }
 }
@@ -82,6 +124,7 @@ pub struct ErroneousAction {
     pub name: String,
     pub action_category: String,
     pub shock_value: String,
+    pub username: String,
     pub error_info: ErrorInfo
 }
 
@@ -92,6 +135,7 @@ impl ErroneousAction {
             name: action_row.action_name.clone(),
             action_category: action_row.action_category.clone(),
             shock_value: action_row.shock_value.clone(),
+            username: action_row.username.clone(),
             error_info: ErrorInfo::new(error_marker_row)
         }
     }
@@ -101,15 +145,166 @@ impl ErroneousAction {
 pub struct MissedAction {
     pub location: PlotLocation,
     pub name: String,
+    /// Participant the rule was evaluated for, None when the rule row has no user.
+    pub username: Option<String>,
     pub error_info: ErrorInfo
 }
 
 impl MissedAction {
     pub(crate) fn new(row: &ActionCsvRow) -> MissedAction {
+        let error_info = ErrorInfo::new(row);
+        let username = row.rule.as_ref()
+            .map(|rule| rule.username.trim())
+            .filter(|username| !username.is_empty() && *username != NO_USER)
+            .map(str::to_owned);
         MissedAction {
             location: PlotLocation::new(row),
-            name: row.action_vital_name.clone(),
-            error_info: ErrorInfo::new(row)
+            name: error_info.action_rule.clone(),
+            username,
+            error_info
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub enum VitalSign {
+    HeartRate,
+    BloodPressure,
+    PulseRate,
+    RespiratoryRate,
+    EndTidalCO2,
+    Temperature,
+    Systolic,
+    Diastolic,
+    Other(String)
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub enum VitalValue {
+    Measured(f64),
+    NotMeasurable
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct VitalSample {
+    pub location: PlotLocation,
+    pub vital_sign: VitalSign,
+    pub old_value: VitalValue,
+    pub new_value: VitalValue
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct RuleEvaluation {
+    pub location: PlotLocation,
+    pub rule_id: String,
+    pub severity: Severity,
+    pub expectation: String,
+    pub outcome: String,
+    pub triggered: bool,
+    pub advice: String
+}
+
+impl RuleEvaluation {
+    pub fn new(row: &ActionCsvRow) -> Self {
+        let rule = row.rule.clone().unwrap_or_default();
+        Self {
+            location: PlotLocation::new(row),
+            rule_id: rule.rule_id,
+            severity: parse_severity(&rule.severity),
+            expectation: rule.expectation,
+            outcome: rule.outcome,
+            triggered: is_rule_triggered(row),
+            advice: rule.advice.trim().to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct SpeechEvent {
+    pub location: PlotLocation,
+    pub speaker: String,
+    pub transcript: String,
+    pub no_match: bool
+}
+
+impl SpeechEvent {
+    pub fn new(row: &ActionCsvRow, stage: Option<&(u32, String)>) -> Self {
+        Self {
+            location: PlotLocation::in_stage(row, stage),
+            speaker: row.username.clone(),
+            transcript: row.speech_command.trim().to_owned(),
+            no_match: is_unrecognized_speech(row),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct RatingScale {
+    pub min: u32,
+    pub max: u32
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct QuestionnaireItem {
+    pub question: String,
+    pub rating: u32,
+    pub scale: RatingScale
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize)]
+pub enum WaveformChannel {
+    Hr,
+    Pr,
+    Rr
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub enum Rhythm {
+    NormalSinus,
+    VTach,
+    VFib,
+    Asystole,
+    SinLike,
+    OxygenNormal,
+    OxygenPoorPerfusion,
+    CO2Normal,
+    CO2CardiacArrest,
+    CO2Cpr,
+    Other(String)
+}
+
+impl Rhythm {
+    pub fn is_shockable(&self) -> bool {
+        matches!(self, Rhythm::VTach | Rhythm::VFib)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub enum RhythmFamily {
+    VTach,
+    VFib,
+    Asystole,
+    Rosc
+}
+
+/// A scenario stage, from the previous stage boundary row (or the start of the log) to the row that names it.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct StagePeriod {
+    pub stage: (u32, String),
+    pub start: CsvRowTime,
+    pub end: CsvRowTime,
+    pub duration_seconds: u32,
+    pub rhythm_family: Option<RhythmFamily>
+}
+
+impl StagePeriod {
+    pub fn new(stage: (u32, String), start: CsvRowTime, end: CsvRowTime) -> Self {
+        Self {
+            rhythm_family: parse_rhythm_family(&stage.1),
+            duration_seconds: end.total_seconds.saturating_sub(start.total_seconds),
+            stage,
+            start,
+            end,
         }
     }
 }
@@ -117,7 +312,65 @@ impl MissedAction {
 #[derive(Debug, PartialEq, Clone, Serialize)]
 pub enum PeriodType {
     CPR,
-    Stage
+    HrWaveform(Rhythm),
+    PrWaveform(Rhythm),
+    RrWaveform(Rhythm)
+}
+
+impl PeriodType {
+    pub fn waveform(channel: &WaveformChannel, rhythm: Rhythm) -> Self {
+        match channel {
+            WaveformChannel::Hr => PeriodType::HrWaveform(rhythm),
+            WaveformChannel::Pr => PeriodType::PrWaveform(rhythm),
+            WaveformChannel::Rr => PeriodType::RrWaveform(rhythm),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub enum ProtocolRule {
+    EpinephrineTooSoon,
+    EpinephrineTooLate,
+    AmiodaroneBeforeThirdShock,
+    AmiodaroneDoseLimit,
+    CprAfterShock,
+    PulseCheckTooLong
+}
+
+impl ProtocolRule {
+    /// Identifier of the rule in exported tables, the same as in json.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProtocolRule::EpinephrineTooSoon => "EpinephrineTooSoon",
+            ProtocolRule::EpinephrineTooLate => "EpinephrineTooLate",
+            ProtocolRule::AmiodaroneBeforeThirdShock => "AmiodaroneBeforeThirdShock",
+            ProtocolRule::AmiodaroneDoseLimit => "AmiodaroneDoseLimit",
+            ProtocolRule::CprAfterShock => "CprAfterShock",
+            ProtocolRule::PulseCheckTooLong => "PulseCheckTooLong",
+        }
+    }
+}
+
+impl Display for ProtocolRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            ProtocolRule::EpinephrineTooSoon => "Epinephrine repeated too soon",
+            ProtocolRule::EpinephrineTooLate => "Epinephrine repeated too late",
+            ProtocolRule::AmiodaroneBeforeThirdShock => "Amiodarone before the third shock",
+            ProtocolRule::AmiodaroneDoseLimit => "Amiodarone dose limit exceeded",
+            ProtocolRule::CprAfterShock => "CPR not resumed after shock",
+            ProtocolRule::PulseCheckTooLong => "Pulse check too long",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct ProtocolDeviation {
+    pub location: PlotLocation,
+    pub rule: ProtocolRule,
+    pub action_name: String,
+    pub explanation: String
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize)]
@@ -125,6 +378,30 @@ pub enum ActionPlotPoint {
     Error(ErroneousAction),
     Action(Action),
     MissedAction(MissedAction),
-    Period(PeriodType, PlotLocation, PlotLocation)
+    Period(PeriodType, PlotLocation, PlotLocation),
+    Stage(StagePeriod),
+    Vital(VitalSample),
+    Speech(SpeechEvent),
+    Questionnaire(QuestionnaireItem),
+    RuleEvaluation(RuleEvaluation),
+    ProtocolDeviation(ProtocolDeviation)
 }
 
+
+impl ActionPlotPoint {
+    /// Name of the variant, as used by serde.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ActionPlotPoint::Error(_) => "Error",
+            ActionPlotPoint::Action(_) => "Action",
+            ActionPlotPoint::MissedAction(_) => "MissedAction",
+            ActionPlotPoint::Period(..) => "Period",
+            ActionPlotPoint::Stage(_) => "Stage",
+            ActionPlotPoint::Vital(_) => "Vital",
+            ActionPlotPoint::Speech(_) => "Speech",
+            ActionPlotPoint::Questionnaire(_) => "Questionnaire",
+            ActionPlotPoint::RuleEvaluation(_) => "RuleEvaluation",
+            ActionPlotPoint::ProtocolDeviation(_) => "ProtocolDeviation",
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use crate::action_catalogue::ActionCatalogue;
+use crate::plot_structures::{ActionPlotPoint, CsvRowTime, ErrorInfo, PeriodType, PlotLocation};
+use serde::Serialize;
+use std::sync::Arc;
+
+const ERROR_TRACE_NAME: &str = "Errors";
+const MISSED_ACTION_TRACE_NAME: &str = "Missed Actions";
+const PROTOCOL_DEVIATION_TRACE_NAME: &str = "Protocol Deviations";
+const STAGE_FILL_COLORS: [&str; 2] = ["rgba(31, 119, 180, 0.08)", "rgba(44, 160, 44, 0.08)"];
+const CPR_FILL_COLOR: &str = "rgba(214, 39, 40, 0.15)";
+
+/// Complete figure that can be passed as is to `Plotly.newPlot(element, figure.data, figure.layout)`.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct PlotlyFigure {
+    pub data: Vec<ScatterTrace>,
+    pub layout: Layout,
+    /// Marker symbols and colours of the action categories.
+    #[serde(skip)]
+    catalogue: Arc<ActionCatalogue>
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct ScatterTrace {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub trace_type: String,
+    pub mode: String,
+    pub x: Vec<String>,
+    pub y: Vec<String>,
+    pub text: Vec<String>,
+    pub hoverinfo: String,
+    pub marker: Marker
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Marker {
+    pub symbol: String,
+    pub size: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Axis {
+    pub title: String,
+    #[serde(rename = "type")]
+    pub axis_type: String
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Layout {
+    pub hovermode: String,
+    pub xaxis: Axis,
+    pub yaxis: Axis,
+    pub shapes: Vec<Shape>
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct ShapeLine {
+    pub width: u32
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Shape {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub shape_type: String,
+    pub xref: String,
+    pub yref: String,
+    pub x0: String,
+    pub x1: String,
+    pub y0: f64,
+    pub y1: f64,
+    pub fillcolor: String,
+    pub layer: String,
+    pub line: ShapeLine
+}
+
+impl ScatterTrace {
+    fn new(name: &str, symbol: &str, color: Option<&str>) -> Self {
+        Self {
+            name: name.to_owned(),
+            trace_type: "scatter".to_owned(),
+            mode: "markers".to_owned(),
+            x: vec![],
+            y: vec![],
+            text: vec![],
+            hoverinfo: "text".to_owned(),
+            marker: Marker {
+                symbol: symbol.to_owned(),
+                size: 10,
+                color: color.map(str::to_owned),
+            },
+        }
+    }
+
+    fn push(&mut self, location: &PlotLocation, name: &str, text: String) {
+        self.x.push(location.timestamp.date_string.clone());
+        self.y.push(name.to_owned());
+        self.text.push(text);
+    }
+}
+
+impl Shape {
+    fn rect(name: String, start: &CsvRowTime, end: &CsvRowTime, fillcolor: &str) -> Self {
+        Self {
+            name,
+            shape_type: "rect".to_owned(),
+            xref: "x".to_owned(),
+            yref: "paper".to_owned(),
+            x0: start.date_string.clone(),
+            x1: end.date_string.clone(),
+            y0: 0.0,
+            y1: 1.0,
+            fillcolor: fillcolor.to_owned(),
+            layer: "below".to_owned(),
+            line: ShapeLine { width: 0 },
+        }
+    }
+}
+
+impl Default for PlotlyFigure {
+    fn default() -> Self {
+        Self {
+            data: vec![],
+            layout: Layout {
+                hovermode: "closest".to_owned(),
+                xaxis: Axis { title: "Time".to_owned(), axis_type: "date".to_owned() },
+                yaxis: Axis { title: "Action".to_owned(), axis_type: "category".to_owned() },
+                shapes: vec![],
+            },
+            catalogue: ActionCatalogue::builtin(),
+        }
+    }
+}
+
+impl PlotlyFigure {
+    pub fn from_points<I>(points: I) -> Self
+    where
+        I: IntoIterator<Item = ActionPlotPoint>,
+    {
+        Self::from_points_with_catalogue(points, ActionCatalogue::builtin())
+    }
+
+    pub fn from_points_with_catalogue<I>(points: I, catalogue: Arc<ActionCatalogue>) -> Self
+    where
+        I: IntoIterator<Item = ActionPlotPoint>,
+    {
+        let mut figure = Self { catalogue, ..Self::default() };
+        points.into_iter().for_each(|point| figure.add_point(&point));
+        figure
+    }
+
+    /// Points that have no representation in the timeline figure (vitals, speech, etc.) are ignored.
+    pub fn add_point(&mut self, point: &ActionPlotPoint) {
+        match point {
+            ActionPlotPoint::Action(action) => {
+                let dose_unit = self.catalogue.lookup(&action.name).and_then(|entry| entry.dose_unit.as_deref());
+                let text = format!(
+                    "{}<br>{}<br>{}{}{}",
+                    action.name,
+                    action.location.timestamp.timestamp,
+                    action.location.stage.1,
+                    shock_text(&action.shock_value),
+                    dose_unit.map(|dose_unit| format!("<br>Dose unit: {}", dose_unit)).unwrap_or_default()
+                );
+                let catalogue = self.catalogue.clone();
+                let (icon, color) = catalogue.category_style(&action.action_category);
+                self.trace(&action.action_category, icon.unwrap_or("circle"), color)
+                    .push(&action.location, &action.name, text);
+            }
+            ActionPlotPoint::Error(error) => {
+                let text = format!(
+                    "{}<br>{}<br>{}",
+                    error.name,
+                    error.location.timestamp.timestamp,
+                    error_info_text(&error.error_info)
+                );
+                self.trace(ERROR_TRACE_NAME, "x", Some("red"))
+                    .push(&error.location, &error.name, text);
+            }
+            ActionPlotPoint::MissedAction(missed) => {
+                let text = format!(
+                    "{}<br>{}<br>{}",
+                    missed.name,
+                    missed.location.timestamp.timestamp,
+                    error_info_text(&missed.error_info)
+                );
+                self.trace(MISSED_ACTION_TRACE_NAME, "circle-open", Some("orange"))
+                    .push(&missed.location, &missed.name, text);
+            }
+            ActionPlotPoint::ProtocolDeviation(deviation) => {
+                let text = format!(
+                    "{}<br>{}<br>{}<br>{}",
+                    deviation.action_name,
+                    deviation.location.timestamp.timestamp,
+                    deviation.rule,
+                    deviation.explanation
+                );
+                self.trace(PROTOCOL_DEVIATION_TRACE_NAME, "diamond-open", Some("purple"))
+                    .push(&deviation.location, &deviation.action_name, text);
+            }
+            ActionPlotPoint::Stage(stage_period) => {
+                let fillcolor = STAGE_FILL_COLORS[stage_period.stage.0 as usize % STAGE_FILL_COLORS.len()];
+                self.layout.shapes.push(Shape::rect(stage_period.stage.1.clone(), &stage_period.start, &stage_period.end, fillcolor));
+            }
+            ActionPlotPoint::Period(PeriodType::CPR, start, end) => {
+                self.layout.shapes.push(Shape::rect("CPR".to_owned(), &start.timestamp, &end.timestamp, CPR_FILL_COLOR));
+            }
+            _ => {}
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    fn trace(&mut self, name: &str, symbol: &str, color: Option<&str>) -> &mut ScatterTrace {
+        let index = match self.data.iter().position(|trace| trace.name == name) {
+            Some(index) => index,
+            None => {
+                self.data.push(ScatterTrace::new(name, symbol, color));
+                self.data.len() - 1
+            }
+        };
+        &mut self.data[index]
+    }
+}
+
+fn shock_text(shock_value: &str) -> String {
+    if shock_value.is_empty() {
+        String::new()
+    } else {
+        format!("<br>Shock: {}", shock_value)
+    }
+}
+
+fn error_info_text(error_info: &ErrorInfo) -> String {
+    format!(
+        "Rule: {}<br>Severity: {}<br>Expectation: {}<br>Violation: {}<br>{}",
+        error_info.action_rule,
+        error_info.severity,
+        error_info.expectation,
+        error_info.violation,
+        error_info.advice
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plot_structures::{Action, ErroneousAction, MissedAction, ProtocolDeviation, ProtocolRule, Severity, StagePeriod};
+    use crate::test_support::{location, stage_location, time};
+
+    fn error_info() -> ErrorInfo {
+        ErrorInfo {
+            action_rule: "EKG".to_owned(),
+            severity: Severity::Error,
+            expectation: "Action-Should-Not-Be-Performed".to_owned(),
+            violation: "Action-Was-Performed".to_owned(),
+            advice: "Don't interrupt CPR for an EKG.".to_owned(),
+        }
+    }
+
+    mod add_point {
+        use super::*;
+
+        #[test]
+        fn actions_grouped_by_category() {
+            let points = vec![
+                ActionPlotPoint::Action(Action {
+                    location: location(5),
+                    name: "Pulse Check".to_owned(),
+                    action_category: "Assessment".to_owned(),
+                    shock_value: String::new(),
+                    username: "umich1".to_owned(),
+                }),
+                ActionPlotPoint::Action(Action {
+                    location: location(9),
+                    name: "Defib (Unsynchronized)".to_owned(),
+                    action_category: "Defib".to_owned(),
+                    shock_value: "200J".to_owned(),
+                    username: "umich1".to_owned(),
+                }),
+                ActionPlotPoint::Action(Action {
+                    location: location(20),
+                    name: "Pulse Check".to_owned(),
+                    action_category: "Assessment".to_owned(),
+                    shock_value: String::new(),
+                    username: "umich1".to_owned(),
+                }),
+            ];
+
+            let figure = PlotlyFigure::from_points(points);
+
+            assert_eq!(figure.data.len(), 2);
+            assert_eq!(figure.data[0].name, "Assessment");
+            assert_eq!(figure.data[0].x, vec!["2024-09-18 00:00:05", "2024-09-18 00:00:20"]);
+            assert_eq!(figure.data[0].y, vec!["Pulse Check", "Pulse Check"]);
+            assert_eq!(figure.data[1].name, "Defib");
+            assert_eq!(figure.data[1].text, vec!["Defib (Unsynchronized)<br>00:00:09<br>V-Tach 2D<br>Shock: 200J"]);
+        }
+
+        #[test]
+        fn catalogue_styles_categories() {
+            let catalogue = ActionCatalogue::from_toml_str(r#"
+                [[action]]
+                name = "Give Aspirin"
+                category = "Medication"
+                icon = "triangle-up"
+                color = "green"
+                dose_unit = "mg"
+            "#).unwrap();
+            let points = vec![ActionPlotPoint::Action(Action {
+                location: location(5),
+                name: "Give Aspirin".to_owned(),
+                action_category: "Medication".to_owned(),
+                shock_value: String::new(),
+                username: "umich2".to_owned(),
+            })];
+
+            let figure = PlotlyFigure::from_points_with_catalogue(points, Arc::new(catalogue));
+
+            assert_eq!(figure.data[0].marker.symbol, "triangle-up");
+            assert_eq!(figure.data[0].marker.color.as_deref(), Some("green"));
+            assert_eq!(figure.data[0].text, vec!["Give Aspirin<br>00:00:05<br>V-Tach 2D<br>Dose unit: mg"]);
+        }
+
+        #[test]
+        fn error_and_missed_markers() {
+            let points = vec![
+                ActionPlotPoint::Error(ErroneousAction {
+                    location: location(10),
+                    name: "Order EKG".to_owned(),
+                    action_category: "Diagnostics".to_owned(),
+                    shock_value: String::new(),
+                    username: "umich3".to_owned(),
+                    error_info: error_info(),
+                }),
+                ActionPlotPoint::MissedAction(MissedAction {
+                    location: stage_location(30, (2, "V-Fib")),
+                    name: "EKG".to_owned(),
+                    username: Some("umich2".to_owned()),
+                    error_info: error_info(),
+                }),
+            ];
+
+            let figure = PlotlyFigure::from_points(points);
+
+            assert_eq!(figure.data.len(), 2);
+            assert_eq!(figure.data[0].name, ERROR_TRACE_NAME);
+            assert_eq!(figure.data[0].marker.symbol, "x");
+            assert_eq!(figure.data[0].y, vec!["Order EKG"]);
+            assert_eq!(
+                figure.data[0].text,
+                vec!["Order EKG<br>00:00:10<br>Rule: EKG<br>Severity: Error<br>Expectation: Action-Should-Not-Be-Performed<br>Violation: Action-Was-Performed<br>Don't interrupt CPR for an EKG."]
+            );
+            assert_eq!(figure.data[1].name, MISSED_ACTION_TRACE_NAME);
+            assert_eq!(figure.data[1].y, vec!["EKG"]);
+        }
+
+        #[test]
+        fn unknown_severity_as_written() {
+            let points = vec![ActionPlotPoint::Error(ErroneousAction {
+                location: location(10),
+                name: "Order EKG".to_owned(),
+                action_category: "Diagnostics".to_owned(),
+                shock_value: String::new(),
+                username: "umich3".to_owned(),
+                error_info: ErrorInfo { severity: Severity::Unknown("Minor".to_owned()), ..error_info() },
+            })];
+
+            let figure = PlotlyFigure::from_points(points);
+
+            assert!(figure.data[0].text[0].contains("<br>Severity: Minor<br>"));
+        }
+
+        #[test]
+        fn protocol_deviation_markers() {
+            let points = vec![ActionPlotPoint::ProtocolDeviation(ProtocolDeviation {
+                location: stage_location(40, (2, "V-Fib")),
+                rule: ProtocolRule::EpinephrineTooSoon,
+                action_name: "Select Epinephrine".to_owned(),
+                explanation: "Give epinephrine every 3-5 minutes.".to_owned(),
+            })];
+
+            let figure = PlotlyFigure::from_points(points);
+
+            assert_eq!(figure.data.len(), 1);
+            assert_eq!(figure.data[0].name, PROTOCOL_DEVIATION_TRACE_NAME);
+            assert_eq!(figure.data[0].y, vec!["Select Epinephrine"]);
+            assert_eq!(figure.data[0].text, vec!["Select Epinephrine<br>00:00:40<br>Epinephrine repeated too soon<br>Give epinephrine every 3-5 minutes."]);
+        }
+
+        #[test]
+        fn periods_as_shapes() {
+            let points = vec![
+                ActionPlotPoint::Period(PeriodType::CPR, location(3), location(8)),
+                ActionPlotPoint::Stage(StagePeriod::new((1, "V-Tach 2D".to_owned()), time(0), time(55))),
+            ];
+
+            let figure = PlotlyFigure::from_points(points);
+
+            assert!(figure.data.is_empty());
+            assert_eq!(figure.layout.shapes.len(), 2);
+            assert_eq!(figure.layout.shapes[0].name, "CPR");
+            assert_eq!(figure.layout.shapes[0].fillcolor, CPR_FILL_COLOR);
+            assert_eq!(figure.layout.shapes[1].name, "V-Tach 2D");
+            assert_eq!(figure.layout.shapes[1].x0, "2024-09-18 00:00:00");
+            assert_eq!(figure.layout.shapes[1].x1, "2024-09-18 00:00:55");
+        }
+
+        #[test]
+        fn unplotted_points_ignored() {
+            let points = vec![ActionPlotPoint::Period(
+                PeriodType::HrWaveform(crate::plot_structures::Rhythm::VTach),
+                location(0),
+                location(10),
+            )];
+
+            let figure = PlotlyFigure::from_points(points);
+
+            assert_eq!(figure, PlotlyFigure::default());
+        }
+    }
+
+    mod to_json {
+        use super::*;
+
+        #[test]
+        fn plotly_field_names() {
+            let figure = PlotlyFigure::from_points(vec![ActionPlotPoint::Period(
+                PeriodType::CPR,
+                location(3),
+                location(8),
+            )]);
+
+            let json: serde_json::Value = serde_json::from_str(&figure.to_json().unwrap()).unwrap();
+
+            assert_eq!(json["layout"]["shapes"][0]["type"], "rect");
+            assert_eq!(json["layout"]["xaxis"]["type"], "date");
+            assert!(json["data"].as_array().unwrap().is_empty());
+        }
+    }
+}
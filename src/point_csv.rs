@@ -0,0 +1,243 @@
+use crate::plot_structures::{ActionPlotPoint, CsvRowTime, PeriodType, PlotLocation, Rhythm, RhythmFamily, VitalSign, VitalValue};
+use crate::processor_error::ProcessorError;
+use serde::Serialize;
+use std::borrow::Borrow;
+use std::io::Write;
+
+/// One csv line per point, columns a kind of point has no value for are left empty.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct PointRecord {
+    pub kind: &'static str,
+    pub seconds: Option<u32>,
+    pub timestamp: String,
+    /// End of periods and stages.
+    pub end_seconds: Option<u32>,
+    pub stage_number: Option<u32>,
+    pub stage_name: String,
+    pub name: String,
+    pub category: String,
+    pub username: String,
+    pub value: String,
+    pub rule: String,
+    pub severity: String,
+    pub detail: String
+}
+
+impl PointRecord {
+    fn at(kind: &'static str, location: &PlotLocation) -> Self {
+        Self {
+            kind,
+            stage_number: Some(location.stage.0),
+            stage_name: location.stage.1.clone(),
+            ..Self::at_time(kind, &location.timestamp)
+        }
+    }
+
+    fn at_time(kind: &'static str, timestamp: &CsvRowTime) -> Self {
+        Self {
+            kind,
+            seconds: Some(timestamp.total_seconds),
+            timestamp: timestamp.timestamp.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+pub(crate) fn vital_sign_name(vital_sign: &VitalSign) -> String {
+    match vital_sign {
+        VitalSign::Other(name) => name.clone(),
+        vital_sign => format!("{:?}", vital_sign),
+    }
+}
+
+pub(crate) fn rhythm_name(rhythm: &Rhythm) -> String {
+    match rhythm {
+        Rhythm::Other(name) => name.clone(),
+        rhythm => format!("{:?}", rhythm),
+    }
+}
+
+/// Type of a period and the rhythm of a waveform period, e.g. `("HrWaveform", Some("VTach"))`.
+pub(crate) fn period_type_name(period_type: &PeriodType) -> (&'static str, Option<String>) {
+    match period_type {
+        PeriodType::CPR => ("CPR", None),
+        PeriodType::HrWaveform(rhythm) => ("HrWaveform", Some(rhythm_name(rhythm))),
+        PeriodType::PrWaveform(rhythm) => ("PrWaveform", Some(rhythm_name(rhythm))),
+        PeriodType::RrWaveform(rhythm) => ("RrWaveform", Some(rhythm_name(rhythm))),
+    }
+}
+
+pub(crate) fn rhythm_family_name(rhythm_family: &RhythmFamily) -> &'static str {
+    match rhythm_family {
+        RhythmFamily::VTach => "VTach",
+        RhythmFamily::VFib => "VFib",
+        RhythmFamily::Asystole => "Asystole",
+        RhythmFamily::Rosc => "Rosc",
+    }
+}
+
+fn vital_value(value: &VitalValue) -> String {
+    match value {
+        VitalValue::Measured(value) => value.to_string(),
+        VitalValue::NotMeasurable => "--".to_owned(),
+    }
+}
+
+impl From<&ActionPlotPoint> for PointRecord {
+    fn from(point: &ActionPlotPoint) -> Self {
+        let kind = point.kind();
+        match point {
+            ActionPlotPoint::Action(action) => Self {
+                name: action.name.clone(),
+                category: action.action_category.clone(),
+                username: action.username.clone(),
+                value: action.shock_value.clone(),
+                ..Self::at(kind, &action.location)
+            },
+            ActionPlotPoint::Error(error) => Self {
+                name: error.name.clone(),
+                category: error.action_category.clone(),
+                username: error.username.clone(),
+                value: error.shock_value.clone(),
+                rule: error.error_info.action_rule.clone(),
+                severity: error.error_info.severity.to_string(),
+                detail: error.error_info.advice.clone(),
+                ..Self::at(kind, &error.location)
+            },
+            ActionPlotPoint::MissedAction(missed) => Self {
+                name: missed.name.clone(),
+                username: missed.username.clone().unwrap_or_default(),
+                rule: missed.error_info.action_rule.clone(),
+                severity: missed.error_info.severity.to_string(),
+                detail: missed.error_info.advice.clone(),
+                ..Self::at(kind, &missed.location)
+            },
+            ActionPlotPoint::Period(period_type, start, end) => Self {
+                name: match period_type_name(period_type) {
+                    (period_type, Some(rhythm)) => format!("{}({})", period_type, rhythm),
+                    (period_type, None) => period_type.to_owned(),
+                },
+                end_seconds: Some(end.timestamp.total_seconds),
+                ..Self::at(kind, start)
+            },
+            ActionPlotPoint::Stage(stage_period) => Self {
+                end_seconds: Some(stage_period.end.total_seconds),
+                stage_number: Some(stage_period.stage.0),
+                stage_name: stage_period.stage.1.clone(),
+                category: stage_period.rhythm_family.as_ref().map(rhythm_family_name).unwrap_or_default().to_owned(),
+                value: stage_period.duration_seconds.to_string(),
+                ..Self::at_time(kind, &stage_period.start)
+            },
+            ActionPlotPoint::Vital(sample) => Self {
+                name: vital_sign_name(&sample.vital_sign),
+                value: vital_value(&sample.new_value),
+                detail: vital_value(&sample.old_value),
+                ..Self::at(kind, &sample.location)
+            },
+            ActionPlotPoint::Speech(speech) => Self {
+                username: speech.speaker.clone(),
+                value: speech.no_match.to_string(),
+                detail: speech.transcript.clone(),
+                ..Self::at(kind, &speech.location)
+            },
+            ActionPlotPoint::Questionnaire(item) => Self {
+                kind,
+                name: item.question.clone(),
+                value: item.rating.to_string(),
+                detail: format!("{}-{}", item.scale.min, item.scale.max),
+                ..Default::default()
+            },
+            ActionPlotPoint::RuleEvaluation(evaluation) => Self {
+                rule: evaluation.rule_id.clone(),
+                severity: evaluation.severity.to_string(),
+                value: evaluation.triggered.to_string(),
+                detail: evaluation.outcome.clone(),
+                ..Self::at(kind, &evaluation.location)
+            },
+            ActionPlotPoint::ProtocolDeviation(deviation) => Self {
+                name: deviation.action_name.clone(),
+                rule: deviation.rule.name().to_owned(),
+                detail: deviation.explanation.clone(),
+                ..Self::at(kind, &deviation.location)
+            },
+        }
+    }
+}
+
+/// Writes the points as csv with a header line.
+pub fn write_points_csv<W, I>(writer: W, points: I) -> Result<(), ProcessorError>
+where
+    W: Write,
+    I: IntoIterator,
+    I::Item: Borrow<ActionPlotPoint>,
+{
+    let io_error = |e: csv::Error| ProcessorError::Io { message: e.to_string() };
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    for point in points {
+        csv_writer.serialize(PointRecord::from(point.borrow())).map_err(io_error)?;
+    }
+    csv_writer.flush().map_err(|e| ProcessorError::Io { message: e.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plot_structures::{Action, PeriodType};
+    use crate::test_support::location;
+
+    mod point_record {
+        use super::*;
+
+        #[test]
+        fn action() {
+            let point = ActionPlotPoint::Action(Action {
+                location: location(12),
+                name: "Defib (200J)".to_owned(),
+                action_category: "Defib".to_owned(),
+                shock_value: "200J".to_owned(),
+                username: "umich1".to_owned(),
+            });
+
+            let record = PointRecord::from(&point);
+
+            assert_eq!(record, PointRecord {
+                kind: "Action",
+                seconds: Some(12),
+                timestamp: "00:00:12".to_owned(),
+                stage_number: Some(1),
+                stage_name: "V-Tach 2D".to_owned(),
+                name: "Defib (200J)".to_owned(),
+                category: "Defib".to_owned(),
+                username: "umich1".to_owned(),
+                value: "200J".to_owned(),
+                ..Default::default()
+            });
+        }
+
+        #[test]
+        fn period() {
+            let record = PointRecord::from(&ActionPlotPoint::Period(PeriodType::CPR, location(3), location(8)));
+
+            assert_eq!(record.name, "CPR");
+            assert_eq!((record.seconds, record.end_seconds), (Some(3), Some(8)));
+        }
+    }
+
+    mod write_points_csv {
+        use super::*;
+
+        #[test]
+        fn header_and_rows() {
+            let points = vec![ActionPlotPoint::Period(PeriodType::CPR, location(3), location(8))];
+            let mut output = vec![];
+
+            write_points_csv(&mut output, &points).unwrap();
+
+            let output = String::from_utf8(output).unwrap();
+            assert_eq!(output.lines().collect::<Vec<_>>(), vec![
+                "kind,seconds,timestamp,end_seconds,stage_number,stage_name,name,category,username,value,rule,severity,detail",
+                "Period,3,00:00:03,8,1,V-Tach 2D,CPR,,,,,,",
+            ]);
+        }
+    }
+}
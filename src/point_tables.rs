@@ -0,0 +1,423 @@
+use crate::batch::CohortDataset;
+use crate::plot_structures::{
+    Action, ActionPlotPoint, CsvRowTime, ErroneousAction, ErrorInfo, MissedAction, PeriodType, PlotLocation, ProtocolDeviation,
+    QuestionnaireItem, RuleEvaluation, SpeechEvent, StagePeriod, VitalSample, VitalValue,
+};
+use crate::point_csv::{period_type_name, rhythm_family_name, vital_sign_name};
+use crate::processor_error::ProcessorError;
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, RecordBatch, StringArray, TimestampSecondArray, UInt32Array};
+use arrow_schema::{Field, Schema};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+type Column = (Field, ArrayRef);
+
+/// One arrow table per kind of point, keyed by table name (`actions`, `errors`, `missed_actions`, `periods`, `stages`,
+/// `vitals`, `speech`, `questionnaire`, `rule_evaluations`, `protocol_deviations`). Kinds without points have no table.
+/// The location of a point is flattened into `seconds`, `timestamp`, `datetime`, `stage_number` and `stage_name`,
+/// the end of a period or stage has the same columns prefixed with `end_`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PointTables {
+    tables: BTreeMap<&'static str, RecordBatch>,
+}
+
+impl PointTables {
+    /// Tables of a single session.
+    pub fn from_points<'p, I>(points: I) -> Result<Self, ProcessorError>
+    where
+        I: IntoIterator<Item = &'p ActionPlotPoint>,
+    {
+        Self::build(points.into_iter().map(|point| (None, point)))
+    }
+
+    /// Tables of every session of a cohort, the first column of each table is `session_id`.
+    pub fn from_cohort(dataset: &CohortDataset) -> Result<Self, ProcessorError> {
+        Self::build(dataset.points.iter().map(|session_point| (Some(session_point.session_id.as_str()), &session_point.point)))
+    }
+
+    fn build<'p, I>(points: I) -> Result<Self, ProcessorError>
+    where
+        I: Iterator<Item = (Option<&'p str>, &'p ActionPlotPoint)>,
+    {
+        let mut grouped: BTreeMap<&'static str, (Vec<Option<&str>>, Vec<&ActionPlotPoint>)> = BTreeMap::new();
+        for (session_id, point) in points {
+            let (session_ids, points) = grouped.entry(table_name(point)).or_default();
+            session_ids.push(session_id);
+            points.push(point);
+        }
+
+        let mut tables = BTreeMap::new();
+        for (name, (session_ids, points)) in grouped {
+            let mut columns = vec![];
+            if session_ids.iter().all(Option::is_some) {
+                columns.push(strings("session_id", session_ids.into_iter().flatten()));
+            }
+            columns.extend(kind_columns(&points));
+            let (fields, arrays): (Vec<Field>, Vec<ArrayRef>) = columns.into_iter().unzip();
+            let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays).map_err(|e| ProcessorError::Io { message: e.to_string() })?;
+            tables.insert(name, batch);
+        }
+        Ok(Self { tables })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RecordBatch> {
+        self.tables.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &RecordBatch)> {
+        self.tables.iter().map(|(name, batch)| (*name, batch))
+    }
+
+    /// Writes every table to `<dir>/<table name>.parquet`, creating `dir` when needed, and returns the written paths.
+    pub fn write_parquet(&self, dir: &Path) -> Result<Vec<PathBuf>, ProcessorError> {
+        let io_error = |path: &Path, message: String| ProcessorError::Io { message: format!("{}: {}", path.display(), message) };
+        std::fs::create_dir_all(dir).map_err(|e| io_error(dir, e.to_string()))?;
+
+        let mut paths = vec![];
+        for (name, batch) in &self.tables {
+            let path = dir.join(format!("{}.parquet", name));
+            let file = File::create(&path).map_err(|e| io_error(&path, e.to_string()))?;
+            let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+            let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(properties)).map_err(|e| io_error(&path, e.to_string()))?;
+            writer.write(batch).map_err(|e| io_error(&path, e.to_string()))?;
+            writer.close().map_err(|e| io_error(&path, e.to_string()))?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+fn table_name(point: &ActionPlotPoint) -> &'static str {
+    match point {
+        ActionPlotPoint::Action(_) => "actions",
+        ActionPlotPoint::Error(_) => "errors",
+        ActionPlotPoint::MissedAction(_) => "missed_actions",
+        ActionPlotPoint::Period(..) => "periods",
+        ActionPlotPoint::Stage(_) => "stages",
+        ActionPlotPoint::Vital(_) => "vitals",
+        ActionPlotPoint::Speech(_) => "speech",
+        ActionPlotPoint::Questionnaire(_) => "questionnaire",
+        ActionPlotPoint::RuleEvaluation(_) => "rule_evaluations",
+        ActionPlotPoint::ProtocolDeviation(_) => "protocol_deviations",
+    }
+}
+
+macro_rules! variant_items {
+    ($points:expr, $variant:path) => {
+        $points.iter().filter_map(|point| match point {
+            $variant(item) => Some(item),
+            _ => None,
+        }).collect::<Vec<_>>()
+    };
+}
+
+/// Columns of points that all have the same kind.
+fn kind_columns(points: &[&ActionPlotPoint]) -> Vec<Column> {
+    match points[0] {
+        ActionPlotPoint::Action(_) => action_columns(&variant_items!(points, ActionPlotPoint::Action)),
+        ActionPlotPoint::Error(_) => error_columns(&variant_items!(points, ActionPlotPoint::Error)),
+        ActionPlotPoint::MissedAction(_) => missed_action_columns(&variant_items!(points, ActionPlotPoint::MissedAction)),
+        ActionPlotPoint::Period(..) => {
+            let periods: Vec<_> = points.iter().filter_map(|point| match point {
+                ActionPlotPoint::Period(period_type, start, end) => Some((period_type, start, end)),
+                _ => None,
+            }).collect();
+            period_columns(&periods)
+        }
+        ActionPlotPoint::Stage(_) => stage_columns(&variant_items!(points, ActionPlotPoint::Stage)),
+        ActionPlotPoint::Vital(_) => vital_columns(&variant_items!(points, ActionPlotPoint::Vital)),
+        ActionPlotPoint::Speech(_) => speech_columns(&variant_items!(points, ActionPlotPoint::Speech)),
+        ActionPlotPoint::Questionnaire(_) => questionnaire_columns(&variant_items!(points, ActionPlotPoint::Questionnaire)),
+        ActionPlotPoint::RuleEvaluation(_) => rule_evaluation_columns(&variant_items!(points, ActionPlotPoint::RuleEvaluation)),
+        ActionPlotPoint::ProtocolDeviation(_) => protocol_deviation_columns(&variant_items!(points, ActionPlotPoint::ProtocolDeviation)),
+    }
+}
+
+fn action_columns(actions: &[&Action]) -> Vec<Column> {
+    let mut columns = location_columns("", actions.iter().map(|action| &action.location));
+    columns.extend([
+        strings("name", actions.iter().map(|action| action.name.as_str())),
+        strings("action_category", actions.iter().map(|action| action.action_category.as_str())),
+        strings("shock_value", actions.iter().map(|action| action.shock_value.as_str())),
+        strings("username", actions.iter().map(|action| action.username.as_str())),
+    ]);
+    columns
+}
+
+fn error_columns(errors: &[&ErroneousAction]) -> Vec<Column> {
+    let mut columns = location_columns("", errors.iter().map(|error| &error.location));
+    columns.extend([
+        strings("name", errors.iter().map(|error| error.name.as_str())),
+        strings("action_category", errors.iter().map(|error| error.action_category.as_str())),
+        strings("shock_value", errors.iter().map(|error| error.shock_value.as_str())),
+        strings("username", errors.iter().map(|error| error.username.as_str())),
+    ]);
+    columns.extend(error_info_columns(errors.iter().map(|error| &error.error_info)));
+    columns
+}
+
+fn missed_action_columns(missed_actions: &[&MissedAction]) -> Vec<Column> {
+    let mut columns = location_columns("", missed_actions.iter().map(|missed| &missed.location));
+    columns.extend([
+        strings("name", missed_actions.iter().map(|missed| missed.name.as_str())),
+        optional_strings("username", missed_actions.iter().map(|missed| missed.username.clone())),
+    ]);
+    columns.extend(error_info_columns(missed_actions.iter().map(|missed| &missed.error_info)));
+    columns
+}
+
+fn error_info_columns<'p>(error_infos: impl Iterator<Item = &'p ErrorInfo> + Clone) -> Vec<Column> {
+    vec![
+        strings("action_rule", error_infos.clone().map(|error_info| error_info.action_rule.as_str())),
+        strings("severity", error_infos.clone().map(|error_info| error_info.severity.to_string())),
+        strings("expectation", error_infos.clone().map(|error_info| error_info.expectation.as_str())),
+        strings("violation", error_infos.clone().map(|error_info| error_info.violation.as_str())),
+        strings("advice", error_infos.map(|error_info| error_info.advice.as_str())),
+    ]
+}
+
+fn period_columns(periods: &[(&PeriodType, &PlotLocation, &PlotLocation)]) -> Vec<Column> {
+    let (period_types, rhythms): (Vec<_>, Vec<_>) = periods.iter().map(|(period_type, ..)| period_type_name(period_type)).unzip();
+    let mut columns = vec![strings("period_type", period_types.into_iter()), optional_strings("rhythm", rhythms.into_iter())];
+    columns.extend(location_columns("", periods.iter().map(|(_, start, _)| *start)));
+    columns.extend(location_columns("end_", periods.iter().map(|(_, _, end)| *end)));
+    columns
+}
+
+fn stage_columns(stages: &[&StagePeriod]) -> Vec<Column> {
+    let mut columns = vec![
+        numbers("stage_number", stages.iter().map(|stage_period| stage_period.stage.0)),
+        strings("stage_name", stages.iter().map(|stage_period| stage_period.stage.1.as_str())),
+    ];
+    columns.extend(time_columns("", stages.iter().map(|stage_period| &stage_period.start)));
+    columns.extend(time_columns("end_", stages.iter().map(|stage_period| &stage_period.end)));
+    columns.extend([
+        numbers("duration_seconds", stages.iter().map(|stage_period| stage_period.duration_seconds)),
+        optional_strings("rhythm_family", stages.iter().map(|stage_period| stage_period.rhythm_family.as_ref().map(|family| rhythm_family_name(family).to_owned()))),
+    ]);
+    columns
+}
+
+fn vital_columns(vitals: &[&VitalSample]) -> Vec<Column> {
+    let measured = |value: &VitalValue| match value {
+        VitalValue::Measured(value) => Some(*value),
+        VitalValue::NotMeasurable => None,
+    };
+    let mut columns = location_columns("", vitals.iter().map(|sample| &sample.location));
+    columns.extend([
+        strings("vital_sign", vitals.iter().map(|sample| vital_sign_name(&sample.vital_sign))),
+        measurements("old_value", vitals.iter().map(|sample| measured(&sample.old_value))),
+        measurements("new_value", vitals.iter().map(|sample| measured(&sample.new_value))),
+    ]);
+    columns
+}
+
+fn speech_columns(speech: &[&SpeechEvent]) -> Vec<Column> {
+    let mut columns = location_columns("", speech.iter().map(|event| &event.location));
+    columns.extend([
+        strings("speaker", speech.iter().map(|event| event.speaker.as_str())),
+        strings("transcript", speech.iter().map(|event| event.transcript.as_str())),
+        flags("no_match", speech.iter().map(|event| event.no_match)),
+    ]);
+    columns
+}
+
+fn questionnaire_columns(items: &[&QuestionnaireItem]) -> Vec<Column> {
+    vec![
+        strings("question", items.iter().map(|item| item.question.as_str())),
+        numbers("rating", items.iter().map(|item| item.rating)),
+        numbers("scale_min", items.iter().map(|item| item.scale.min)),
+        numbers("scale_max", items.iter().map(|item| item.scale.max)),
+    ]
+}
+
+fn rule_evaluation_columns(evaluations: &[&RuleEvaluation]) -> Vec<Column> {
+    let mut columns = location_columns("", evaluations.iter().map(|evaluation| &evaluation.location));
+    columns.extend([
+        strings("rule_id", evaluations.iter().map(|evaluation| evaluation.rule_id.as_str())),
+        strings("severity", evaluations.iter().map(|evaluation| evaluation.severity.to_string())),
+        strings("expectation", evaluations.iter().map(|evaluation| evaluation.expectation.as_str())),
+        strings("outcome", evaluations.iter().map(|evaluation| evaluation.outcome.as_str())),
+        flags("triggered", evaluations.iter().map(|evaluation| evaluation.triggered)),
+        strings("advice", evaluations.iter().map(|evaluation| evaluation.advice.as_str())),
+    ]);
+    columns
+}
+
+fn protocol_deviation_columns(deviations: &[&ProtocolDeviation]) -> Vec<Column> {
+    let mut columns = location_columns("", deviations.iter().map(|deviation| &deviation.location));
+    columns.extend([
+        strings("rule", deviations.iter().map(|deviation| deviation.rule.name())),
+        strings("action_name", deviations.iter().map(|deviation| deviation.action_name.as_str())),
+        strings("explanation", deviations.iter().map(|deviation| deviation.explanation.as_str())),
+    ]);
+    columns
+}
+
+fn location_columns<'p>(prefix: &str, locations: impl Iterator<Item = &'p PlotLocation> + Clone) -> Vec<Column> {
+    let mut columns = time_columns(prefix, locations.clone().map(|location| &location.timestamp));
+    columns.extend([
+        numbers(&format!("{}stage_number", prefix), locations.clone().map(|location| location.stage.0)),
+        strings(&format!("{}stage_name", prefix), locations.map(|location| location.stage.1.as_str())),
+    ]);
+    columns
+}
+
+fn time_columns<'p>(prefix: &str, times: impl Iterator<Item = &'p CsvRowTime> + Clone) -> Vec<Column> {
+    vec![
+        numbers(&format!("{}seconds", prefix), times.clone().map(|time| time.total_seconds)),
+        strings(&format!("{}timestamp", prefix), times.clone().map(|time| time.timestamp.as_str())),
+        datetimes(&format!("{}datetime", prefix), times.map(|time| time.datetime.timestamp())),
+    ]
+}
+
+fn column(name: &str, array: ArrayRef) -> Column {
+    (Field::new(name, array.data_type().clone(), array.null_count() > 0), array)
+}
+
+fn strings<S: AsRef<str>>(name: &str, values: impl Iterator<Item = S>) -> Column {
+    column(name, Arc::new(StringArray::from_iter_values(values)))
+}
+
+fn optional_strings(name: &str, values: impl Iterator<Item = Option<String>>) -> Column {
+    (Field::new(name, arrow_schema::DataType::Utf8, true), Arc::new(values.collect::<StringArray>()))
+}
+
+fn numbers(name: &str, values: impl Iterator<Item = u32>) -> Column {
+    column(name, Arc::new(UInt32Array::from_iter_values(values)))
+}
+
+/// Utc instants, the offset of the session is not kept.
+fn datetimes(name: &str, values: impl Iterator<Item = i64>) -> Column {
+    column(name, Arc::new(TimestampSecondArray::from_iter_values(values).with_timezone("UTC")))
+}
+
+fn measurements(name: &str, values: impl Iterator<Item = Option<f64>>) -> Column {
+    (Field::new(name, arrow_schema::DataType::Float64, true), Arc::new(values.collect::<Float64Array>()))
+}
+
+fn flags(name: &str, values: impl Iterator<Item = bool>) -> Column {
+    column(name, Arc::new(values.map(Some).collect::<BooleanArray>()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::SessionPoint;
+    use crate::plot_structures::Rhythm;
+    use crate::test_support::{location, time};
+    use arrow_array::Array;
+
+    fn points() -> Vec<ActionPlotPoint> {
+        vec![
+            ActionPlotPoint::Action(Action {
+                location: location(12),
+                name: "Defib (200J)".to_owned(),
+                action_category: "Defib".to_owned(),
+                shock_value: "200J".to_owned(),
+                username: "umich1".to_owned(),
+            }),
+            ActionPlotPoint::Period(PeriodType::HrWaveform(Rhythm::VTach), location(0), location(40)),
+            ActionPlotPoint::Period(PeriodType::CPR, location(20), location(40)),
+        ]
+    }
+
+    fn string_column<'b>(batch: &'b RecordBatch, name: &str) -> &'b StringArray {
+        batch.column_by_name(name).unwrap().as_any().downcast_ref::<StringArray>().unwrap()
+    }
+
+    mod from_points {
+        use super::*;
+
+        #[test]
+        fn table_per_kind() {
+            let tables = PointTables::from_points(&points()).unwrap();
+
+            assert_eq!(tables.iter().map(|(name, _)| name).collect::<Vec<_>>(), vec!["actions", "periods"]);
+            let actions = tables.get("actions").unwrap();
+            assert_eq!(actions.num_rows(), 1);
+            assert_eq!(
+                actions.schema().fields().iter().map(|field| field.name().as_str()).collect::<Vec<_>>(),
+                vec!["seconds", "timestamp", "datetime", "stage_number", "stage_name", "name", "action_category", "shock_value", "username"]
+            );
+            assert_eq!(string_column(actions, "timestamp").value(0), "00:00:12");
+            let datetime = actions.column_by_name("datetime").unwrap().as_any().downcast_ref::<TimestampSecondArray>().unwrap();
+            assert_eq!(datetime.value_as_datetime(0), Some(time(12).datetime.naive_utc()));
+            assert_eq!(datetime.timezone(), Some("UTC"));
+        }
+
+        #[test]
+        fn period_rhythm_and_end() {
+            let tables = PointTables::from_points(&points()).unwrap();
+
+            let periods = tables.get("periods").unwrap();
+            let rhythm = string_column(periods, "rhythm");
+            assert_eq!((rhythm.value(0), rhythm.is_null(1)), ("VTach", true));
+            assert_eq!(string_column(periods, "end_timestamp").value(1), "00:00:40");
+        }
+    }
+
+    mod protocol_deviation_columns {
+        use super::*;
+        use crate::plot_structures::ProtocolRule;
+
+        #[test]
+        fn rule_name() {
+            let deviation = ActionPlotPoint::ProtocolDeviation(ProtocolDeviation {
+                location: location(40),
+                rule: ProtocolRule::PulseCheckTooLong,
+                action_name: "Pulse Check".to_owned(),
+                explanation: String::new(),
+            });
+
+            let tables = PointTables::from_points([&deviation]).unwrap();
+
+            assert_eq!(string_column(tables.get("protocol_deviations").unwrap(), "rule").value(0), "PulseCheckTooLong");
+        }
+    }
+
+    mod from_cohort {
+        use super::*;
+
+        #[test]
+        fn session_id_column() {
+            let dataset = CohortDataset {
+                sessions: vec!["session-a".to_owned(), "session-b".to_owned()],
+                points: points().into_iter().zip(["session-a", "session-b", "session-b"])
+                    .map(|(point, session_id)| SessionPoint { session_id: session_id.to_owned(), point })
+                    .collect(),
+                ..Default::default()
+            };
+
+            let tables = PointTables::from_cohort(&dataset).unwrap();
+
+            let periods = tables.get("periods").unwrap();
+            assert_eq!(periods.schema().field(0).name(), "session_id");
+            assert_eq!(string_column(periods, "session_id").value(0), "session-b");
+        }
+    }
+
+    mod write_parquet {
+        use super::*;
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        #[test]
+        fn file_per_table() {
+            let dir = std::env::temp_dir().join(format!("point_tables_{}", std::process::id()));
+            let tables = PointTables::from_points(&points()).unwrap();
+
+            let paths = tables.write_parquet(&dir).unwrap();
+
+            assert_eq!(paths, vec![dir.join("actions.parquet"), dir.join("periods.parquet")]);
+            let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&paths[1]).unwrap()).unwrap().build().unwrap();
+            let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(&batches[0], tables.get("periods").unwrap());
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
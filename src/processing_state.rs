@@ -1,24 +1,63 @@
 use crate::action_csv_row::ActionCsvRow;
-use crate::plot_structures::PlotLocation;
+use crate::detection::is_stage_boundary;
+use crate::plot_structures::{CsvRowTime, PlotLocation, Rhythm, WaveformChannel};
+use crate::processor_config::ProcessorConfig;
+use crate::session_date::SessionDate;
 use std::cell::RefCell;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+
+#[derive(Debug, PartialEq)]
+pub enum CsvSection {
+    Timeline,
+    Questionnaire
+}
 
 pub struct CsvProcessingState {
-    pub max_rows_to_check: usize,
+    pub config: ProcessorConfig,
+    pub session_date: SessionDate,
+    pub section: CsvSection,
     pub recent_rows: VecDeque<ActionCsvRow>,
     pub stage_boundaries: Vec<PlotLocation>,
     pub cpr_points: Vec<(PlotLocation, PlotLocation)>,
     pub pending_error_marker: RefCell<Option<(usize, ActionCsvRow)>>,
+    pub waveform_segments: BTreeMap<WaveformChannel, (Rhythm, PlotLocation)>,
+    pub current_stage: Option<(u32, String)>,
+    pub last_timestamp: Option<CsvRowTime>,
 }
 
 impl CsvProcessingState {
-    pub fn new(max_rows_to_check: usize) -> Self {
+    pub fn new(config: &ProcessorConfig) -> Self {
+        let session_date = config.session_date_for(None);
         Self {
-            max_rows_to_check,
-            recent_rows: VecDeque::with_capacity(max_rows_to_check),
-            stage_boundaries: vec![PlotLocation::default()],
+            config: config.clone(),
+            session_date,
+            section: CsvSection::Timeline,
+            recent_rows: VecDeque::with_capacity(config.max_rows_to_check),
+            stage_boundaries: vec![PlotLocation {
+                timestamp: CsvRowTime::new(0, &session_date),
+                ..Default::default()
+            }],
             cpr_points: Vec::new(),
             pending_error_marker: RefCell::new(None),
+            waveform_segments: BTreeMap::new(),
+            current_stage: None,
+            last_timestamp: None,
         }
     }
-}
\ No newline at end of file
+
+    pub fn track_progress(&mut self, row: &ActionCsvRow) {
+        if row.timestamp.is_some() {
+            self.last_timestamp = row.timestamp.clone();
+        }
+        let Some(stage) = &row.parsed_stage else {
+            return;
+        };
+        if is_stage_boundary(row) {
+            // The boundary row closed the stage it names, the next stage gets its name from the first row naming it.
+            self.current_stage = Some((stage.0 + 1, String::new()));
+        } else if self.current_stage.as_ref().is_none_or(|current| stage.0 >= current.0) {
+            // Rule evaluations name the stage they evaluated, which has already been closed.
+            self.current_stage = Some(stage.clone());
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use crate::action_catalogue::ActionCatalogue;
+use crate::processor_error::ProcessorError;
+use crate::session_date::SessionDate;
+use crate::utils;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const DEFAULT_MAX_ROWS_TO_CHECK: usize = 5;
+const DEFAULT_CPR_START_MARKERS: [&str; 2] = ["begin cpr", "enter cpr"];
+const DEFAULT_CPR_END_MARKERS: [&str; 2] = ["stop cpr", "end cpr"];
+const DEFAULT_ERROR_MARKER_TIME_THRESHOLD: u32 = 2;
+
+/// How an error marker row finds the erroneous action it refers to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkerMatching {
+    /// Search the buffered actions before the marker first, then wait for an action after it.
+    #[default]
+    BackwardThenForward,
+    BackwardOnly,
+    ForwardOnly,
+}
+
+/// Settings of the processing pipeline, use `ProcessorConfig::builder()` to change the defaults.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessorConfig {
+    /// Number of actions held back so that an error marker logged after an action can still claim it, 0 emits them right away.
+    pub max_rows_to_check: usize,
+    /// Seconds an error marker waits for an erroneous action logged after it.
+    pub error_marker_time_threshold: u32,
+    /// Seconds an error marker looks back for an erroneous action logged before it.
+    pub look_back_window: u32,
+    pub marker_matching: MarkerMatching,
+    /// Lower case `SubAction Name` values that start and end a CPR period.
+    pub cpr_start_markers: Vec<String>,
+    pub cpr_end_markers: Vec<String>,
+    /// When not set, the date is inferred from the source name where there is one.
+    pub session_date: Option<SessionDate>,
+    pub catalogue: Arc<ActionCatalogue>,
+}
+
+impl Default for ProcessorConfig {
+    fn default() -> Self {
+        Self {
+            max_rows_to_check: DEFAULT_MAX_ROWS_TO_CHECK,
+            error_marker_time_threshold: DEFAULT_ERROR_MARKER_TIME_THRESHOLD,
+            look_back_window: DEFAULT_ERROR_MARKER_TIME_THRESHOLD,
+            marker_matching: MarkerMatching::default(),
+            cpr_start_markers: DEFAULT_CPR_START_MARKERS.map(str::to_owned).to_vec(),
+            cpr_end_markers: DEFAULT_CPR_END_MARKERS.map(str::to_owned).to_vec(),
+            session_date: None,
+            catalogue: ActionCatalogue::builtin(),
+        }
+    }
+}
+
+/// Settings that can be changed in a config file, the rest keep their defaults.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    max_rows_to_check: Option<usize>,
+    error_marker_time_threshold: Option<u32>,
+    look_back_window: Option<u32>,
+    marker_matching: Option<MarkerMatching>,
+    cpr_start_markers: Option<Vec<String>>,
+    cpr_end_markers: Option<Vec<String>>,
+    /// `YYYY-MM-DD` optionally followed by a UTC offset.
+    session_date: Option<String>,
+    /// Path of an action catalogue file.
+    catalogue: Option<PathBuf>,
+}
+
+impl ProcessorConfig {
+    pub fn builder() -> ProcessorConfigBuilder {
+        ProcessorConfigBuilder::default()
+    }
+
+    /// Reads a toml config, a relative catalogue path is resolved against the working directory.
+    pub fn from_toml_str(toml: &str) -> Result<Self, ProcessorError> {
+        Self::from_toml_in(toml, Path::new(""))
+    }
+
+    /// Loads a toml config file, a relative catalogue path is resolved against the directory of the file.
+    pub fn load(path: &Path) -> Result<Self, ProcessorError> {
+        let content = std::fs::read_to_string(path).map_err(|e| ProcessorError::Io { message: format!("{}: {}", path.display(), e) })?;
+        Self::from_toml_in(&content, path.parent().unwrap_or(Path::new(""))).map_err(|e| match e {
+            ProcessorError::InvalidConfig { message } => ProcessorError::InvalidConfig { message: format!("{}: {}", path.display(), message) },
+            e => e,
+        })
+    }
+
+    fn from_toml_in(toml: &str, base_dir: &Path) -> Result<Self, ProcessorError> {
+        let file: ConfigFile = toml::from_str(toml).map_err(|e| ProcessorError::InvalidConfig { message: e.to_string() })?;
+        let mut builder = Self::builder();
+        if let Some(max_rows_to_check) = file.max_rows_to_check {
+            builder = builder.max_rows_to_check(max_rows_to_check);
+        }
+        if let Some(seconds) = file.error_marker_time_threshold {
+            builder = builder.error_marker_time_threshold(seconds);
+        }
+        if let Some(seconds) = file.look_back_window {
+            builder = builder.look_back_window(seconds);
+        }
+        if let Some(marker_matching) = file.marker_matching {
+            builder = builder.marker_matching(marker_matching);
+        }
+        if let Some(markers) = file.cpr_start_markers {
+            builder = builder.cpr_start_markers(markers);
+        }
+        if let Some(markers) = file.cpr_end_markers {
+            builder = builder.cpr_end_markers(markers);
+        }
+        if let Some(session_date) = file.session_date {
+            builder = builder.session_date(session_date.parse().map_err(|message| ProcessorError::InvalidConfig { message })?);
+        }
+        if let Some(catalogue) = file.catalogue {
+            builder = builder.catalogue(Arc::new(ActionCatalogue::load(&base_dir.join(catalogue))?));
+        }
+        Ok(builder.build())
+    }
+
+    /// The configured session date, or the one in the name of `source`.
+    pub fn session_date_for(&self, source: Option<&str>) -> SessionDate {
+        self.session_date
+            .or_else(|| source.and_then(SessionDate::from_source_name))
+            .unwrap_or_default()
+    }
+
+    /// Same configuration with the session date fixed for `source`.
+    pub fn for_source(&self, source: &str) -> Self {
+        Self { session_date: Some(self.session_date_for(Some(source))), ..self.clone() }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ProcessorConfigBuilder {
+    config: ProcessorConfig,
+}
+
+impl ProcessorConfigBuilder {
+    pub fn max_rows_to_check(mut self, max_rows_to_check: usize) -> Self {
+        self.config.max_rows_to_check = max_rows_to_check;
+        self
+    }
+
+    pub fn error_marker_time_threshold(mut self, seconds: u32) -> Self {
+        self.config.error_marker_time_threshold = seconds;
+        self
+    }
+
+    pub fn look_back_window(mut self, seconds: u32) -> Self {
+        self.config.look_back_window = seconds;
+        self
+    }
+
+    pub fn marker_matching(mut self, marker_matching: MarkerMatching) -> Self {
+        self.config.marker_matching = marker_matching;
+        self
+    }
+
+    pub fn cpr_start_markers<I, S>(mut self, markers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.config.cpr_start_markers = normalize_markers(markers);
+        self
+    }
+
+    pub fn cpr_end_markers<I, S>(mut self, markers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.config.cpr_end_markers = normalize_markers(markers);
+        self
+    }
+
+    pub fn session_date(mut self, session_date: SessionDate) -> Self {
+        self.config.session_date = Some(session_date);
+        self
+    }
+
+    pub fn catalogue(mut self, catalogue: Arc<ActionCatalogue>) -> Self {
+        self.config.catalogue = catalogue;
+        self
+    }
+
+    pub fn build(self) -> ProcessorConfig {
+        self.config
+    }
+}
+
+fn normalize_markers<I, S>(markers: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    markers.into_iter().map(|marker| utils::normalize_whitespace(&marker.as_ref().to_lowercase())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    mod builder {
+        use super::super::*;
+
+        #[test]
+        fn defaults() {
+            let config = ProcessorConfig::builder().build();
+
+            assert_eq!(config, ProcessorConfig::default());
+            assert_eq!(config.max_rows_to_check, 5);
+            assert_eq!(config.error_marker_time_threshold, 2);
+            assert_eq!(config.cpr_start_markers, vec!["begin cpr", "enter cpr"]);
+        }
+
+        #[test]
+        fn markers_normalized() {
+            let config = ProcessorConfig::builder()
+                .cpr_start_markers(["Start  Compressions"])
+                .cpr_end_markers(vec!["PAUSE CPR".to_owned()])
+                .build();
+
+            assert_eq!(config.cpr_start_markers, vec!["start compressions"]);
+            assert_eq!(config.cpr_end_markers, vec!["pause cpr"]);
+        }
+    }
+
+    mod from_toml_str {
+        use super::super::*;
+        use chrono::NaiveDate;
+
+        #[test]
+        fn overrides_defaults() {
+            let config = ProcessorConfig::from_toml_str(r#"
+                max_rows_to_check = 10
+                marker_matching = "forward_only"
+                cpr_start_markers = ["Start Compressions"]
+                session_date = "2024-09-18"
+            "#).unwrap();
+
+            assert_eq!(config.max_rows_to_check, 10);
+            assert_eq!(config.marker_matching, MarkerMatching::ForwardOnly);
+            assert_eq!(config.cpr_start_markers, vec!["start compressions"]);
+            assert_eq!(config.cpr_end_markers, ProcessorConfig::default().cpr_end_markers);
+            assert_eq!(config.session_date, Some(SessionDate::new(NaiveDate::from_ymd_opt(2024, 9, 18).unwrap())));
+        }
+
+        #[test]
+        fn empty() {
+            assert_eq!(ProcessorConfig::from_toml_str("").unwrap(), ProcessorConfig::default());
+        }
+
+        #[test]
+        fn invalid() {
+            assert!(matches!(ProcessorConfig::from_toml_str("max_rows = 10"), Err(ProcessorError::InvalidConfig { .. })));
+            assert!(matches!(ProcessorConfig::from_toml_str("session_date = \"09/18/2024\""), Err(ProcessorError::InvalidConfig { .. })));
+        }
+    }
+
+    mod load {
+        use super::super::*;
+
+        #[test]
+        fn catalogue_relative_to_config() {
+            let dir = std::env::temp_dir().join(format!("processor_config_{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("scenario.toml"), "[[action]]\nname = \"Check Pulse\"\n").unwrap();
+            std::fs::write(dir.join("config.toml"), "catalogue = \"scenario.toml\"\n").unwrap();
+
+            let config = ProcessorConfig::load(&dir.join("config.toml")).unwrap();
+
+            assert_eq!(config.catalogue.entries().len(), 1);
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    mod session_date_for {
+        use super::super::*;
+        use chrono::NaiveDate;
+
+        #[test]
+        fn configured_date_wins() {
+            let session_date = SessionDate::new(NaiveDate::from_ymd_opt(2025, 1, 2).unwrap());
+            let config = ProcessorConfig::builder().session_date(session_date).build();
+
+            assert_eq!(config.session_date_for(Some("timeline-multiplayer-09182024.csv")), session_date);
+        }
+
+        #[test]
+        fn inferred_from_source() {
+            let config = ProcessorConfig::default();
+
+            assert_eq!(config.session_date_for(Some("timeline-multiplayer-09182024.csv")).date, NaiveDate::from_ymd_opt(2024, 9, 18).unwrap());
+            assert_eq!(config.session_date_for(None), SessionDate::default());
+        }
+    }
+}
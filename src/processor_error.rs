@@ -0,0 +1,144 @@
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+
+/// Errors reported by the processing pipeline. Row numbers are 1-based csv line numbers, the header being line 1.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind")]
+pub enum ProcessorError {
+    Io { message: String },
+    HttpStatus { url: String, status: u16 },
+    Request { url: String, message: String },
+    HeaderMismatch { expected: Vec<String>, actual: Vec<String> },
+    RowDeserialization { row_number: usize, record: Vec<String>, message: String },
+    TimeParse { row_number: usize, value: String },
+    InvalidVitalValue { row_number: usize, name: String, old_value: String, new_value: String },
+    InvalidQuestionnaireItem { row_number: usize, record: Vec<String>, message: String },
+    UnresolvedErrorMarker { row_number: usize, marker: String },
+    SourceTruncated { path: String },
+    SourceRotated { path: String },
+    InvalidCatalogue { message: String },
+    InvalidConfig { message: String },
+    Database { message: String },
+}
+
+impl ProcessorError {
+    pub fn row_number(&self) -> Option<usize> {
+        match self {
+            ProcessorError::HeaderMismatch { .. } => Some(1),
+            ProcessorError::RowDeserialization { row_number, .. }
+            | ProcessorError::TimeParse { row_number, .. }
+            | ProcessorError::InvalidVitalValue { row_number, .. }
+            | ProcessorError::InvalidQuestionnaireItem { row_number, .. }
+            | ProcessorError::UnresolvedErrorMarker { row_number, .. } => Some(*row_number),
+            ProcessorError::Io { .. }
+            | ProcessorError::HttpStatus { .. }
+            | ProcessorError::Request { .. }
+            | ProcessorError::SourceTruncated { .. }
+            | ProcessorError::SourceRotated { .. }
+            | ProcessorError::InvalidCatalogue { .. }
+            | ProcessorError::InvalidConfig { .. }
+            | ProcessorError::Database { .. } => None,
+        }
+    }
+
+    pub(crate) fn from_csv_error(row_number: usize, error: csv::Error) -> Self {
+        if let csv::ErrorKind::Io(e) = error.kind() {
+            return ProcessorError::Io { message: e.to_string() };
+        }
+        ProcessorError::RowDeserialization {
+            row_number: error.position().map(|p| p.line() as usize).unwrap_or(row_number),
+            record: vec![],
+            message: error.to_string(),
+        }
+    }
+}
+
+impl Display for ProcessorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessorError::Io { message } => write!(f, "IO error: {}", message),
+            ProcessorError::HttpStatus { url, status } => write!(f, "HTTP status error: {} for {}", status, url),
+            ProcessorError::Request { url, message } => write!(f, "Request error for {}: {}", url, message),
+            ProcessorError::HeaderMismatch { expected, actual } => write!(
+                f,
+                "Line {:?}: expected {:?} as the header row of csv but got {:?}",
+                1, expected, actual
+            ),
+            ProcessorError::RowDeserialization { row_number, record, message } => {
+                write!(f, "Could not deserialize row {} {:?}: {}", row_number, record, message)
+            }
+            ProcessorError::TimeParse { row_number, value } => {
+                write!(f, "Could not parse time {:?} at row {}", value, row_number)
+            }
+            ProcessorError::InvalidVitalValue { row_number, name, old_value, new_value } => write!(
+                f,
+                "Could not parse {} values at row {}: {:?} -> {:?}",
+                name, row_number, old_value, new_value
+            ),
+            ProcessorError::InvalidQuestionnaireItem { row_number, message, .. } => {
+                write!(f, "{} at row {}", message, row_number)
+            }
+            ProcessorError::UnresolvedErrorMarker { row_number, marker } => write!(
+                f,
+                "Error marker '{}' at row {} could not be matched to an erroneous action before the end of the data",
+                marker, row_number
+            ),
+            ProcessorError::SourceTruncated { path } => {
+                write!(f, "{} was truncated, processing restarted from the beginning of the file", path)
+            }
+            ProcessorError::SourceRotated { path } => {
+                write!(f, "{} was replaced by a new file, processing restarted with the new file", path)
+            }
+            ProcessorError::InvalidCatalogue { message } => write!(f, "Invalid action catalogue: {}", message),
+            ProcessorError::InvalidConfig { message } => write!(f, "Invalid processor config: {}", message),
+            ProcessorError::Database { message } => write!(f, "Database error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ProcessorError {}
+
+#[cfg(test)]
+mod tests {
+    mod from_csv_error {
+        use super::super::*;
+
+        #[test]
+        fn deserialization_error_uses_record_line() {
+            let mut reader = csv::ReaderBuilder::new().has_headers(true).from_reader("a,b\n1,2\n3\n".as_bytes());
+            let error = reader.records().nth(1).unwrap().unwrap_err();
+
+            let result = ProcessorError::from_csv_error(10, error);
+
+            assert!(matches!(result, ProcessorError::RowDeserialization { row_number: 3, .. }));
+        }
+    }
+
+    mod display {
+        use super::super::*;
+
+        #[test]
+        fn header_mismatch() {
+            let error = ProcessorError::HeaderMismatch {
+                expected: vec!["Time".to_owned()],
+                actual: vec!["Name".to_owned()],
+            };
+
+            assert_eq!(error.to_string(), "Line 1: expected [\"Time\"] as the header row of csv but got [\"Name\"]");
+        }
+    }
+
+    mod serialize {
+        use super::super::*;
+
+        #[test]
+        fn tagged_with_kind() {
+            let error = ProcessorError::TimeParse { row_number: 4, value: "1:x:3".to_owned() };
+
+            let json = serde_json::to_value(&error).unwrap();
+
+            assert_eq!(json, serde_json::json!({"kind": "TimeParse", "row_number": 4, "value": "1:x:3"}));
+            assert_eq!(error.row_number(), Some(4));
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use serde::Deserialize;
+
+/*
+ * Rule evaluation rows do not follow the csv header, their columns are:
+ * time stamp, rule id, severity, expectation, outcome, trigger state, username (or NA), stage, advice
+ */
+#[derive(Default, Debug, Deserialize, Clone, PartialEq)]
+pub struct RuleCsvRow {
+    pub timestamp: String,
+    pub rule_id: String,
+    pub severity: String,
+    pub expectation: String,
+    pub outcome: String,
+    pub trigger_state: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub stage: String,
+    #[serde(default)]
+    pub advice: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RuleCsvRow;
+    use csv::StringRecord;
+
+    #[test]
+    fn deserialize_rule_row() {
+        let record = StringRecord::from(vec![
+            "0:1:31", "EKG", "Error", "Action-Should-Not-Be-Performed", "Action-Was-Performed", "Error-Triggered",
+            "umich3", "(1)V-Tach 2D(action)", "Don't interrupt CPR for an EKG.", "", "", "", "", "", "",
+        ]);
+
+        let rule_row: RuleCsvRow = record.deserialize(None).unwrap();
+
+        assert_eq!(rule_row, RuleCsvRow {
+            timestamp: "0:1:31".to_owned(),
+            rule_id: "EKG".to_owned(),
+            severity: "Error".to_owned(),
+            expectation: "Action-Should-Not-Be-Performed".to_owned(),
+            outcome: "Action-Was-Performed".to_owned(),
+            trigger_state: "Error-Triggered".to_owned(),
+            username: "umich3".to_owned(),
+            stage: "(1)V-Tach 2D(action)".to_owned(),
+            advice: "Don't interrupt CPR for an EKG.".to_owned(),
+        });
+    }
+
+    #[test]
+    fn deserialize_rule_row_without_advice() {
+        let record = StringRecord::from(vec![
+            "0:1:55", "pulse_check", "Error", "Action-Should-Be-Performed", "Action-Was-Performed", "Error-Did-Not-Trigger",
+            "NA", "(1)V-Tach 2D(action)",
+        ]);
+
+        let rule_row: RuleCsvRow = record.deserialize(None).unwrap();
+
+        assert_eq!(rule_row.username, "NA");
+        assert_eq!(rule_row.advice, "");
+    }
+}
@@ -0,0 +1,148 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveTime};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::str::FromStr;
+
+lazy_static! {
+    static ref SOURCE_DATE_REGEX: Regex = Regex::new(r"(?:^|\D)(\d{2})(\d{2})(\d{4})(?:\D|$)").unwrap();
+}
+
+/// Date the `Hr:Min:Sec` offsets of a timeline are counted from. Without a time zone the times are UTC.
+/// The default is 1970-01-01 so that logs without a known date still give reproducible datetimes.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SessionDate {
+    pub date: NaiveDate,
+    pub time_zone: Option<FixedOffset>
+}
+
+impl SessionDate {
+    pub fn new(date: NaiveDate) -> Self {
+        Self { date, time_zone: None }
+    }
+
+    pub fn with_time_zone(self, time_zone: FixedOffset) -> Self {
+        Self { time_zone: Some(time_zone), ..self }
+    }
+
+    /// Infers the date from a `MMDDYYYY` group in the file name of a path or url,
+    /// e.g. `timeline-multiplayer-09182024.csv` was recorded on 2024-09-18.
+    pub fn from_source_name(source: &str) -> Option<Self> {
+        let path = source.split(['?', '#']).next().unwrap_or_default();
+        let file_name = path.rsplit(['/', '\\']).next().unwrap_or_default();
+        SOURCE_DATE_REGEX.captures_iter(file_name).find_map(|captures| {
+            let month = captures[1].parse().ok()?;
+            let day = captures[2].parse().ok()?;
+            let year = captures[3].parse().ok()?;
+            NaiveDate::from_ymd_opt(year, month, day).map(Self::new)
+        })
+    }
+
+    pub fn datetime(&self, total_seconds: u32) -> DateTime<FixedOffset> {
+        let time_zone = self.time_zone.unwrap_or(FixedOffset::east_opt(0).unwrap());
+        let local = self.date.and_time(NaiveTime::MIN) + Duration::seconds(total_seconds.into());
+        local.and_local_timezone(time_zone).unwrap()
+    }
+}
+
+/// Parses `YYYY-MM-DD` optionally followed by a UTC offset, e.g. `2024-09-18 -04:00`.
+impl FromStr for SessionDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (date, time_zone) = (s.get(..10).unwrap_or(s), s.get(10..).unwrap_or_default());
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| format!("Invalid session date '{}': {}", s, e))?;
+        let session_date = Self::new(date);
+        match time_zone.trim() {
+            "" => Ok(session_date),
+            time_zone => FixedOffset::from_str(time_zone)
+                .map(|time_zone| session_date.with_time_zone(time_zone))
+                .map_err(|e| format!("Invalid time zone '{}': {}", time_zone, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod from_source_name {
+        use super::super::*;
+
+        #[test]
+        fn file_name() {
+            let session_date = SessionDate::from_source_name("logs/timeline-multiplayer-09182024.csv").unwrap();
+
+            assert_eq!(session_date.date, NaiveDate::from_ymd_opt(2024, 9, 18).unwrap());
+            assert_eq!(session_date.time_zone, None);
+        }
+
+        #[test]
+        fn url_query_ignored() {
+            let url = "https://dl.dropboxusercontent.com/scl/fi/6os941r9qnk19nkd22415/timeline-multiplayer-09182024.csv?rlkey=4lpfpmkf62fnua597t7bh3p17&st=12345678&dl=0";
+
+            let session_date = SessionDate::from_source_name(url).unwrap();
+
+            assert_eq!(session_date.date, NaiveDate::from_ymd_opt(2024, 9, 18).unwrap());
+        }
+
+        #[test]
+        fn no_date() {
+            assert_eq!(SessionDate::from_source_name("tests/data/sample.csv"), None);
+            assert_eq!(SessionDate::from_source_name("timeline-123456789.csv"), None);
+        }
+
+        #[test]
+        fn invalid_date() {
+            assert_eq!(SessionDate::from_source_name("timeline-13182024.csv"), None);
+        }
+    }
+
+    mod datetime {
+        use super::super::*;
+
+        #[test]
+        fn offset_from_midnight() {
+            let session_date = SessionDate::new(NaiveDate::from_ymd_opt(2024, 9, 18).unwrap());
+
+            assert_eq!(session_date.datetime(3723).to_rfc3339(), "2024-09-18T01:02:03+00:00");
+        }
+
+        #[test]
+        fn past_midnight() {
+            let session_date = SessionDate::new(NaiveDate::from_ymd_opt(2024, 9, 18).unwrap());
+
+            assert_eq!(session_date.datetime(25 * 3600).to_rfc3339(), "2024-09-19T01:00:00+00:00");
+        }
+
+        #[test]
+        fn time_zone() {
+            let session_date = SessionDate::new(NaiveDate::from_ymd_opt(2024, 9, 18).unwrap())
+                .with_time_zone(FixedOffset::west_opt(4 * 3600).unwrap());
+
+            assert_eq!(session_date.datetime(60).to_rfc3339(), "2024-09-18T00:01:00-04:00");
+        }
+    }
+
+    mod from_str {
+        use super::super::*;
+
+        #[test]
+        fn date_only() {
+            assert_eq!("2024-09-18".parse(), Ok(SessionDate::new(NaiveDate::from_ymd_opt(2024, 9, 18).unwrap())));
+        }
+
+        #[test]
+        fn with_offset() {
+            let expected = SessionDate::new(NaiveDate::from_ymd_opt(2024, 9, 18).unwrap());
+
+            assert_eq!("2024-09-18 -04:00".parse(), Ok(expected.with_time_zone(FixedOffset::west_opt(4 * 3600).unwrap())));
+            assert_eq!("2024-09-18+02:00".parse(), Ok(expected.with_time_zone(FixedOffset::east_opt(2 * 3600).unwrap())));
+            assert_eq!("2024-09-18-04:00".parse(), Ok(expected.with_time_zone(FixedOffset::west_opt(4 * 3600).unwrap())));
+        }
+
+        #[test]
+        fn invalid() {
+            assert!("09/18/2024".parse::<SessionDate>().is_err());
+            assert!("2024-09-18 EST".parse::<SessionDate>().is_err());
+        }
+    }
+}
@@ -0,0 +1,448 @@
+use crate::participants::summarize_participants;
+use crate::plot_structures::{ActionPlotPoint, PlotLocation, VitalValue};
+use crate::point_csv::{period_type_name, rhythm_family_name, vital_sign_name};
+use crate::processor_error::ProcessorError;
+use crate::session_date::SessionDate;
+use rusqlite::{params, Connection, Transaction};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Every event table has the position of the point in the session (`event_index`), its time and its stage.
+/// `stage_number` is NULL outside of a stage and `username` is NULL when the point has no participant.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sessions (
+    session_id TEXT PRIMARY KEY,
+    source TEXT NOT NULL,
+    session_date TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS participants (
+    session_id TEXT NOT NULL REFERENCES sessions(session_id) ON DELETE CASCADE,
+    username TEXT NOT NULL,
+    actions INTEGER NOT NULL,
+    errors INTEGER NOT NULL,
+    missed_actions INTEGER NOT NULL,
+    speech_utterances INTEGER NOT NULL,
+    unrecognized_utterances INTEGER NOT NULL,
+    PRIMARY KEY (session_id, username)
+);
+CREATE TABLE IF NOT EXISTS stages (
+    session_id TEXT NOT NULL REFERENCES sessions(session_id) ON DELETE CASCADE,
+    stage_number INTEGER NOT NULL,
+    stage_name TEXT NOT NULL,
+    start_seconds INTEGER NOT NULL,
+    end_seconds INTEGER NOT NULL,
+    duration_seconds INTEGER NOT NULL,
+    rhythm_family TEXT,
+    PRIMARY KEY (session_id, stage_number)
+);
+CREATE TABLE IF NOT EXISTS actions (
+    session_id TEXT NOT NULL REFERENCES sessions(session_id) ON DELETE CASCADE,
+    event_index INTEGER NOT NULL,
+    seconds INTEGER NOT NULL,
+    datetime TEXT NOT NULL,
+    stage_number INTEGER,
+    username TEXT,
+    name TEXT NOT NULL,
+    category TEXT NOT NULL,
+    shock_value TEXT,
+    PRIMARY KEY (session_id, event_index),
+    FOREIGN KEY (session_id, stage_number) REFERENCES stages(session_id, stage_number),
+    FOREIGN KEY (session_id, username) REFERENCES participants(session_id, username)
+);
+CREATE TABLE IF NOT EXISTS errors (
+    session_id TEXT NOT NULL REFERENCES sessions(session_id) ON DELETE CASCADE,
+    event_index INTEGER NOT NULL,
+    seconds INTEGER NOT NULL,
+    datetime TEXT NOT NULL,
+    stage_number INTEGER,
+    username TEXT,
+    name TEXT NOT NULL,
+    category TEXT NOT NULL,
+    shock_value TEXT,
+    rule TEXT NOT NULL,
+    severity TEXT NOT NULL,
+    expectation TEXT NOT NULL,
+    violation TEXT NOT NULL,
+    advice TEXT NOT NULL,
+    PRIMARY KEY (session_id, event_index),
+    FOREIGN KEY (session_id, stage_number) REFERENCES stages(session_id, stage_number),
+    FOREIGN KEY (session_id, username) REFERENCES participants(session_id, username)
+);
+CREATE TABLE IF NOT EXISTS missed_actions (
+    session_id TEXT NOT NULL REFERENCES sessions(session_id) ON DELETE CASCADE,
+    event_index INTEGER NOT NULL,
+    seconds INTEGER NOT NULL,
+    datetime TEXT NOT NULL,
+    stage_number INTEGER,
+    username TEXT,
+    name TEXT NOT NULL,
+    rule TEXT NOT NULL,
+    severity TEXT NOT NULL,
+    expectation TEXT NOT NULL,
+    violation TEXT NOT NULL,
+    advice TEXT NOT NULL,
+    PRIMARY KEY (session_id, event_index),
+    FOREIGN KEY (session_id, stage_number) REFERENCES stages(session_id, stage_number),
+    FOREIGN KEY (session_id, username) REFERENCES participants(session_id, username)
+);
+CREATE TABLE IF NOT EXISTS periods (
+    session_id TEXT NOT NULL REFERENCES sessions(session_id) ON DELETE CASCADE,
+    event_index INTEGER NOT NULL,
+    seconds INTEGER NOT NULL,
+    datetime TEXT NOT NULL,
+    stage_number INTEGER,
+    end_seconds INTEGER NOT NULL,
+    end_stage_number INTEGER,
+    period_type TEXT NOT NULL,
+    rhythm TEXT,
+    PRIMARY KEY (session_id, event_index),
+    FOREIGN KEY (session_id, stage_number) REFERENCES stages(session_id, stage_number),
+    FOREIGN KEY (session_id, end_stage_number) REFERENCES stages(session_id, stage_number)
+);
+CREATE TABLE IF NOT EXISTS vitals (
+    session_id TEXT NOT NULL REFERENCES sessions(session_id) ON DELETE CASCADE,
+    event_index INTEGER NOT NULL,
+    seconds INTEGER NOT NULL,
+    datetime TEXT NOT NULL,
+    stage_number INTEGER,
+    vital_sign TEXT NOT NULL,
+    old_value REAL,
+    new_value REAL,
+    PRIMARY KEY (session_id, event_index),
+    FOREIGN KEY (session_id, stage_number) REFERENCES stages(session_id, stage_number)
+);
+CREATE TABLE IF NOT EXISTS speech (
+    session_id TEXT NOT NULL REFERENCES sessions(session_id) ON DELETE CASCADE,
+    event_index INTEGER NOT NULL,
+    seconds INTEGER NOT NULL,
+    datetime TEXT NOT NULL,
+    stage_number INTEGER,
+    username TEXT,
+    transcript TEXT NOT NULL,
+    no_match INTEGER NOT NULL,
+    PRIMARY KEY (session_id, event_index),
+    FOREIGN KEY (session_id, stage_number) REFERENCES stages(session_id, stage_number),
+    FOREIGN KEY (session_id, username) REFERENCES participants(session_id, username)
+);
+CREATE VIEW IF NOT EXISTS events AS
+    SELECT session_id, event_index, 'Action' AS kind, seconds, datetime, stage_number, username FROM actions
+    UNION ALL SELECT session_id, event_index, 'Error', seconds, datetime, stage_number, username FROM errors
+    UNION ALL SELECT session_id, event_index, 'MissedAction', seconds, datetime, stage_number, username FROM missed_actions
+    UNION ALL SELECT session_id, event_index, 'Period', seconds, datetime, stage_number, NULL FROM periods
+    UNION ALL SELECT session_id, event_index, 'Vital', seconds, datetime, stage_number, NULL FROM vitals
+    UNION ALL SELECT session_id, event_index, 'Speech', seconds, datetime, stage_number, username FROM speech;
+";
+
+/// Child tables of a session, in the order they are cleared before a session is exported again.
+const SESSION_TABLES: [&str; 8] = ["actions", "errors", "missed_actions", "periods", "vitals", "speech", "stages", "participants"];
+
+/// Writes processed sessions into a normalized SQLite database: `sessions`, `participants`, `stages` and one table
+/// per kind of event (`actions`, `errors`, `missed_actions`, `periods`, `vitals` and `speech`) that reference them.
+/// The `events` view lists the events of all kinds with their common columns.
+pub struct SqliteExporter {
+    connection: Connection,
+}
+
+impl SqliteExporter {
+    /// Opens or creates the database file and its tables.
+    pub fn open(path: &Path) -> Result<Self, ProcessorError> {
+        Self::with_connection(Connection::open(path).map_err(|e| ProcessorError::Database { message: format!("{}: {}", path.display(), e) })?)
+    }
+
+    pub fn in_memory() -> Result<Self, ProcessorError> {
+        Self::with_connection(Connection::open_in_memory().map_err(database_error)?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, ProcessorError> {
+        connection.execute_batch("PRAGMA foreign_keys = ON;").map_err(database_error)?;
+        connection.execute_batch(SCHEMA).map_err(database_error)?;
+        Ok(Self { connection })
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Replaces everything stored for `session_id` with `points`, so exporting a session again does not duplicate it.
+    /// Questionnaire answers, rule evaluations and protocol deviations are not exported.
+    pub fn export_session(&mut self, session_id: &str, source: &str, session_date: &SessionDate, points: &[ActionPlotPoint]) -> Result<(), ProcessorError> {
+        let transaction = self.connection.transaction().map_err(database_error)?;
+        transaction.execute(
+            "INSERT INTO sessions (session_id, source, session_date) VALUES (?1, ?2, ?3)
+             ON CONFLICT (session_id) DO UPDATE SET source = excluded.source, session_date = excluded.session_date",
+            params![session_id, source, session_date_text(session_date)],
+        ).map_err(database_error)?;
+        for table in SESSION_TABLES {
+            transaction.execute(&format!("DELETE FROM {} WHERE session_id = ?1", table), params![session_id]).map_err(database_error)?;
+        }
+        insert_participants(&transaction, session_id, points)?;
+        let stages = insert_stages(&transaction, session_id, points)?;
+        let events = SessionEvents { transaction: &transaction, session_id, session_date, stages: &stages };
+        for (event_index, point) in points.iter().enumerate() {
+            events.insert(event_index, point)?;
+        }
+        transaction.commit().map_err(database_error)
+    }
+}
+
+fn insert_participants(transaction: &Transaction, session_id: &str, points: &[ActionPlotPoint]) -> Result<(), ProcessorError> {
+    let mut statement = transaction.prepare(
+        "INSERT INTO participants (session_id, username, actions, errors, missed_actions, speech_utterances, unrecognized_utterances)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    ).map_err(database_error)?;
+    for participant in summarize_participants(points) {
+        statement.execute(params![
+            session_id,
+            participant.username,
+            participant.actions_by_category.values().sum::<u32>(),
+            participant.errors_by_severity.values().sum::<u32>(),
+            participant.missed_actions,
+            participant.speech_utterances,
+            participant.unrecognized_utterances,
+        ]).map_err(database_error)?;
+    }
+    Ok(())
+}
+
+/// Returns the numbers of the inserted stages.
+fn insert_stages(transaction: &Transaction, session_id: &str, points: &[ActionPlotPoint]) -> Result<BTreeSet<u32>, ProcessorError> {
+    let mut statement = transaction.prepare(
+        "INSERT OR REPLACE INTO stages (session_id, stage_number, stage_name, start_seconds, end_seconds, duration_seconds, rhythm_family)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    ).map_err(database_error)?;
+    let mut stages = BTreeSet::new();
+    for point in points {
+        if let ActionPlotPoint::Stage(stage_period) = point {
+            statement.execute(params![
+                session_id,
+                stage_period.stage.0,
+                stage_period.stage.1,
+                stage_period.start.total_seconds,
+                stage_period.end.total_seconds,
+                stage_period.duration_seconds,
+                stage_period.rhythm_family.as_ref().map(rhythm_family_name),
+            ]).map_err(database_error)?;
+            stages.insert(stage_period.stage.0);
+        }
+    }
+    Ok(stages)
+}
+
+struct SessionEvents<'t> {
+    transaction: &'t Transaction<'t>,
+    session_id: &'t str,
+    session_date: &'t SessionDate,
+    stages: &'t BTreeSet<u32>,
+}
+
+impl SessionEvents<'_> {
+    fn insert(&self, event_index: usize, point: &ActionPlotPoint) -> Result<(), ProcessorError> {
+        match point {
+            ActionPlotPoint::Action(action) => self.execute(
+                "INSERT INTO actions (session_id, event_index, seconds, datetime, stage_number, username, name, category, shock_value)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    self.session_id, event_index, action.location.timestamp.total_seconds, self.datetime(&action.location), self.stage_number(&action.location),
+                    non_empty(&action.username), action.name, action.action_category, non_empty(&action.shock_value),
+                ],
+            ),
+            ActionPlotPoint::Error(error) => self.execute(
+                "INSERT INTO errors (session_id, event_index, seconds, datetime, stage_number, username, name, category, shock_value,
+                                     rule, severity, expectation, violation, advice)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                params![
+                    self.session_id, event_index, error.location.timestamp.total_seconds, self.datetime(&error.location), self.stage_number(&error.location),
+                    non_empty(&error.username), error.name, error.action_category, non_empty(&error.shock_value),
+                    error.error_info.action_rule, error.error_info.severity.to_string(), error.error_info.expectation,
+                    error.error_info.violation, error.error_info.advice,
+                ],
+            ),
+            ActionPlotPoint::MissedAction(missed) => self.execute(
+                "INSERT INTO missed_actions (session_id, event_index, seconds, datetime, stage_number, username, name,
+                                             rule, severity, expectation, violation, advice)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    self.session_id, event_index, missed.location.timestamp.total_seconds, self.datetime(&missed.location), self.stage_number(&missed.location),
+                    missed.username.as_deref().and_then(non_empty), missed.name,
+                    missed.error_info.action_rule, missed.error_info.severity.to_string(), missed.error_info.expectation,
+                    missed.error_info.violation, missed.error_info.advice,
+                ],
+            ),
+            ActionPlotPoint::Period(period_type, start, end) => {
+                let (period_type, rhythm) = period_type_name(period_type);
+                self.execute(
+                    "INSERT INTO periods (session_id, event_index, seconds, datetime, stage_number, end_seconds, end_stage_number, period_type, rhythm)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        self.session_id, event_index, start.timestamp.total_seconds, self.datetime(start), self.stage_number(start),
+                        end.timestamp.total_seconds, self.stage_number(end), period_type, rhythm,
+                    ],
+                )
+            }
+            ActionPlotPoint::Vital(sample) => self.execute(
+                "INSERT INTO vitals (session_id, event_index, seconds, datetime, stage_number, vital_sign, old_value, new_value)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    self.session_id, event_index, sample.location.timestamp.total_seconds, self.datetime(&sample.location), self.stage_number(&sample.location),
+                    vital_sign_name(&sample.vital_sign), measured(&sample.old_value), measured(&sample.new_value),
+                ],
+            ),
+            ActionPlotPoint::Speech(speech) => self.execute(
+                "INSERT INTO speech (session_id, event_index, seconds, datetime, stage_number, username, transcript, no_match)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    self.session_id, event_index, speech.location.timestamp.total_seconds, self.datetime(&speech.location), self.stage_number(&speech.location),
+                    non_empty(&speech.speaker), speech.transcript, speech.no_match,
+                ],
+            ),
+            ActionPlotPoint::Stage(_)
+            | ActionPlotPoint::Questionnaire(_)
+            | ActionPlotPoint::RuleEvaluation(_)
+            | ActionPlotPoint::ProtocolDeviation(_) => Ok(()),
+        }
+    }
+
+    fn execute(&self, sql: &str, params: impl rusqlite::Params) -> Result<(), ProcessorError> {
+        self.transaction.prepare_cached(sql).and_then(|mut statement| statement.execute(params)).map(|_| ()).map_err(database_error)
+    }
+
+    fn datetime(&self, location: &PlotLocation) -> String {
+        self.session_date.datetime(location.timestamp.total_seconds).to_rfc3339()
+    }
+
+    /// Points before the first stage boundary of a log belong to no exported stage.
+    fn stage_number(&self, location: &PlotLocation) -> Option<u32> {
+        Some(location.stage.0).filter(|stage_number| self.stages.contains(stage_number))
+    }
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    Some(value).filter(|value| !value.is_empty())
+}
+
+fn measured(value: &VitalValue) -> Option<f64> {
+    match value {
+        VitalValue::Measured(value) => Some(*value),
+        VitalValue::NotMeasurable => None,
+    }
+}
+
+/// Same format as the `session_date` config setting, e.g. `2024-09-18 -04:00`.
+fn session_date_text(session_date: &SessionDate) -> String {
+    match session_date.time_zone {
+        Some(time_zone) => format!("{} {}", session_date.date, time_zone),
+        None => session_date.date.to_string(),
+    }
+}
+
+fn database_error(e: rusqlite::Error) -> ProcessorError {
+    ProcessorError::Database { message: e.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    mod export_session {
+        use super::super::*;
+        use crate::plot_structures::{Action, PeriodType, Rhythm, StagePeriod, VitalSample, VitalSign};
+        use crate::test_support::{location, time};
+
+        fn points() -> Vec<ActionPlotPoint> {
+            vec![
+                ActionPlotPoint::Action(Action {
+                    location: location(12),
+                    name: "Defib (200J)".to_owned(),
+                    action_category: "Defib".to_owned(),
+                    shock_value: "200J".to_owned(),
+                    username: "umich1".to_owned(),
+                }),
+                ActionPlotPoint::Period(PeriodType::HrWaveform(Rhythm::VTach), location(0), location(60)),
+                ActionPlotPoint::Vital(VitalSample {
+                    location: location(30),
+                    vital_sign: VitalSign::HeartRate,
+                    old_value: VitalValue::NotMeasurable,
+                    new_value: VitalValue::Measured(150.0),
+                }),
+                ActionPlotPoint::Stage(StagePeriod::new(
+                    (1, "V-Tach 2D".to_owned()),
+                    time(0),
+                    time(60),
+                )),
+            ]
+        }
+
+        fn count(exporter: &SqliteExporter, table: &str) -> i64 {
+            exporter.connection().query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0)).unwrap()
+        }
+
+        #[test]
+        fn tables_filled() {
+            let mut exporter = SqliteExporter::in_memory().unwrap();
+
+            exporter.export_session("session-a", "session-a.csv", &SessionDate::default(), &points()).unwrap();
+
+            assert_eq!(count(&exporter, "sessions"), 1);
+            assert_eq!(count(&exporter, "participants"), 1);
+            assert_eq!(count(&exporter, "stages"), 1);
+            assert_eq!(count(&exporter, "events"), 3);
+            let (name, shock_value, datetime, stage_number, username): (String, String, String, u32, String) = exporter.connection()
+                .query_row("SELECT name, shock_value, datetime, stage_number, username FROM actions", [], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+                })
+                .unwrap();
+            assert_eq!((name.as_str(), shock_value.as_str()), ("Defib (200J)", "200J"));
+            assert_eq!(datetime, "1970-01-01T00:00:12+00:00");
+            assert_eq!((stage_number, username.as_str()), (1, "umich1"));
+        }
+
+        #[test]
+        fn typed_columns() {
+            let mut exporter = SqliteExporter::in_memory().unwrap();
+
+            exporter.export_session("session-a", "session-a.csv", &SessionDate::default(), &points()).unwrap();
+
+            let period: (String, Option<String>, u32) = exporter.connection()
+                .query_row("SELECT period_type, rhythm, end_seconds FROM periods", [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .unwrap();
+            assert_eq!(period, ("HrWaveform".to_owned(), Some("VTach".to_owned()), 60));
+            let vital: (String, Option<f64>, Option<f64>) = exporter.connection()
+                .query_row("SELECT vital_sign, old_value, new_value FROM vitals", [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+                .unwrap();
+            assert_eq!(vital, ("HeartRate".to_owned(), None, Some(150.0)));
+            let violations: i64 = exporter.connection()
+                .query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(violations, 0);
+        }
+
+        #[test]
+        fn stage_outside_export() {
+            let mut exporter = SqliteExporter::in_memory().unwrap();
+            let mut action = points().remove(0);
+            if let ActionPlotPoint::Action(action) = &mut action {
+                action.location.stage = (0, String::new());
+            }
+
+            exporter.export_session("session-a", "session-a.csv", &SessionDate::default(), &[action]).unwrap();
+
+            let stage_number: Option<u32> = exporter.connection().query_row("SELECT stage_number FROM actions", [], |row| row.get(0)).unwrap();
+            assert_eq!(stage_number, None);
+        }
+
+        #[test]
+        fn reexport_replaces_session() {
+            let mut exporter = SqliteExporter::in_memory().unwrap();
+            exporter.export_session("session-a", "session-a.csv", &SessionDate::default(), &points()).unwrap();
+            exporter.export_session("session-b", "session-b.csv", &SessionDate::default(), &points()).unwrap();
+
+            exporter.export_session("session-a", "logs/session-a.csv", &SessionDate::default(), &points()[..1]).unwrap();
+
+            assert_eq!(count(&exporter, "sessions"), 2);
+            assert_eq!(count(&exporter, "stages"), 1);
+            assert_eq!(count(&exporter, "events"), 4);
+            let source: String = exporter.connection()
+                .query_row("SELECT source FROM sessions WHERE session_id = 'session-a'", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(source, "logs/session-a.csv");
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use crate::action_catalogue::{ActionCatalogue, ActionKind};
+use crate::compliance::merge_periods;
+use crate::detection::EPINEPHRINE;
+use crate::plot_structures::{ActionPlotPoint, CsvRowTime, PeriodType};
+use serde::Serialize;
+use std::borrow::Borrow;
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct HandsOffInterval {
+    pub start_seconds: u32,
+    pub end_seconds: u32,
+    pub duration_seconds: u32
+}
+
+/// ACLS quality indicators for a session, times are in seconds from the start of the session.
+#[derive(Debug, Default, PartialEq, Clone, Serialize)]
+pub struct SessionSummary {
+    pub session_seconds: u32,
+    pub total_cpr_seconds: u32,
+    pub cpr_fraction: Option<f64>,
+    pub time_to_first_shock: Option<u32>,
+    pub time_to_first_epinephrine: Option<u32>,
+    /// Longest pause in compressions between two CPR periods.
+    pub longest_hands_off: Option<HandsOffInterval>,
+    pub pulse_check_count: u32
+}
+
+fn first_time(current: Option<u32>, seconds: u32) -> Option<u32> {
+    Some(current.map_or(seconds, |current| current.min(seconds)))
+}
+
+fn longest_hands_off(cpr_periods: &mut [(u32, u32)]) -> Option<HandsOffInterval> {
+    cpr_periods.sort();
+    let mut compressions_end = cpr_periods.first()?.1;
+    let mut longest: Option<HandsOffInterval> = None;
+    for &(start, end) in cpr_periods.iter().skip(1) {
+        let duration = start.saturating_sub(compressions_end);
+        if duration > 0 && longest.as_ref().is_none_or(|longest| duration > longest.duration_seconds) {
+            longest = Some(HandsOffInterval { start_seconds: compressions_end, end_seconds: start, duration_seconds: duration });
+        }
+        compressions_end = compressions_end.max(end);
+    }
+    longest
+}
+
+/// Performed actions are counted whether or not they were flagged as erroneous.
+pub fn summarize<I>(points: I) -> SessionSummary
+where
+    I: IntoIterator,
+    I::Item: Borrow<ActionPlotPoint>,
+{
+    summarize_with_catalogue(points, &ActionCatalogue::builtin())
+}
+
+/// Shocks, epinephrine and pulse checks are the actions of these kinds in `catalogue`.
+pub fn summarize_with_catalogue<I>(points: I, catalogue: &ActionCatalogue) -> SessionSummary
+where
+    I: IntoIterator,
+    I::Item: Borrow<ActionPlotPoint>,
+{
+    let mut summary = SessionSummary::default();
+    let mut session_seconds = 0;
+    let mut cpr_periods = vec![];
+    let mut track = |timestamp: &CsvRowTime| {
+        session_seconds = session_seconds.max(timestamp.total_seconds);
+        timestamp.total_seconds
+    };
+
+    for point in points {
+        let (seconds, name, shock_value) = match point.borrow() {
+            ActionPlotPoint::Action(action) => (track(&action.location.timestamp), &action.name, &action.shock_value),
+            ActionPlotPoint::Error(error) => (track(&error.location.timestamp), &error.name, &error.shock_value),
+            ActionPlotPoint::Period(period_type, start, end) => {
+                let period = (track(&start.timestamp), track(&end.timestamp));
+                if *period_type == PeriodType::CPR {
+                    cpr_periods.push(period);
+                }
+                continue;
+            }
+            ActionPlotPoint::Stage(stage_period) => { track(&stage_period.end); continue; }
+            ActionPlotPoint::MissedAction(missed) => { track(&missed.location.timestamp); continue; }
+            ActionPlotPoint::Vital(sample) => { track(&sample.location.timestamp); continue; }
+            ActionPlotPoint::Speech(speech) => { track(&speech.location.timestamp); continue; }
+            ActionPlotPoint::RuleEvaluation(evaluation) => { track(&evaluation.location.timestamp); continue; }
+            ActionPlotPoint::ProtocolDeviation(deviation) => { track(&deviation.location.timestamp); continue; }
+            ActionPlotPoint::Questionnaire(_) => continue,
+        };
+
+        if catalogue.is_kind(name, shock_value, ActionKind::Shock) {
+            summary.time_to_first_shock = first_time(summary.time_to_first_shock, seconds);
+        }
+        if catalogue.gives_drug(name, shock_value, EPINEPHRINE) {
+            summary.time_to_first_epinephrine = first_time(summary.time_to_first_epinephrine, seconds);
+        }
+        if catalogue.is_kind(name, shock_value, ActionKind::PulseCheck) {
+            summary.pulse_check_count += 1;
+        }
+    }
+
+    summary.session_seconds = session_seconds;
+    // Overlapping periods are counted once.
+    summary.total_cpr_seconds = merge_periods(&cpr_periods).iter().map(|(start, end)| end.saturating_sub(*start)).sum();
+    if summary.session_seconds > 0 {
+        summary.cpr_fraction = Some(summary.total_cpr_seconds as f64 / summary.session_seconds as f64);
+    }
+    summary.longest_hands_off = longest_hands_off(&mut cpr_periods);
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{action, cpr, shock};
+
+    mod summarize {
+        use super::*;
+
+        #[test]
+        fn quality_indicators() {
+            let points = vec![
+                action(10, "Pulse Check", "Pulse Check"),
+                cpr(20, 80),
+                shock(85, "Defib (Unsynchronized Shock)"),
+                action(90, "Select Epinephrine", "Medication"),
+                cpr(95, 150),
+                action(155, "Pulse Check", "Pulse Check"),
+                shock(160, "Synchronized Shock"),
+                cpr(170, 200),
+            ];
+
+            let summary = summarize(&points);
+
+            assert_eq!(summary, SessionSummary {
+                session_seconds: 200,
+                total_cpr_seconds: 145,
+                cpr_fraction: Some(0.725),
+                time_to_first_shock: Some(85),
+                time_to_first_epinephrine: Some(90),
+                longest_hands_off: Some(HandsOffInterval { start_seconds: 150, end_seconds: 170, duration_seconds: 20 }),
+                pulse_check_count: 2,
+            });
+        }
+
+        #[test]
+        fn overlapping_cpr_periods() {
+            let summary = summarize(vec![cpr(10, 50), cpr(40, 60), cpr(65, 70)]);
+
+            assert_eq!(summary.total_cpr_seconds, 55);
+            assert_eq!(summary.longest_hands_off, Some(HandsOffInterval { start_seconds: 60, end_seconds: 65, duration_seconds: 5 }));
+        }
+
+        #[test]
+        fn scenario_catalogue() {
+            let catalogue = ActionCatalogue::from_toml_str(r#"
+                [[action]]
+                name = "Cardiovert"
+                kind = "shock"
+
+                [[action]]
+                name = "Push Adrenaline"
+                kind = "medication"
+                drug = "epinephrine"
+            "#).unwrap();
+            let points = vec![action(30, "Pulse Check", "Pulse Check"), shock(40, "Cardiovert"), action(50, "Push Adrenaline", "Push Adrenaline")];
+
+            let summary = summarize_with_catalogue(&points, &catalogue);
+
+            assert_eq!(summary.time_to_first_shock, Some(40));
+            assert_eq!(summary.time_to_first_epinephrine, Some(50));
+            assert_eq!(summary.pulse_check_count, 0);
+        }
+
+        #[test]
+        fn empty_session() {
+            let summary = summarize(Vec::<ActionPlotPoint>::new());
+
+            assert_eq!(summary, SessionSummary::default());
+            assert_eq!(summary.cpr_fraction, None);
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::plot_structures::{Action, ActionPlotPoint, CsvRowTime, PeriodType, PlotLocation};
+use crate::session_date::SessionDate;
+use chrono::NaiveDate;
+
+/// Date of the multiplayer test timelines.
+pub(crate) fn session_date() -> SessionDate {
+    SessionDate::new(NaiveDate::from_ymd_opt(2024, 9, 18).unwrap())
+}
+
+pub(crate) fn time(total_seconds: u32) -> CsvRowTime {
+    CsvRowTime::new(total_seconds, &session_date())
+}
+
+/// Location in the first stage of the test timelines.
+pub(crate) fn location(total_seconds: u32) -> PlotLocation {
+    stage_location(total_seconds, (1, "V-Tach 2D"))
+}
+
+pub(crate) fn stage_location(total_seconds: u32, stage: (u32, &str)) -> PlotLocation {
+    PlotLocation { timestamp: time(total_seconds), stage: (stage.0, stage.1.to_owned()) }
+}
+
+pub(crate) fn action(total_seconds: u32, name: &str, action_category: &str) -> ActionPlotPoint {
+    ActionPlotPoint::Action(Action {
+        location: location(total_seconds),
+        name: name.to_owned(),
+        action_category: action_category.to_owned(),
+        shock_value: String::new(),
+        username: "umich1".to_owned(),
+    })
+}
+
+/// A 200J shock of the catalogue action `name`.
+pub(crate) fn shock(total_seconds: u32, name: &str) -> ActionPlotPoint {
+    ActionPlotPoint::Action(Action {
+        location: location(total_seconds),
+        name: format!("{} 200J", name),
+        action_category: name.to_owned(),
+        shock_value: "200J".to_owned(),
+        username: "umich1".to_owned(),
+    })
+}
+
+pub(crate) fn cpr(start: u32, end: u32) -> ActionPlotPoint {
+    ActionPlotPoint::Period(PeriodType::CPR, location(start), location(end))
+}
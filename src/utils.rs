@@ -1,14 +1,12 @@
 use std::string::ToString;
 pub(crate) fn normalize_whitespace(input: &str) -> String {
     input
-        .trim()
         .split_whitespace()
         .collect::<Vec<&str>>()
         .join(" ")
 }
 pub(crate) fn capitalize_words(input: &str) -> String {
     input
-        .trim()
         .split_whitespace()
         .map(|word| {
             if word.chars().all(|c| c.is_numeric() || c.is_uppercase()) {
@@ -27,7 +25,6 @@ pub(crate) fn capitalize_words(input: &str) -> String {
         .collect::<Vec<String>>()
         .join(" ")
         .replace(" ( ", " (")
-        .replace(") ", ") ")
         .replace(" )", ")")
 }
 #[cfg(test)]
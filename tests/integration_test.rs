@@ -1,7 +1,18 @@
-use mteam_dashboard_action_processor::process_csv;
+use mteam_dashboard_action_processor::action_catalogue::ActionCatalogue;
+use mteam_dashboard_action_processor::compliance::with_protocol_deviations;
+use mteam_dashboard_action_processor::participants::summarize_participants;
+use mteam_dashboard_action_processor::plot_structures::{ActionPlotPoint, PeriodType, ProtocolRule, QuestionnaireItem, RatingScale, Rhythm, RhythmFamily, RuleEvaluation, Severity, SpeechEvent, StagePeriod, VitalSample, VitalSign, VitalValue};
+use mteam_dashboard_action_processor::plotly_figure::PlotlyFigure;
+use mteam_dashboard_action_processor::summary::{summarize, HandsOffInterval};
+use mteam_dashboard_action_processor::{process_csv, process_tail, try_process_csv_numbered, MarkerMatching, ProcessorConfig, ProcessorError, SessionDate, TailOptions};
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
+use std::sync::Arc;
+
+fn config(max_rows_to_check: usize) -> ProcessorConfig {
+    ProcessorConfig::builder().max_rows_to_check(max_rows_to_check).build()
+}
 
 #[test]
 fn test_stream_csv_with_errors() {
@@ -14,7 +25,7 @@ fn test_stream_csv_with_errors() {
     let max_rows_to_check = 10;
 
     // Run the stream_csv_with_errors function
-    let results: Vec<_> = process_csv(reader, max_rows_to_check).collect();
+    let results: Vec<_> = process_csv(reader, &config(max_rows_to_check)).collect();
 
     // Set expectations for the results
     assert!(!results.is_empty(), "No rows were processed");
@@ -31,3 +42,626 @@ fn test_stream_csv_with_errors() {
         }
     }
 }
+
+const HEADER: &str = "Time Stamp[Hr:Min:Sec],Action/Vital Name,SubAction Time[Min:Sec],SubAction Name,Score,Old Value,New Value,Username,Speech Command\n";
+
+#[test]
+fn test_end_of_stream_flushes_actions_and_open_periods() {
+    let csv = format!(
+        "{}{}{}",
+        HEADER,
+        "0:0:10,(1)V-Tach 2D(action),0:10,Enter CPR,100,,,umich1\n",
+        "0:0:50,(1)V-Tach 2D(action),0:50,Pulse Check,100,,,umich1\n"
+    );
+
+    let results: Vec<_> = process_csv(csv.as_bytes(), &config(5)).collect();
+
+    assert_eq!(results.len(), 3, "Unexpected points: {:?}", results);
+    match &results[0] {
+        Ok(ActionPlotPoint::Action(action)) => assert_eq!(action.name, "Pulse Check"),
+        other => panic!("Expected buffered action to be flushed, got {:?}", other),
+    }
+    match &results[1] {
+        Ok(ActionPlotPoint::Period(PeriodType::CPR, start, end)) => {
+            assert_eq!(start.timestamp.total_seconds, 10);
+            assert_eq!(end.timestamp.total_seconds, 50);
+        }
+        other => panic!("Expected open CPR period to be closed, got {:?}", other),
+    }
+    match &results[2] {
+        Ok(ActionPlotPoint::Stage(stage_period)) => {
+            assert_eq!(stage_period.start.total_seconds, 0);
+            assert_eq!(stage_period.end.total_seconds, 50);
+            assert_eq!(stage_period.stage, (1, "V-Tach 2D".to_owned()));
+        }
+        other => panic!("Expected open stage period to be closed, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_end_of_stream_reports_unresolved_error_marker() {
+    let csv = format!(
+        "{}{}",
+        HEADER,
+        "0:0:5,EKG,Error,Action-Should-Not-Be-Performed,Action-Was-Performed,Error-Triggered,umich3,(1)V-Tach 2D(action),Don't interrupt CPR for an EKG.,,,,,,\n"
+    );
+
+    let errors: Vec<ProcessorError> = process_csv(csv.as_bytes(), &config(5)).filter_map(Result::err).collect();
+
+    assert_eq!(errors, vec![ProcessorError::UnresolvedErrorMarker { row_number: 2, marker: "EKG".to_owned() }]);
+}
+
+#[test]
+fn test_closed_stage_is_not_reported_twice() {
+    let csv = format!(
+        "{}{}{}",
+        HEADER,
+        "0:0:35,(1)V-Tach 2D(action),0:35,Pulse Check,100,,,umich1\n",
+        "0:1:55,(1)V-Tach 2D(action)\n"
+    );
+
+    let stage_periods = process_csv(csv.as_bytes(), &config(5))
+        .filter(|result| matches!(result, Ok(ActionPlotPoint::Stage(_))))
+        .count();
+
+    assert_eq!(stage_periods, 1);
+}
+
+#[test]
+fn test_speech_after_stage_boundary_is_in_next_stage() {
+    let csv = format!(
+        "{}{}{}{}{}{}{}",
+        HEADER,
+        "0:0:35,(1)V-Tach 2D(action),0:35,Pulse Check,100,,,umich1\n",
+        "0:1:55,(1)V-Tach 2D(action)\n",
+        "0:1:55,pulse_check,Error,Action-Should-Be-Performed,Action-Was-Performed,Error-Did-Not-Trigger,NA,(1)V-Tach 2D(action)\n",
+        "0:2:5,umich1(speech),,,,,,umich1,\"Start CPR.\"\n",
+        "0:2:20,(2)V-Tach 2A.1(action),2:20,Begin CPR,100,,,umich1\n",
+        "0:2:30,umich1(speech),,,,,,umich1,\"Check pulse.\"\n"
+    );
+
+    let stages: Vec<(u32, String)> = process_csv(csv.as_bytes(), &config(5))
+        .filter_map(|result| match result {
+            Ok(ActionPlotPoint::Speech(event)) => Some(event.location.stage),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(stages, vec![(2, String::new()), (2, "V-Tach 2A.1".to_owned())]);
+}
+
+#[test]
+fn test_numbered_results_keep_row_of_origin() {
+    let csv = format!(
+        "{}{}{}{}",
+        HEADER,
+        "0:0:10,(1)V-Tach 2D(action),0:10,Pulse Check,100,,,umich1\n",
+        "0:0:12,(1)V-Tach 2D(action),0:12,Order EKG,100,,,umich1\n",
+        "0:x:14,(1)V-Tach 2D(action),0:14,Order EKG,100,,,umich1\n"
+    );
+
+    let results: Vec<_> = try_process_csv_numbered(csv.as_bytes(), &config(1))
+        .unwrap()
+        .map(|(row_number, result)| (row_number, result.map(|point| point.kind()).map_err(|e| e.row_number())))
+        .collect();
+
+    assert_eq!(results, vec![
+        (Some(2), Ok("Action")),
+        (Some(4), Err(Some(4))),
+        (Some(3), Ok("Action")),
+        (None, Ok("Stage")),
+    ]);
+}
+
+#[test]
+fn test_row_numbers_are_csv_lines() {
+    let csv = format!(
+        "{}{}{}",
+        HEADER.replace('\n', "\r\n"),
+        "\r\n0:0:10,(1)V-Tach 2D(action),0:10,Pulse Check,100,,,umich1\r\n\r\n",
+        "0:x:14,(1)V-Tach 2D(action),0:14,Order EKG,100,,,umich1\r\n"
+    );
+
+    let rows: Vec<_> = try_process_csv_numbered(csv.as_bytes(), &config(10)).unwrap().map(|(row_number, _)| row_number).collect();
+
+    assert_eq!(rows, vec![Some(5), Some(3), None]);
+}
+
+#[test]
+fn test_questionnaire_row_numbers_from_sample_csv() {
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+
+    let rows: Vec<_> = try_process_csv_numbered(BufReader::new(file), &config(10))
+        .unwrap()
+        .filter(|(_, result)| matches!(result, Ok(ActionPlotPoint::Questionnaire(_))))
+        .map(|(row_number, _)| row_number)
+        .collect();
+
+    assert_eq!(rows, vec![Some(685), Some(686), Some(687)]);
+}
+
+#[test]
+fn test_stage_periods_from_sample_csv() {
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+
+    let stages: Vec<StagePeriod> = process_csv(BufReader::new(file), &config(10))
+        .filter_map(|result| match result {
+            Ok(ActionPlotPoint::Stage(stage_period)) => Some(stage_period),
+            _ => None,
+        })
+        .collect();
+
+    let summary: Vec<_> = stages
+        .iter()
+        .map(|stage| (stage.stage.0, stage.stage.1.as_str(), stage.start.total_seconds, stage.duration_seconds, stage.rhythm_family.clone()))
+        .collect();
+    assert_eq!(summary, vec![
+        (1, "V-Tach 2D", 0, 115, Some(RhythmFamily::VTach)),
+        (2, "V-Tach 2A.1", 115, 161, Some(RhythmFamily::VTach)),
+        (3, "V-Tach 2B.1", 276, 75, Some(RhythmFamily::VTach)),
+        (4, "Asystole 1D No.1", 351, 19, Some(RhythmFamily::Asystole)),
+        (5, "V-Fib 4C.1 - AMIO", 370, 125, Some(RhythmFamily::VFib)),
+        (6, "ROSC 5B - Stemi", 495, 161, Some(RhythmFamily::Rosc)),
+    ]);
+    // Every stage starts where the previous one ended.
+    assert!(stages.windows(2).all(|pair| pair[0].end == pair[1].start));
+}
+
+#[test]
+fn test_vital_samples_from_sample_csv() {
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+
+    let vitals: Vec<VitalSample> = process_csv(BufReader::new(file), &config(10))
+        .filter_map(|result| match result {
+            Ok(ActionPlotPoint::Vital(sample)) => Some(sample),
+            _ => None,
+        })
+        .collect();
+
+    let first_heart_rate = vitals.iter().find(|sample| sample.vital_sign == VitalSign::HeartRate).expect("No heart rate samples");
+    assert_eq!(first_heart_rate.location.timestamp.total_seconds, 49);
+    assert_eq!(first_heart_rate.old_value, VitalValue::Measured(178.0));
+    assert_eq!(first_heart_rate.new_value, VitalValue::Measured(192.0));
+    assert!(vitals.iter().any(|sample| sample.vital_sign == VitalSign::BloodPressure && sample.old_value == VitalValue::NotMeasurable));
+    assert_eq!(vitals.iter().filter(|sample| sample.vital_sign == VitalSign::EndTidalCO2).count(), 27);
+}
+
+#[test]
+fn test_rhythm_segments_from_sample_csv() {
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+
+    let heart_rhythms: Vec<(Rhythm, u32, u32)> = process_csv(BufReader::new(file), &config(10))
+        .filter_map(|result| match result {
+            Ok(ActionPlotPoint::Period(PeriodType::HrWaveform(rhythm), start, end)) => {
+                Some((rhythm, start.timestamp.total_seconds, end.timestamp.total_seconds))
+            }
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(heart_rhythms.first(), Some(&(Rhythm::NormalSinus, 0, 49)));
+    assert_eq!(heart_rhythms.get(1), Some(&(Rhythm::VTach, 49, 140)));
+    // Segments are contiguous up to the end of the data.
+    for pair in heart_rhythms.windows(2) {
+        assert_eq!(pair[0].2, pair[1].1);
+    }
+    assert_eq!(heart_rhythms.last().map(|segment| segment.2), Some(670));
+}
+
+#[test]
+fn test_speech_events_from_sample_csv() {
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+
+    let speech: Vec<SpeechEvent> = process_csv(BufReader::new(file), &config(10))
+        .filter_map(|result| match result {
+            Ok(ActionPlotPoint::Speech(event)) => Some(event),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(speech[0].speaker, "umich4");
+    assert_eq!(speech[0].transcript, "BJP.");
+    assert!(!speech[0].no_match);
+    assert!(speech[1].no_match);
+    // Stage 1 ends with the boundary row at 115s, the speech after it is in stage 2 before any row names that stage.
+    let first_in_stage_two = speech.iter().find(|event| event.location.stage.0 == 2).expect("No speech in stage 2");
+    assert_eq!(first_in_stage_two.location.timestamp.total_seconds, 115);
+    assert_eq!(first_in_stage_two.location.stage, (2, String::new()));
+    assert!(speech.iter().any(|event| event.location.stage == (2, "V-Tach 2A.1".to_owned())));
+    assert!(speech.iter().all(|event| event.location.stage.0 != 1 || event.location.timestamp.total_seconds <= 115));
+}
+
+#[test]
+fn test_questionnaire_from_sample_csv() {
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+
+    let results: Vec<_> = process_csv(BufReader::new(file), &config(10)).collect();
+    let questionnaire: Vec<&QuestionnaireItem> = results
+        .iter()
+        .filter_map(|result| match result {
+            Ok(ActionPlotPoint::Questionnaire(item)) => Some(item),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(questionnaire.len(), 3);
+    assert_eq!(questionnaire[0].question, "How effectively did your team communicate?");
+    assert_eq!(questionnaire[0].rating, 6);
+    assert_eq!(questionnaire[0].scale, RatingScale { min: 0, max: 7 });
+    assert_eq!(questionnaire[1].rating, 5);
+    assert!(!results.iter().any(|result| matches!(result, Err(ProcessorError::RowDeserialization { .. }))));
+}
+
+#[test]
+fn test_rule_evaluations_from_sample_csv() {
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+
+    let evaluations: Vec<RuleEvaluation> = process_csv(BufReader::new(file), &config(10))
+        .filter_map(|result| match result {
+            Ok(ActionPlotPoint::RuleEvaluation(evaluation)) => Some(evaluation),
+            _ => None,
+        })
+        .collect();
+
+    let sync_defib = evaluations.iter().find(|evaluation| evaluation.rule_id == "sync_defib_100+").expect("Rule is missing");
+    assert!(!sync_defib.triggered);
+    assert_eq!(sync_defib.severity, Severity::CriticalError);
+    assert_eq!(sync_defib.outcome, "Action-Was-Performed");
+    assert_eq!(sync_defib.location.stage, (1, "V-Tach 2D".to_owned()));
+    assert!(evaluations.iter().any(|evaluation| evaluation.triggered));
+    assert!(evaluations.iter().all(|evaluation| evaluation.location.stage.0 > 0));
+}
+
+#[test]
+fn test_error_severity_from_sample_csv() {
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+
+    let results: Vec<_> = process_csv(BufReader::new(file), &config(10)).filter_map(Result::ok).collect();
+
+    let ekg_error = results.iter().find_map(|point| match point {
+        ActionPlotPoint::Error(error) if error.error_info.action_rule == "EKG" => Some(error),
+        _ => None,
+    }).expect("EKG error is missing");
+    assert_eq!(ekg_error.name, "Order EKG");
+    assert_eq!(ekg_error.error_info.severity, Severity::Error);
+    assert_eq!(ekg_error.error_info.expectation, "Action-Should-Not-Be-Performed");
+    assert_eq!(ekg_error.error_info.violation, "Action-Was-Performed");
+    assert_eq!(ekg_error.error_info.advice, "Good CPR saves lives! Don't interrupt CPR for an EKG.");
+
+    let lab_check = results.iter().find_map(|point| match point {
+        ActionPlotPoint::MissedAction(missed) if missed.name == "labcheck_anytime" => Some(missed),
+        _ => None,
+    }).expect("Missed lab check is missing");
+    assert_eq!(lab_check.error_info.severity, Severity::Warning);
+}
+
+#[test]
+fn test_plotly_figure_from_sample_csv() {
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+
+    let figure = PlotlyFigure::from_points(process_csv(BufReader::new(file), &config(10)).filter_map(Result::ok));
+
+    let errors = figure.data.iter().find(|trace| trace.name == "Errors").expect("Errors trace is missing");
+    assert_eq!(errors.x.len(), 3);
+    let missed = figure.data.iter().find(|trace| trace.name == "Missed Actions").expect("Missed actions trace is missing");
+    assert_eq!(missed.x.len(), 28);
+    let actions: usize = figure.data.iter()
+        .filter(|trace| trace.name != "Errors" && trace.name != "Missed Actions")
+        .map(|trace| trace.x.len())
+        .sum();
+    assert_eq!(actions, 34);
+    assert_eq!(figure.layout.shapes.iter().filter(|shape| shape.name == "CPR").count(), 11);
+    assert_eq!(figure.layout.shapes.len(), 17);
+    assert!(figure.to_json().unwrap().starts_with("{\"data\":["));
+}
+
+#[test]
+fn test_configured_cpr_markers() {
+    let csv = format!(
+        "{}{}{}",
+        HEADER,
+        "0:0:10,(1)V-Tach 2D(action),0:10,Start Compressions,100,,,umich1\n",
+        "0:0:40,(1)V-Tach 2D(action),0:40,Pause Compressions,100,,,umich1\n"
+    );
+    let config = ProcessorConfig::builder()
+        .cpr_start_markers(["Start Compressions"])
+        .cpr_end_markers(["Pause Compressions"])
+        .build();
+
+    let cpr_periods: Vec<_> = process_csv(csv.as_bytes(), &config)
+        .filter_map(|result| match result {
+            Ok(ActionPlotPoint::Period(PeriodType::CPR, start, end)) => Some((start.timestamp.total_seconds, end.timestamp.total_seconds)),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(cpr_periods, vec![(10, 40)]);
+}
+
+#[test]
+fn test_forward_only_marker_matching_ignores_earlier_action() {
+    let csv = format!(
+        "{}{}{}",
+        HEADER,
+        "0:0:4,(1)V-Tach 2D(action),0:04,Order EKG,100,,,umich3\n",
+        "0:0:5,EKG,Error,Action-Should-Not-Be-Performed,Action-Was-Performed,Error-Triggered,umich3,(1)V-Tach 2D(action),Don't interrupt CPR for an EKG.,,,,,,\n"
+    );
+    let erroneous_actions = |config: &ProcessorConfig| {
+        process_csv(csv.as_bytes(), config)
+            .filter(|result| matches!(result, Ok(ActionPlotPoint::Error(_))))
+            .count()
+    };
+
+    assert_eq!(erroneous_actions(&config(5)), 1);
+    assert_eq!(erroneous_actions(&ProcessorConfig::builder().marker_matching(MarkerMatching::ForwardOnly).build()), 0);
+}
+
+#[test]
+fn test_no_action_lost_without_buffer() {
+    let performed_actions = |max_rows_to_check| {
+        let file = File::open("timeline-multiplayer-09182024.csv").expect("Failed to open the CSV file");
+        process_csv(BufReader::new(file), &config(max_rows_to_check))
+            .filter(|result| matches!(result, Ok(ActionPlotPoint::Action(_)) | Ok(ActionPlotPoint::Error(_))))
+            .count()
+    };
+
+    assert_eq!(performed_actions(0), performed_actions(5));
+    assert_eq!(performed_actions(5), 37);
+}
+
+#[test]
+fn test_structured_errors_carry_row_numbers() {
+    let csv = format!(
+        "{}{}{}{}",
+        HEADER,
+        "0:0:5,Pulse Check(action),,,,,,umich1,,\n",
+        "0:x:9,Pulse Check(action),,,,,,umich1,,\n",
+        "0:0:12,HR(vital),,,,high,120,,,\n"
+    );
+
+    let errors: Vec<ProcessorError> = process_csv(csv.as_bytes(), &config(5)).filter_map(Result::err).collect();
+
+    assert_eq!(errors, vec![
+        ProcessorError::TimeParse { row_number: 3, value: "0:x:9".to_owned() },
+        ProcessorError::InvalidVitalValue {
+            row_number: 4,
+            name: "HR(vital)".to_owned(),
+            old_value: "high".to_owned(),
+            new_value: "120".to_owned(),
+        },
+    ]);
+}
+
+#[test]
+fn test_header_mismatch_error() {
+    let errors: Vec<ProcessorError> = process_csv("Name,Score\nPulse Check,1\n".as_bytes(), &config(5)).filter_map(Result::err).collect();
+
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(&errors[0], ProcessorError::HeaderMismatch { actual, .. } if actual == &vec!["Name".to_owned(), "Score".to_owned()]));
+}
+
+#[tokio::test]
+async fn test_process_url_stream() {
+    use futures::StreamExt;
+    use mteam_dashboard_action_processor::csv_stream::process_url_stream;
+
+    let sample = std::fs::read("tests/data/sample.csv").expect("Failed to read the CSV file");
+    let mut server = mockito::Server::new_async().await;
+    let mock = server.mock("GET", "/sample.csv").with_status(200).with_body(&sample).create_async().await;
+
+    let points: Vec<_> = process_url_stream(&format!("{}/sample.csv", server.url()), &config(5))
+        .await
+        .expect("Failed to open the stream")
+        .collect()
+        .await;
+
+    mock.assert_async().await;
+    let expected: Vec<_> = process_csv(sample.as_slice(), &config(5)).collect();
+    assert_eq!(points, expected);
+}
+
+#[tokio::test]
+async fn test_process_url_stream_http_status() {
+    use mteam_dashboard_action_processor::csv_stream::process_url_stream;
+
+    let mut server = mockito::Server::new_async().await;
+    let url = format!("{}/missing.csv", server.url());
+    server.mock("GET", "/missing.csv").with_status(404).create_async().await;
+
+    let result = process_url_stream(&url, &config(5)).await;
+
+    assert_eq!(result.err(), Some(ProcessorError::HttpStatus { url, status: 404 }));
+}
+
+fn tail_test_options() -> TailOptions {
+    TailOptions {
+        idle_timeout: std::time::Duration::from_millis(400),
+        poll_interval: std::time::Duration::from_millis(10),
+    }
+}
+
+fn append_to(path: &Path, content: &str) {
+    use std::io::Write;
+    let mut file = std::fs::OpenOptions::new().append(true).create(true).open(path).unwrap();
+    file.write_all(content.as_bytes()).unwrap();
+}
+
+#[test]
+fn test_process_tail_follows_growing_file() {
+    let sample = std::fs::read_to_string("tests/data/sample.csv").expect("Failed to read the CSV file");
+    let split_at = sample.len() / 2;
+    let path = std::env::temp_dir().join(format!("tail_growing_{}.csv", std::process::id()));
+    append_to(&path, &sample[..split_at]);
+
+    let writer_path = path.clone();
+    let rest = sample[split_at..].to_owned();
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        append_to(&writer_path, &rest);
+    });
+    let points: Vec<_> = process_tail(path.to_str().unwrap(), &config(5), tail_test_options()).collect();
+    writer.join().unwrap();
+
+    let expected: Vec<_> = process_csv(sample.as_bytes(), &config(5)).collect();
+    assert_eq!(points, expected);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_process_tail_restarts_after_truncation() {
+    let path = std::env::temp_dir().join(format!("tail_truncated_{}.csv", std::process::id()));
+    let speech_row = "0:0:5,umich1(speech),,,,,,umich1,\"check pulse\"\n";
+    append_to(&path, &format!("{}{}{}", HEADER, speech_row, speech_row));
+
+    let writer_path = path.clone();
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        std::fs::OpenOptions::new().write(true).open(&writer_path).unwrap().set_len(0).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        append_to(&writer_path, &format!("{}{}", HEADER, speech_row));
+    });
+    let results: Vec<_> = process_tail(path.to_str().unwrap(), &config(5), tail_test_options()).collect();
+    writer.join().unwrap();
+
+    let speech_points = results.iter().filter(|result| matches!(result, Ok(ActionPlotPoint::Speech(_)))).count();
+    let errors: Vec<_> = results.iter().filter_map(|result| result.as_ref().err()).collect();
+    assert_eq!(speech_points, 3);
+    assert_eq!(errors, vec![&ProcessorError::SourceTruncated { path: path.to_str().unwrap().to_owned() }]);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_session_summary_from_sample_csv() {
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+
+    let summary = summarize(process_csv(BufReader::new(file), &config(10)).filter_map(Result::ok));
+
+    assert_eq!(summary.session_seconds, 670);
+    assert_eq!(summary.total_cpr_seconds, 266);
+    assert!((summary.cpr_fraction.unwrap() - 266.0 / 670.0).abs() < 1e-9);
+    assert_eq!(summary.pulse_check_count, 9);
+    assert_eq!(summary.longest_hands_off, Some(HandsOffInterval { start_seconds: 252, end_seconds: 286, duration_seconds: 34 }));
+    assert_eq!(summary.time_to_first_shock, Some(114));
+    assert_eq!(summary.time_to_first_epinephrine, Some(169));
+}
+
+#[test]
+fn test_protocol_deviations_from_sample_csv() {
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+
+    let deviations: Vec<_> = with_protocol_deviations(process_csv(BufReader::new(file), &config(10)))
+        .filter_map(|result| match result {
+            Ok(ActionPlotPoint::ProtocolDeviation(deviation)) => Some(deviation),
+            _ => None,
+        })
+        .collect();
+
+    let count = |rule: ProtocolRule| deviations.iter().filter(|deviation| deviation.rule == rule).count();
+    assert_eq!(deviations.len(), 12);
+    assert_eq!(count(ProtocolRule::CprAfterShock), 4);
+    assert_eq!(count(ProtocolRule::PulseCheckTooLong), 5);
+    assert_eq!(count(ProtocolRule::EpinephrineTooSoon), 2);
+    assert_eq!(count(ProtocolRule::AmiodaroneBeforeThirdShock), 1);
+    let amiodarone = deviations.iter().find(|deviation| deviation.rule == ProtocolRule::AmiodaroneBeforeThirdShock).unwrap();
+    assert_eq!(amiodarone.location.timestamp.total_seconds, 213);
+    assert_eq!(amiodarone.location.stage.1, "V-Tach 2A.1");
+}
+
+#[test]
+fn test_participant_breakdown_from_sample_csv() {
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+
+    let participants = summarize_participants(process_csv(BufReader::new(file), &config(10)).filter_map(Result::ok));
+
+    let usernames: Vec<_> = participants.iter().map(|participant| participant.username.as_str()).collect();
+    assert_eq!(usernames, vec!["umich1", "umich2", "umich3", "umich4"]);
+    let umich1 = &participants[0];
+    assert_eq!(umich1.actions_by_category.get("Pulse Check"), Some(&9));
+    assert_eq!(umich1.errors_by_severity.get("Error"), Some(&3));
+    assert_eq!(umich1.speech_utterances, 61);
+    assert_eq!(umich1.unrecognized_utterances, 22);
+    let umich2 = &participants[1];
+    assert_eq!(umich2.missed_actions, 26);
+    assert_eq!(umich2.errors_by_severity.get("Warning"), Some(&25));
+    assert_eq!(umich2.actions_by_category.get("Medication"), Some(&8));
+    let umich3 = &participants[2];
+    assert_eq!(umich3.actions_by_category.get("Order EKG"), Some(&2));
+    assert!(umich3.no_match_rate.unwrap() < 0.05);
+    assert!(participants[3].errors_by_severity.is_empty());
+}
+
+#[test]
+fn test_session_date_anchors_timestamps() {
+    let session_date: SessionDate = "2024-09-18 -04:00".parse().unwrap();
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+
+    let points: Vec<_> = process_csv(BufReader::new(file), &ProcessorConfig::builder().session_date(session_date).build()).filter_map(Result::ok).collect();
+
+    let last_speech = points
+        .iter()
+        .rev()
+        .find_map(|point| match point {
+            ActionPlotPoint::Speech(speech) => Some(speech),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(last_speech.location.timestamp.total_seconds, 670);
+    assert_eq!(last_speech.location.timestamp.date_string, "2024-09-18 00:11:10");
+    assert_eq!(last_speech.location.timestamp.datetime.to_rfc3339(), "2024-09-18T00:11:10-04:00");
+    let stage_start = points
+        .iter()
+        .find_map(|point| match point {
+            ActionPlotPoint::Stage(stage_period) => Some(&stage_period.start),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(stage_start.date_string, "2024-09-18 00:00:00");
+    let period_starts: Vec<_> = points
+        .iter()
+        .filter_map(|point| match point {
+            ActionPlotPoint::Period(_, start, _) => Some(start),
+            _ => None,
+        })
+        .collect();
+    assert!(period_starts.iter().any(|start| start.timestamp.total_seconds == 0));
+    assert!(period_starts.iter().all(|start| start.timestamp.date_string.starts_with("2024-09-18 ")));
+
+    // Processing the same log again gives the same datetimes.
+    let again: Vec<_> = process_csv(BufReader::new(File::open("tests/data/sample.csv").unwrap()), &config(10)).filter_map(Result::ok).collect();
+    let once_more: Vec<_> = process_csv(BufReader::new(File::open("tests/data/sample.csv").unwrap()), &config(10)).filter_map(Result::ok).collect();
+    assert_eq!(again, once_more);
+}
+
+#[test]
+fn test_scenario_catalogue_reports_unknown_actions() {
+    let path = std::env::temp_dir().join(format!("scenario_catalogue_{}.toml", std::process::id()));
+    std::fs::write(&path, r#"
+        [[action]]
+        name = "Check Pulse"
+        aliases = ["Pulse Check"]
+        category = "Assessment"
+    "#).unwrap();
+    let catalogue = Arc::new(ActionCatalogue::load(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+
+    let results: Vec<_> = process_csv(BufReader::new(file), &ProcessorConfig::builder().catalogue(catalogue.clone()).build()).collect();
+
+    let pulse_checks = results
+        .iter()
+        .filter(|result| matches!(result, Ok(ActionPlotPoint::Action(action)) if action.name == "Check Pulse" && action.action_category == "Assessment"))
+        .count();
+    assert_eq!(pulse_checks, 9);
+    let points: Vec<_> = results.iter().filter_map(|result| result.as_ref().ok()).collect();
+    let unknown_actions = catalogue.unknown_actions(points.iter().copied());
+    assert!(unknown_actions.contains("Select Epinephrine"));
+    assert!(!unknown_actions.contains("Check Pulse"));
+    // Unknown actions are not errors of the log.
+    assert!(results.iter().all(Result::is_ok));
+    // Unknown actions are still plotted under their own name.
+    assert!(results.iter().any(|result| matches!(result, Ok(ActionPlotPoint::Action(action)) if action.action_category == "Select Epinephrine")));
+
+    let file = File::open("tests/data/sample.csv").expect("Failed to open the CSV file");
+    let points: Vec<_> = process_csv(BufReader::new(file), &config(10)).filter_map(Result::ok).collect();
+    assert!(ActionCatalogue::default().unknown_actions(&points).is_empty());
+}